@@ -0,0 +1,204 @@
+use anyhow::{anyhow, Result};
+use log::debug;
+
+use crate::providers::{
+    aws::{Aws, AwsCredentials},
+    env::AwsEnv,
+    metadata::{Ec2Imds, EcsContainer},
+    process::CredentialProcess,
+    provider::ProvideCredentials,
+    sso::AwsSso,
+};
+
+/// Credentials together with the name of the chain link that supplied them.
+#[derive(Debug)]
+pub struct ProvidedCredentials<T> {
+    pub credentials: T,
+    pub source: String,
+}
+
+/// Ordered list of providers, the first one returning credentials wins.
+pub struct CredentialsChain<'a, T> {
+    links: Vec<Box<dyn ProvideCredentials<T> + 'a>>,
+}
+
+impl<'a, T> Default for CredentialsChain<'a, T> {
+    fn default() -> Self {
+        CredentialsChain { links: vec![] }
+    }
+}
+
+impl<'a, T> CredentialsChain<'a, T> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_provider(mut self, provider: impl ProvideCredentials<T> + 'a) -> Self {
+        self.register(Box::new(provider));
+        self
+    }
+
+    pub fn register(&mut self, provider: Box<dyn ProvideCredentials<T> + 'a>) {
+        self.links.push(provider);
+    }
+
+    pub fn link_names(&self) -> Vec<&str> {
+        self.links.iter().map(|link| link.name()).collect()
+    }
+
+    pub fn provide(&self) -> Result<ProvidedCredentials<T>> {
+        for link in &self.links {
+            debug!("Trying credentials provider {}", link.name());
+            if let Some(credentials) = link
+                .provide()
+                .map_err(|e| e.context(format!("Credentials provider {} failed", link.name())))?
+            {
+                debug!("Credentials supplied by {}", link.name());
+                return Ok(ProvidedCredentials {
+                    credentials,
+                    source: link.name().to_string(),
+                });
+            }
+        }
+        Err(anyhow!(
+            "No credentials found, tried {}",
+            self.link_names().join(", ")
+        ))
+    }
+
+    /// Name and description of the first configured link, the one `provide` would try
+    /// to get credentials from. Nothing is run nor called.
+    pub fn describe(&self) -> Result<Option<(&str, String)>> {
+        for link in &self.links {
+            if let Some(description) = link
+                .describe()
+                .map_err(|e| e.context(format!("Credentials provider {} failed", link.name())))?
+            {
+                return Ok(Some((link.name(), description)));
+            }
+        }
+        Ok(None)
+    }
+}
+
+impl<'a> CredentialsChain<'a, AwsCredentials> {
+    /// env -> profile file -> sso -> credential_process -> container -> instance metadata,
+    /// like the aws sdk default chain. A named profile has to be resolved by one of the
+    /// profile links, it never falls back to the container or instance role.
+    pub fn aws_default(profile: Option<&'a str>, credentials_path: Option<String>) -> Self {
+        let chain = CredentialsChain::new()
+            .with_provider(AwsEnv::new())
+            .with_provider(Aws::new(profile, credentials_path.clone()))
+            .with_provider(AwsSso::new(profile, None, None))
+            .with_provider(CredentialProcess::new(profile, None, credentials_path));
+        match profile {
+            Some(_) => chain,
+            None => chain
+                .with_provider(EcsContainer::new())
+                .with_provider(Ec2Imds::new()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use anyhow::{anyhow, Result};
+
+    use crate::providers::provider::ProvideCredentials;
+
+    use super::CredentialsChain;
+
+    struct Static(&'static str, Option<&'static str>);
+
+    impl ProvideCredentials<String> for Static {
+        fn name(&self) -> &str {
+            self.0
+        }
+
+        fn provide(&self) -> Result<Option<String>> {
+            Ok(self.1.map(|value| value.to_string()))
+        }
+
+        fn describe(&self) -> Result<Option<String>> {
+            Ok(self.1.map(|value| format!("returns {}", value)))
+        }
+    }
+
+    struct Failing;
+
+    impl ProvideCredentials<String> for Failing {
+        fn name(&self) -> &str {
+            "failing"
+        }
+
+        fn provide(&self) -> Result<Option<String>> {
+            Err(anyhow!("broken"))
+        }
+
+        fn describe(&self) -> Result<Option<String>> {
+            Err(anyhow!("broken"))
+        }
+    }
+
+    #[test]
+    fn test_first_configured_link_wins() {
+        let chain = CredentialsChain::new()
+            .with_provider(Static("first", None))
+            .with_provider(Static("second", Some("second-value")))
+            .with_provider(Static("third", Some("third-value")));
+        let provided = chain.provide().expect("second link should provide");
+        assert_eq!(provided.credentials, "second-value");
+        assert_eq!(provided.source, "second");
+        assert_eq!(
+            chain.describe().expect("second link is configured"),
+            Some(("second", "returns second-value".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_failing_link_stops_chain() {
+        let chain = CredentialsChain::new()
+            .with_provider(Failing)
+            .with_provider(Static("second", Some("second-value")));
+        let result = chain.provide();
+        assert!(result.is_err());
+        assert_eq!(
+            result.unwrap_err().to_string(),
+            "Credentials provider failing failed"
+        );
+    }
+
+    #[test]
+    fn test_exhausted_chain() {
+        let mut chain = CredentialsChain::new().with_provider(Static("first", None));
+        chain.register(Box::new(Static("custom", None)));
+        let result = chain.provide();
+        assert!(result.is_err());
+        assert_eq!(
+            result.unwrap_err().to_string(),
+            "No credentials found, tried first, custom"
+        );
+        assert!(chain.describe().expect("nothing fails").is_none());
+    }
+
+    #[test]
+    fn test_aws_default_order() {
+        let chain = CredentialsChain::aws_default(None, None);
+        assert_eq!(
+            chain.link_names(),
+            vec![
+                "env",
+                "profile",
+                "sso",
+                "credential_process",
+                "container",
+                "instance"
+            ]
+        );
+        let chain = CredentialsChain::aws_default(Some("work"), None);
+        assert_eq!(
+            chain.link_names(),
+            vec!["env", "profile", "sso", "credential_process"]
+        );
+    }
+}
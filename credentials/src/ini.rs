@@ -0,0 +1,467 @@
+use std::{
+    collections::{HashMap, HashSet},
+    fs,
+    sync::Mutex,
+};
+
+use anyhow::{anyhow, Context, Result};
+use log::warn;
+use zeroize::Zeroize;
+
+use crate::secret::Secret;
+
+/// Parsed representation of an ini file such as `~/.aws/credentials` or `~/.aws/config`.
+///
+/// Sections are matched exactly, `#` and `;` start comments, values can be quoted and
+/// can continue on the following lines when those are indented deeper than the key.
+/// Duplicated keys keep the last value, they and the lines that are not `key=value` pairs
+/// are reported through `warnings`. Values are zeroed on drop as they can be secrets.
+#[derive(Debug, Default)]
+pub struct Ini {
+    sections: Vec<(String, HashMap<String, String>)>,
+    warnings: Vec<String>,
+}
+
+impl Drop for Ini {
+    fn drop(&mut self) {
+        for (_, values) in &mut self.sections {
+            for value in values.values_mut() {
+                value.zeroize();
+            }
+        }
+    }
+}
+
+struct PendingValue {
+    section: usize,
+    key: String,
+    indent: usize,
+    lines: Vec<String>,
+}
+
+impl Ini {
+    pub fn parse(content: &str) -> Result<Ini> {
+        let mut ini = Ini::default();
+        let mut current_section: Option<usize> = None;
+        let mut pending: Option<PendingValue> = None;
+
+        for (index, raw_line) in content.lines().enumerate() {
+            let line_number = index + 1;
+            let indent = raw_line.len() - raw_line.trim_start().len();
+            let line = raw_line.trim();
+
+            if let Some(value) = pending.as_mut() {
+                if !line.is_empty()
+                    && indent > value.indent
+                    && !is_comment(line)
+                    && !line.starts_with('[')
+                {
+                    value.lines.push(line.to_string());
+                    continue;
+                }
+            }
+            if line.is_empty() || is_comment(line) {
+                continue;
+            }
+            if let Some(value) = pending.take() {
+                ini.insert(value);
+            }
+
+            if line.starts_with('[') {
+                let name = strip_comment(line);
+                let name = name
+                    .strip_prefix('[')
+                    .and_then(|name| name.strip_suffix(']'))
+                    .ok_or_else(|| {
+                        anyhow!("Invalid section header at line {}: {}", line_number, line)
+                    })?
+                    .trim();
+                current_section = Some(ini.section_index(name, line_number));
+                continue;
+            }
+
+            let Some((key, value)) = line
+                .split_once('=')
+                .filter(|(key, _)| !key.trim().is_empty())
+            else {
+                ini.warnings.push(format!(
+                    "Ignoring line {} as it is not a key=value pair: {}",
+                    line_number, line
+                ));
+                continue;
+            };
+            let key = key.trim();
+            let Some(section) = current_section else {
+                ini.warnings.push(format!(
+                    "Ignoring key {} at line {} as it is outside of any section",
+                    key, line_number
+                ));
+                continue;
+            };
+            if ini.sections[section].1.contains_key(key) {
+                ini.warnings.push(format!(
+                    "Duplicate key {} in section [{}] at line {}, last value wins",
+                    key, ini.sections[section].0, line_number
+                ));
+            }
+            pending = Some(PendingValue {
+                section,
+                key: key.to_string(),
+                indent,
+                lines: vec![parse_value(value.trim())],
+            });
+        }
+        if let Some(value) = pending.take() {
+            ini.insert(value);
+        }
+        Ok(ini)
+    }
+
+    /// Reads and parses the file in `path`, None when it cannot be read.
+    pub fn load(path: &str) -> Result<Option<Ini>> {
+        let Ok(content) = fs::read_to_string(path).map(Secret::new) else {
+            return Ok(None);
+        };
+        Ini::parse_file(content.expose(), path).map(Some)
+    }
+
+    /// Parses the content of the file in `path` and logs its warnings, once per file as
+    /// several providers read the same files.
+    pub fn parse_file(content: &str, path: &str) -> Result<Ini> {
+        static WARNED: Mutex<Option<HashSet<String>>> = Mutex::new(None);
+        let ini = Ini::parse(content).with_context(|| format!("Failed to parse {}", path))?;
+        let mut warned = WARNED
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        if warned
+            .get_or_insert_with(HashSet::new)
+            .insert(path.to_string())
+        {
+            for warning in ini.warnings() {
+                warn!("{}: {}", path, warning);
+            }
+        }
+        Ok(ini)
+    }
+
+    pub fn section(&self, name: &str) -> Option<&HashMap<String, String>> {
+        self.sections
+            .iter()
+            .find(|(section_name, _)| section_name == name)
+            .map(|(_, values)| values)
+    }
+
+    /// Moves the values of a section out, for the callers keeping them as `Secret`.
+    pub fn take_section(&mut self, name: &str) -> Option<HashMap<String, String>> {
+        self.sections
+            .iter_mut()
+            .find(|(section_name, _)| section_name == name)
+            .map(|(_, values)| std::mem::take(values))
+    }
+
+    pub fn section_names(&self) -> impl Iterator<Item = &str> {
+        self.sections.iter().map(|(name, _)| name.as_str())
+    }
+
+    pub fn warnings(&self) -> &[String] {
+        &self.warnings
+    }
+
+    fn section_index(&mut self, name: &str, line_number: usize) -> usize {
+        if let Some(index) = self.sections.iter().position(|(n, _)| n == name) {
+            self.warnings.push(format!(
+                "Duplicate section [{}] at line {}, values are merged",
+                name, line_number
+            ));
+            index
+        } else {
+            self.sections.push((name.to_string(), HashMap::new()));
+            self.sections.len() - 1
+        }
+    }
+
+    fn insert(&mut self, value: PendingValue) {
+        let joined = value
+            .lines
+            .into_iter()
+            .skip_while(|line| line.is_empty())
+            .collect::<Vec<_>>()
+            .join("\n");
+        self.sections[value.section].1.insert(value.key, joined);
+    }
+}
+
+fn is_comment(line: &str) -> bool {
+    line.starts_with('#') || line.starts_with(';')
+}
+
+fn strip_comment(value: &str) -> &str {
+    let comment_start = value.char_indices().find(|(index, c)| {
+        (*c == '#' || *c == ';')
+            && value[..*index]
+                .chars()
+                .last()
+                .is_some_and(|previous| previous.is_whitespace())
+    });
+    match comment_start {
+        Some((index, _)) => value[..index].trim_end(),
+        None => value,
+    }
+}
+
+fn parse_value(value: &str) -> String {
+    for quote in ['"', '\''] {
+        if let Some(rest) = value.strip_prefix(quote) {
+            if let Some(end) = rest.find(quote) {
+                return rest[..end].to_string();
+            }
+        }
+    }
+    strip_comment(value).to_string()
+}
+
+#[cfg(test)]
+mod test {
+    use std::fs;
+
+    use crate::generated_test_files_path;
+
+    use super::Ini;
+
+    #[test]
+    fn test_exact_section_matching() {
+        let ini = Ini::parse(
+            "[prod-readonly]
+            key=readonly
+            [prod]
+            key=prod
+            [preprod]
+            key=preprod
+            ",
+        )
+        .expect("file should be parsed");
+        assert_eq!(ini.section("prod").unwrap().get("key").unwrap(), "prod");
+        assert_eq!(
+            ini.section("prod-readonly").unwrap().get("key").unwrap(),
+            "readonly"
+        );
+        assert!(ini.section("pro").is_none());
+        assert_eq!(
+            ini.section_names().collect::<Vec<_>>(),
+            vec!["prod-readonly", "prod", "preprod"]
+        );
+    }
+
+    #[test]
+    fn test_section_with_spaces() {
+        let ini = Ini::parse(
+            "[ profile test ]\nregion=eu-west-1\n[sso-session team]\nsso_region=us-east-1",
+        )
+        .expect("file should be parsed");
+        assert_eq!(
+            ini.section("profile test").unwrap().get("region").unwrap(),
+            "eu-west-1"
+        );
+        assert_eq!(
+            ini.section("sso-session team")
+                .unwrap()
+                .get("sso_region")
+                .unwrap(),
+            "us-east-1"
+        );
+    }
+
+    #[test]
+    fn test_comments() {
+        let ini = Ini::parse(
+            "# leading comment
+            [default] ; section comment
+            # aws_access_key_id=commented
+            ; aws_secret_access_key=commented
+            aws_access_key_id=id # inline comment
+            aws_secret_access_key=secret#not-a-comment
+            region=eu-west-1;not-a-comment
+            ",
+        )
+        .expect("file should be parsed");
+        let default = ini.section("default").unwrap();
+        assert_eq!(default.len(), 3);
+        assert_eq!(default.get("aws_access_key_id").unwrap(), "id");
+        assert_eq!(
+            default.get("aws_secret_access_key").unwrap(),
+            "secret#not-a-comment"
+        );
+        assert_eq!(default.get("region").unwrap(), "eu-west-1;not-a-comment");
+    }
+
+    #[test]
+    fn test_quoted_values() {
+        let ini = Ini::parse(
+            "[default]
+            double=\"value with spaces # and hash\"
+            single='[not a section]'
+            unterminated=\"value
+            empty=\"\"
+            ",
+        )
+        .expect("file should be parsed");
+        let default = ini.section("default").unwrap();
+        assert_eq!(
+            default.get("double").unwrap(),
+            "value with spaces # and hash"
+        );
+        assert_eq!(default.get("single").unwrap(), "[not a section]");
+        assert_eq!(default.get("unterminated").unwrap(), "\"value");
+        assert_eq!(default.get("empty").unwrap(), "");
+    }
+
+    #[test]
+    fn test_brackets_and_equals_in_values() {
+        let ini = Ini::parse(
+            "[default]
+            aws_secret_access_key=abc[def]=ghi
+            [test]
+            region=test
+            ",
+        )
+        .expect("file should be parsed");
+        assert_eq!(
+            ini.section("default")
+                .unwrap()
+                .get("aws_secret_access_key")
+                .unwrap(),
+            "abc[def]=ghi"
+        );
+        assert_eq!(ini.section("default").unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_continuation_lines() {
+        let ini = Ini::parse(
+            "[profile test]
+s3 =
+    max_concurrent_requests = 20
+    # comment inside continuation
+    max_queue_size = 100
+region = eu-west-1
+description = first line
+  second line
+",
+        )
+        .expect("file should be parsed");
+        let profile = ini.section("profile test").unwrap();
+        assert_eq!(
+            profile.get("s3").unwrap(),
+            "max_concurrent_requests = 20\nmax_queue_size = 100"
+        );
+        assert_eq!(profile.get("region").unwrap(), "eu-west-1");
+        assert_eq!(
+            profile.get("description").unwrap(),
+            "first line\nsecond line"
+        );
+    }
+
+    #[test]
+    fn test_duplicate_keys_warn() {
+        let ini = Ini::parse(
+            "[default]
+            region=first
+            region=second
+            ",
+        )
+        .expect("file should be parsed");
+        assert_eq!(
+            ini.section("default").unwrap().get("region").unwrap(),
+            "second"
+        );
+        assert_eq!(
+            ini.warnings(),
+            ["Duplicate key region in section [default] at line 3, last value wins"]
+        );
+    }
+
+    #[test]
+    fn test_duplicate_sections_are_merged() {
+        let ini = Ini::parse("[default]\na=1\n[other]\nb=2\n[default]\nc=3\n")
+            .expect("file should be parsed");
+        let default = ini.section("default").unwrap();
+        assert_eq!(default.get("a").unwrap(), "1");
+        assert_eq!(default.get("c").unwrap(), "3");
+        assert_eq!(
+            ini.warnings(),
+            ["Duplicate section [default] at line 5, values are merged"]
+        );
+    }
+
+    #[test]
+    fn test_keys_outside_section_are_ignored() {
+        let ini =
+            Ini::parse("orphan=value\n[default]\nregion=test\n").expect("file should be parsed");
+        assert_eq!(ini.section("default").unwrap().len(), 1);
+        assert_eq!(ini.warnings().len(), 1);
+    }
+
+    #[test]
+    fn test_windows_line_endings() {
+        let ini = Ini::parse("[default]\r\nregion=test\r\n").expect("file should be parsed");
+        assert_eq!(
+            ini.section("default").unwrap().get("region").unwrap(),
+            "test"
+        );
+    }
+
+    #[test]
+    fn test_invalid_files() {
+        assert!(Ini::parse("[default\nregion=test").is_err());
+    }
+
+    #[test]
+    fn test_lines_without_key_are_ignored() {
+        let ini = Ini::parse("[default]\nnot a key value\n=value\nregion=test\n")
+            .expect("file should be parsed");
+        assert_eq!(ini.section("default").unwrap().len(), 1);
+        assert_eq!(
+            ini.warnings(),
+            [
+                "Ignoring line 2 as it is not a key=value pair: not a key value",
+                "Ignoring line 3 as it is not a key=value pair: =value"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_indented_section_ends_value() {
+        let ini = Ini::parse("[default]\nregion=test\n  [other]\n  region=other\n")
+            .expect("file should be parsed");
+        assert_eq!(
+            ini.section("default").unwrap().get("region").unwrap(),
+            "test"
+        );
+        assert_eq!(
+            ini.section("other").unwrap().get("region").unwrap(),
+            "other"
+        );
+    }
+
+    #[test]
+    fn test_load() {
+        assert!(Ini::load("ini/does/not/exist").unwrap().is_none());
+        let path = generated_test_files_path!("ini_load");
+        fs::write(&path, "[default]\nregion=test\nregion=other\n").unwrap();
+        let ini = Ini::load(&path).unwrap().expect("file should be read");
+        assert_eq!(
+            ini.section("default").unwrap().get("region").unwrap(),
+            "other"
+        );
+        assert_eq!(ini.warnings().len(), 1);
+        fs::write(&path, "[default\n").unwrap();
+        assert!(Ini::load(&path).is_err());
+    }
+
+    #[test]
+    fn test_empty_file() {
+        let ini = Ini::parse("").expect("file should be parsed");
+        assert_eq!(ini.section_names().count(), 0);
+        assert!(ini.warnings().is_empty());
+    }
+}
@@ -0,0 +1,324 @@
+use std::{collections::HashMap, env};
+
+use anyhow::Result;
+
+use crate::{
+    ini::Ini,
+    providers::provider::config_profile_section,
+    secret::{Secret, REDACTED},
+};
+
+/// A profile found in the aws credentials or config file.
+#[derive(Debug, PartialEq)]
+pub struct ProfileSummary {
+    pub name: String,
+    pub sources: Vec<String>,
+    pub fields: Vec<String>,
+}
+
+/// Where the value used for a credentials field comes from.
+#[derive(Debug, PartialEq)]
+pub struct FieldExplanation {
+    pub field: &'static str,
+    pub source: Option<String>,
+    pub value: Option<String>,
+}
+
+const EXPLAINED_FIELDS: [(&str, &str, bool); 4] = [
+    ("aws_access_key_id", "AWS_ACCESS_KEY_ID", false),
+    ("aws_secret_access_key", "AWS_SECRET_ACCESS_KEY", true),
+    ("aws_session_token", "AWS_SESSION_TOKEN", true),
+    ("region", "AWS_REGION", false),
+];
+
+pub fn default_credentials_path() -> String {
+    shellexpand::tilde("~/.aws/credentials").to_string()
+}
+
+pub fn default_config_path() -> String {
+    shellexpand::tilde("~/.aws/config").to_string()
+}
+
+pub fn list_profiles(
+    credentials_path: Option<String>,
+    config_path: Option<String>,
+) -> Result<Vec<ProfileSummary>> {
+    let credentials_path = credentials_path.unwrap_or_else(default_credentials_path);
+    let config_path = config_path.unwrap_or_else(default_config_path);
+    let mut profiles: Vec<ProfileSummary> = vec![];
+    let sources = [(credentials_path, "credentials"), (config_path, "config")];
+    for (path, source) in sources {
+        let Some(ini) = Ini::load(&path)? else {
+            continue;
+        };
+        for section in ini.section_names() {
+            let name = if source == "config" {
+                match section.strip_prefix("profile ") {
+                    Some(name) => name.trim(),
+                    None if section == "default" => section,
+                    None => continue,
+                }
+            } else {
+                section
+            };
+            let mut fields = ini
+                .section(section)
+                .map(|values| values.keys().cloned().collect::<Vec<_>>())
+                .unwrap_or_default();
+            let profile = match profiles.iter_mut().find(|profile| profile.name == name) {
+                Some(profile) => profile,
+                None => {
+                    profiles.push(ProfileSummary {
+                        name: name.to_string(),
+                        sources: vec![],
+                        fields: vec![],
+                    });
+                    profiles.last_mut().expect("profile was just pushed")
+                }
+            };
+            profile.sources.push(source.to_string());
+            profile.fields.append(&mut fields);
+            profile.fields.sort();
+            profile.fields.dedup();
+        }
+    }
+    Ok(profiles)
+}
+
+/// Fields of the profile in `~/.aws/config` used by the sso and credential_process
+/// providers, explained when they are set.
+const CONFIG_FIELDS: [&str; 6] = [
+    "sso_session",
+    "sso_start_url",
+    "sso_region",
+    "sso_account_id",
+    "sso_role_name",
+    "credential_process",
+];
+
+fn take_values(ini: &mut Option<Ini>, section: &str) -> HashMap<String, Secret> {
+    ini.as_mut()
+        .and_then(|ini| ini.take_section(section))
+        .unwrap_or_default()
+        .into_iter()
+        .map(|(key, value)| (key, Secret::new(value)))
+        .collect()
+}
+
+/// Explains where each field used by the providers of the chain comes from: the env, the
+/// credentials file or the config file, in the order they win. Secrets are redacted.
+pub fn explain_profile(
+    profile: Option<&str>,
+    credentials_path: Option<String>,
+    config_path: Option<String>,
+) -> Result<Vec<FieldExplanation>> {
+    let profile = profile.unwrap_or("default");
+    let credentials_path = credentials_path.unwrap_or_else(default_credentials_path);
+    let config_path = config_path.unwrap_or_else(default_config_path);
+    let config_section = config_profile_section(profile);
+    let mut file_values = take_values(&mut Ini::load(&credentials_path)?, profile);
+    let mut config = Ini::load(&config_path)?;
+    let mut config_values = take_values(&mut config, &config_section);
+    let session = config_values
+        .get("sso_session")
+        .map(|name| format!("sso-session {}", name.expose()));
+    let mut session_values = session
+        .as_ref()
+        .map(|session| take_values(&mut config, session))
+        .unwrap_or_default();
+
+    let mut explanations: Vec<FieldExplanation> = EXPLAINED_FIELDS
+        .iter()
+        .map(|(field, env_name, secret)| {
+            let (source, value) = if let Ok(value) = env::var(env_name) {
+                (Some(format!("env {}", env_name)), Some(Secret::new(value)))
+            } else if let Some(value) = file_values.remove(*field) {
+                (
+                    Some(format!("file {} [{}]", credentials_path, profile)),
+                    Some(value),
+                )
+            } else if let Some(value) = config_values.remove(*field).filter(|_| !*secret) {
+                (
+                    Some(format!("file {} [{}]", config_path, config_section)),
+                    Some(value),
+                )
+            } else {
+                (None, None)
+            };
+            FieldExplanation {
+                field,
+                source,
+                value: value.map(|value| {
+                    if *secret {
+                        redact(value.expose())
+                    } else if *field == "aws_access_key_id" {
+                        redact_partially(value.expose())
+                    } else {
+                        value.expose().to_string()
+                    }
+                }),
+            }
+        })
+        .collect();
+    for field in CONFIG_FIELDS {
+        let (section, value) = match session_values.remove(field) {
+            Some(value) => (session.as_deref().unwrap_or_default(), value),
+            None => match config_values.remove(field) {
+                Some(value) => (config_section.as_str(), value),
+                None => continue,
+            },
+        };
+        explanations.push(FieldExplanation {
+            field,
+            source: Some(format!("file {} [{}]", config_path, section)),
+            value: Some(value.expose().to_string()),
+        });
+    }
+    Ok(explanations)
+}
+
+pub fn redact(_value: &str) -> String {
+    REDACTED.to_string()
+}
+
+/// Keeps the first and last four characters, enough to recognise an access key id.
+pub fn redact_partially(value: &str) -> String {
+    let chars = value.chars().collect::<Vec<_>>();
+    if chars.len() <= 8 {
+        return redact(value);
+    }
+    format!(
+        "{}****{}",
+        chars[..4].iter().collect::<String>(),
+        chars[chars.len() - 4..].iter().collect::<String>()
+    )
+}
+
+#[cfg(test)]
+mod test {
+    use std::{env, fs};
+
+    use crate::{generated_test_files_path, lock_env};
+
+    use super::{
+        explain_profile, list_profiles, redact_partially, FieldExplanation, ProfileSummary,
+    };
+
+    const CREDENTIALS_FILE: &str = "[default]
+    aws_access_key_id=AKIADEFAULTKEY1234
+    aws_secret_access_key=defaultsecret
+    [test]
+    aws_access_key_id=AKIATESTKEY0000001
+    ";
+
+    const CONFIG_FILE: &str = "[default]
+    region=eu-west-1
+    [profile sso]
+    sso_session=team
+    [sso-session team]
+    sso_region=eu-west-1
+    ";
+
+    fn write_files(test_name: &str) -> (String, String) {
+        let credentials_path = generated_test_files_path!(format!("{}_credentials", test_name));
+        let config_path = generated_test_files_path!(format!("{}_config", test_name));
+        fs::write(&credentials_path, CREDENTIALS_FILE).expect("should write credentials");
+        fs::write(&config_path, CONFIG_FILE).expect("should write config");
+        (credentials_path, config_path)
+    }
+
+    #[test]
+    fn test_list_profiles() {
+        let _env = lock_env();
+        let (credentials_path, config_path) = write_files("inspect_list_profiles");
+        let profiles = list_profiles(Some(credentials_path), Some(config_path))
+            .expect("profiles should be listed");
+        assert_eq!(
+            profiles,
+            vec![
+                ProfileSummary {
+                    name: "default".to_string(),
+                    sources: vec!["credentials".to_string(), "config".to_string()],
+                    fields: vec![
+                        "aws_access_key_id".to_string(),
+                        "aws_secret_access_key".to_string(),
+                        "region".to_string()
+                    ],
+                },
+                ProfileSummary {
+                    name: "test".to_string(),
+                    sources: vec!["credentials".to_string()],
+                    fields: vec!["aws_access_key_id".to_string()],
+                },
+                ProfileSummary {
+                    name: "sso".to_string(),
+                    sources: vec!["config".to_string()],
+                    fields: vec!["sso_session".to_string()],
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_explain_profile() {
+        let _env = lock_env();
+        let (credentials_path, config_path) = write_files("inspect_explain_profile");
+        env::set_var("AWS_SECRET_ACCESS_KEY", "envsecret");
+        let explanations = explain_profile(
+            None,
+            Some(credentials_path.clone()),
+            Some(config_path.clone()),
+        )
+        .expect("should explain");
+        env::remove_var("AWS_SECRET_ACCESS_KEY");
+
+        assert_eq!(explanations.len(), 4);
+        assert_eq!(
+            explanations[0].source,
+            Some(format!("file {} [default]", credentials_path))
+        );
+        assert_eq!(explanations[0].value.as_deref(), Some("AKIA****1234"));
+        assert_eq!(
+            explanations[1].source.as_deref(),
+            Some("env AWS_SECRET_ACCESS_KEY")
+        );
+        assert_eq!(explanations[1].value.as_deref(), Some("********"));
+        assert!(explanations[2].source.is_none());
+        assert_eq!(
+            explanations[3].source,
+            Some(format!("file {} [default]", config_path))
+        );
+        assert_eq!(explanations[3].value.as_deref(), Some("eu-west-1"));
+
+        let explanations = explain_profile(
+            Some("sso"),
+            Some(credentials_path),
+            Some(config_path.clone()),
+        )
+        .expect("should explain");
+        assert_eq!(explanations.len(), 6);
+        assert!(explanations[3].source.is_none());
+        assert_eq!(
+            explanations[4],
+            FieldExplanation {
+                field: "sso_session",
+                source: Some(format!("file {} [profile sso]", config_path)),
+                value: Some("team".to_string()),
+            }
+        );
+        assert_eq!(
+            explanations[5],
+            FieldExplanation {
+                field: "sso_region",
+                source: Some(format!("file {} [sso-session team]", config_path)),
+                value: Some("eu-west-1".to_string()),
+            }
+        );
+    }
+
+    #[test]
+    fn test_redact_partially() {
+        assert_eq!(redact_partially("AKIAABCDEFGH1234"), "AKIA****1234");
+        assert_eq!(redact_partially("short"), "********");
+    }
+}
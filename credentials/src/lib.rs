@@ -1,30 +1,99 @@
+pub mod chain;
+pub mod ini;
+pub mod inspect;
 pub mod providers;
-use anyhow::Result;
-use providers::{
-    aws::{Aws, AwsCredentials},
-    provider::ParseCredentials,
-};
-
-enum Providers<'a> {
-    Aws(Aws<'a>),
-}
-
-impl<'a> ParseCredentials<AwsCredentials> for Providers<'a> {
-    fn parse(&self) -> anyhow::Result<AwsCredentials> {
-        match self {
-            Providers::Aws(aws) => aws.parse(),
-        }
-    }
-}
+pub mod s3;
+pub mod secret;
+pub mod sigv4;
+pub mod sts;
+use anyhow::{Context, Result};
+use chain::CredentialsChain;
+use log::debug;
+use providers::aws::AwsCredentials;
 
 pub fn get_credentials(
     provider: &str,
     profile: Option<&str>,
     credentials_path: Option<String>,
 ) -> Result<AwsCredentials> {
-    let provider = match provider {
-        "aws" => Providers::Aws(Aws::new(profile, credentials_path)),
+    let chain = match provider {
+        "aws" => CredentialsChain::aws_default(profile, credentials_path),
         _ => return Err(anyhow::anyhow!("Invalid Execution type")),
     };
-    provider.parse()
+    let provided = match profile {
+        Some(profile) => chain
+            .provide()
+            .with_context(|| format!("Failed to resolve credentials of profile {}", profile))?,
+        None => chain.provide()?,
+    };
+    debug!(
+        "Using credentials of profile {} from {} with access key {}",
+        profile.unwrap_or("default"),
+        provided.source,
+        inspect::redact_partially(&provided.credentials.access_key_id)
+    );
+    Ok(provided.credentials)
+}
+
+/// Held by the tests setting or reading the AWS environment variables, which would
+/// otherwise see each other's values as tests run in parallel.
+#[cfg(test)]
+pub(crate) fn lock_env() -> std::sync::MutexGuard<'static, ()> {
+    static ENV: std::sync::Mutex<()> = std::sync::Mutex::new(());
+    ENV.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+/// Local http server answering one request per response, in order. A response is a body
+/// sent with `200 OK`, or `HTTP/1.1 <status>\r\n\r\n<body>` for another status.
+/// Returns the endpoint and the requests, their head lowercased and followed by the body.
+#[cfg(test)]
+pub(crate) fn serve(
+    responses: Vec<&'static str>,
+) -> (String, std::thread::JoinHandle<Vec<String>>) {
+    use std::io::{BufRead, BufReader, Read, Write};
+
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").expect("should bind local port");
+    let endpoint = format!("http://{}", listener.local_addr().unwrap());
+    let handle = std::thread::spawn(move || {
+        responses
+            .into_iter()
+            .map(|response| {
+                let (mut stream, _) = listener.accept().expect("should accept connection");
+                let mut reader = BufReader::new(stream.try_clone().unwrap());
+                let mut request = String::new();
+                let mut content_length = 0;
+                loop {
+                    let mut line = String::new();
+                    reader.read_line(&mut line).unwrap();
+                    if line == "\r\n" || line.is_empty() {
+                        break;
+                    }
+                    let line = line.to_lowercase();
+                    if let Some(length) = line.strip_prefix("content-length: ") {
+                        content_length = length.trim().parse().unwrap();
+                    }
+                    request.push_str(&line);
+                }
+                let mut body = vec![0; content_length];
+                reader.read_exact(&mut body).unwrap();
+                request.push_str("\r\n");
+                request.push_str(&String::from_utf8(body).unwrap());
+
+                let (status, body) = match response.strip_prefix("HTTP/1.1 ") {
+                    Some(response) => response.split_once("\r\n\r\n").unwrap(),
+                    None => ("200 OK", response),
+                };
+                write!(
+                    stream,
+                    "HTTP/1.1 {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                    status,
+                    body.len(),
+                    body
+                )
+                .unwrap();
+                request
+            })
+            .collect()
+    });
+    (endpoint, handle)
 }
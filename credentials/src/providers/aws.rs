@@ -1,9 +1,14 @@
-use std::{collections::HashMap, env, fs};
+use std::{collections::HashMap, fs};
 
-use anyhow::{anyhow, Context};
-use regex::Regex;
+use anyhow::{anyhow, Context, Result};
+use chrono::{DateTime, Utc};
 
-use super::provider::ParseCredentials;
+use crate::{ini::Ini, inspect::redact_partially, secret::Secret};
+
+use super::{
+    env::extract_aws_env,
+    provider::{ParseCredentials, ProvideCredentials},
+};
 
 pub struct Aws<'a> {
     profile: &'a str,
@@ -13,9 +18,33 @@ pub struct Aws<'a> {
 #[derive(Debug)]
 pub struct AwsCredentials {
     pub access_key_id: String,
-    pub secret_access_key: String,
-    pub session_token: String,
+    pub secret_access_key: Secret,
+    pub session_token: Secret,
     pub region: String,
+    pub expiration: Option<DateTime<Utc>>,
+}
+
+impl AwsCredentials {
+    /// Moves the `aws_*` and `region` values read from a file or the env into credentials,
+    /// None when one is missing.
+    pub(crate) fn from_values(mut values: HashMap<String, Secret>) -> Option<AwsCredentials> {
+        let access_key_id = values.remove("aws_access_key_id")?;
+        let secret_access_key = values.remove("aws_secret_access_key")?;
+        let session_token = values.remove("aws_session_token")?;
+        let region = values.remove("region")?;
+        Some(AwsCredentials {
+            access_key_id: access_key_id.expose().to_string(),
+            secret_access_key,
+            session_token,
+            region: region.expose().to_string(),
+            expiration: None,
+        })
+    }
+
+    pub fn is_expired(&self) -> bool {
+        self.expiration
+            .is_some_and(|expiration| expiration <= Utc::now())
+    }
 }
 
 impl<'a> Aws<'a> {
@@ -32,81 +61,74 @@ impl<'a> Aws<'a> {
         }
     }
 
-    fn extract_credentials_from_file(&self, file: String) -> HashMap<String, String> {
-        let mut parsing = false;
-        let property_delimiter = '=';
-        let re = Regex::new(r"\[.*\]").unwrap();
-        let credentials: HashMap<String, String> = file
-            .lines()
-            .filter_map(|line| {
-                if line.contains(&format!("[{}]", self.profile)) {
-                    parsing = true;
-                } else if re.is_match(line) {
-                    parsing = false;
-                }
-                if parsing && !line.is_empty() {
-                    let mut parts = line.trim().splitn(2, property_delimiter);
-                    let property = parts.next()?.trim().to_string();
-                    let value = parts.next()?.trim().to_string();
-                    Some((property, value))
-                } else {
-                    None
-                }
-            })
-            .collect();
-
-        credentials
+    fn extract_credentials_from_file(&self, file: String) -> Result<HashMap<String, Secret>> {
+        let file = Secret::new(file);
+        let mut ini = Ini::parse_file(file.expose(), &self.credentials_path)?;
+        Ok(ini
+            .take_section(self.profile)
+            .unwrap_or_default()
+            .into_iter()
+            .map(|(key, value)| (key, Secret::new(value)))
+            .collect())
     }
 
-    fn extract_credentials_from_env(&self) -> HashMap<String, String> {
-        let mut credentials = HashMap::new();
-        if let Ok(access_key_id) = env::var("AWS_ACCESS_KEY_ID") {
-            credentials.insert("aws_access_key_id".to_string(), access_key_id);
-        }
-        if let Ok(secret_access_key) = env::var("AWS_SECRET_ACCESS_KEY") {
-            credentials.insert("aws_secret_access_key".to_string(), secret_access_key);
-        }
-        if let Ok(session_token) = env::var("AWS_SESSION_TOKEN") {
-            credentials.insert("aws_session_token".to_string(), session_token);
-        }
-        if let Ok(region) = env::var("AWS_REGION") {
-            credentials.insert("region".to_string(), region);
-        }
-        credentials
+    fn extract_credentials_from_env(&self) -> HashMap<String, Secret> {
+        extract_aws_env()
     }
 }
 
 impl<'a> ParseCredentials<AwsCredentials> for Aws<'a> {
     fn parse(&self) -> anyhow::Result<AwsCredentials> {
-        let file = fs::read_to_string(&self.credentials_path)
-            .with_context(|| format!("File does not exist"))?;
-        let file_credentials = self.extract_credentials_from_file(file);
+        let file =
+            fs::read_to_string(&self.credentials_path).with_context(|| "File does not exist")?;
+        let file_credentials = self.extract_credentials_from_file(file)?;
         let env_credentials = self.extract_credentials_from_env();
         let credentials = file_credentials
             .into_iter()
             .chain(env_credentials)
             .collect::<HashMap<_, _>>();
-        let access_key_id = credentials.get("aws_access_key_id");
-        let secret_access_key = credentials.get("aws_secret_access_key");
-        let session_token = credentials.get("aws_session_token");
-        let region = credentials.get("region");
-        match (access_key_id, secret_access_key, session_token, region) {
-            (Some(key_id), Some(secret), Some(session_token), Some(region)) => Ok(AwsCredentials {
-                access_key_id: key_id.to_string(),
-                secret_access_key: secret.to_string(),
-                session_token: session_token.to_string(),
-                region: region.to_string(),
-            }),
-            _ => Err(anyhow!("Missing aws credentials")),
+        AwsCredentials::from_values(credentials).ok_or_else(|| anyhow!("Missing aws credentials"))
+    }
+}
+
+impl<'a> ProvideCredentials<AwsCredentials> for Aws<'a> {
+    fn name(&self) -> &str {
+        "profile"
+    }
+
+    fn provide(&self) -> Result<Option<AwsCredentials>> {
+        let Some(ini) = Ini::load(&self.credentials_path)? else {
+            return Ok(None);
+        };
+        if ini.section(self.profile).is_none() {
+            return Ok(None);
         }
+        self.parse().map(Some)
+    }
+
+    fn describe(&self) -> Result<Option<String>> {
+        let Some(ini) = Ini::load(&self.credentials_path)? else {
+            return Ok(None);
+        };
+        Ok(ini.section(self.profile).map(|values| {
+            format!(
+                "[{}] of {} with access key {}",
+                self.profile,
+                self.credentials_path,
+                values
+                    .get("aws_access_key_id")
+                    .map_or("missing".to_string(), |key| redact_partially(key))
+            )
+        }))
     }
 }
 
 #[cfg(test)]
+#[allow(clippy::unnecessary_get_then_check)]
 mod test {
     use std::{env, fs::File, io::Write};
 
-    use crate::providers::provider::ParseCredentials;
+    use crate::{lock_env, providers::provider::ParseCredentials};
 
     use super::Aws;
 
@@ -146,9 +168,12 @@ mod test {
 
     #[test]
     fn test_extract_credentials_default() {
+        let _env = lock_env();
         let aws_provider = Aws::new(None, None);
 
-        let credentials = aws_provider.extract_credentials_from_file(TEST_FILE.to_string());
+        let credentials = aws_provider
+            .extract_credentials_from_file(TEST_FILE.to_string())
+            .expect("file should be parsed");
         assert!(credentials.get("aws_access_key_id").is_some());
         assert!(credentials.get("aws_secret_access_key").is_some());
         assert!(credentials.get("region").is_some());
@@ -163,9 +188,12 @@ mod test {
 
     #[test]
     fn test_extract_credentials_different_profile() {
+        let _env = lock_env();
         let aws_provider = Aws::new(Some("test"), None);
 
-        let credentials = aws_provider.extract_credentials_from_file(TEST_FILE.to_string());
+        let credentials = aws_provider
+            .extract_credentials_from_file(TEST_FILE.to_string())
+            .expect("file should be parsed");
         assert!(credentials.get("aws_access_key_id").is_some());
         assert!(credentials.get("aws_secret_access_key").is_some());
         assert!(credentials.get("region").is_some());
@@ -180,6 +208,7 @@ mod test {
 
     #[test]
     fn test_extract_credentials_from_env() {
+        let _env = lock_env();
         let aws_provider = Aws::new(None, None);
         env::set_var("AWS_ACCESS_KEY_ID", "keyid");
         env::set_var("AWS_SECRET_ACCESS_KEY", "secret");
@@ -202,6 +231,7 @@ mod test {
 
     #[test]
     fn test_parse() {
+        let _env = lock_env();
         let credentials_path = generated_test_files_path!("test_parse");
         let mut file =
             File::create(&credentials_path).expect("should be able to create file in test");
@@ -213,12 +243,13 @@ mod test {
         let credentials = result.unwrap();
 
         assert_eq!(credentials.access_key_id, "testid");
-        assert_eq!(credentials.secret_access_key, "testsecret");
+        assert_eq!(credentials.secret_access_key.expose(), "testsecret");
         assert_eq!(credentials.region, "test");
     }
 
     #[test]
     fn test_parse_env_priority() {
+        let _env = lock_env();
         let credentials_path = generated_test_files_path!("test_parse_env_priority");
         let mut file =
             File::create(&credentials_path).expect("should be able to create file in test");
@@ -234,8 +265,8 @@ mod test {
         let credentials = result.unwrap();
 
         assert_eq!(credentials.access_key_id, "env_keyid");
-        assert_eq!(credentials.secret_access_key, "env_secret");
-        assert_eq!(credentials.session_token, "env_token");
+        assert_eq!(credentials.secret_access_key.expose(), "env_secret");
+        assert_eq!(credentials.session_token.expose(), "env_token");
         assert_eq!(credentials.region, "env_region");
         env::remove_var("AWS_ACCESS_KEY_ID");
         env::remove_var("AWS_SECRET_ACCESS_KEY");
@@ -245,6 +276,7 @@ mod test {
 
     #[test]
     fn test_parse_env_priority_partial() {
+        let _env = lock_env();
         let credentials_path = generated_test_files_path!("test_parse_env_priority_partial");
         let mut file =
             File::create(&credentials_path).expect("should be able to create file in test");
@@ -258,8 +290,8 @@ mod test {
         let credentials = result.unwrap();
 
         assert_eq!(credentials.access_key_id, "env_keyid");
-        assert_eq!(credentials.secret_access_key, "env_secret");
-        assert_eq!(credentials.session_token, "test-session");
+        assert_eq!(credentials.secret_access_key.expose(), "env_secret");
+        assert_eq!(credentials.session_token.expose(), "test-session");
         assert_eq!(credentials.region, "test");
         env::remove_var("AWS_ACCESS_KEY_ID");
         env::remove_var("AWS_SECRET_ACCESS_KEY");
@@ -267,6 +299,7 @@ mod test {
 
     #[test]
     fn test_parse_failure_file_does_not_exist() {
+        let _env = lock_env();
         let aws_provider = Aws::new(
             Some("test"),
             Some("test_parse_failure_file_does_not_exist".to_string()),
@@ -278,6 +311,7 @@ mod test {
 
     #[test]
     fn test_parse_failure_incomplete_credentials() {
+        let _env = lock_env();
         let credentials_path =
             generated_test_files_path!("test_parse_failure_incomplete_credentials");
         let mut file =
@@ -289,4 +323,31 @@ mod test {
         assert!(result.is_err());
         assert_eq!(result.unwrap_err().to_string(), "Missing aws credentials");
     }
+
+    #[test]
+    fn test_extract_credentials_exact_profile_match() {
+        let _env = lock_env();
+        let aws_provider = Aws::new(Some("prod"), None);
+        let credentials = aws_provider
+            .extract_credentials_from_file(
+                "[prod-readonly]
+                aws_access_key_id=readonlyid
+                # aws_access_key_id=commentedid
+                [prod]
+                ; region=commented
+                aws_access_key_id=prodid
+                aws_secret_access_key=\"secret[with]brackets\"
+                region=prod
+                "
+                .to_string(),
+            )
+            .expect("file should be parsed");
+        assert_eq!(credentials.len(), 3);
+        assert_eq!(credentials.get("aws_access_key_id").unwrap(), "prodid");
+        assert_eq!(
+            credentials.get("aws_secret_access_key").unwrap(),
+            "secret[with]brackets"
+        );
+        assert_eq!(credentials.get("region").unwrap(), "prod");
+    }
 }
@@ -0,0 +1,109 @@
+use std::{collections::HashMap, env};
+
+use anyhow::Result;
+
+use crate::{inspect::redact_partially, secret::Secret};
+
+use super::{aws::AwsCredentials, provider::ProvideCredentials};
+
+/// Reads credentials from the `AWS_*` environment variables.
+///
+/// Only complete sets of variables are provided, partial ones are merged with the
+/// profile by `Aws` instead.
+#[derive(Default)]
+pub struct AwsEnv;
+
+impl AwsEnv {
+    pub fn new() -> Self {
+        AwsEnv
+    }
+}
+
+pub(crate) fn extract_aws_env() -> HashMap<String, Secret> {
+    let mut credentials = HashMap::new();
+    if let Ok(access_key_id) = env::var("AWS_ACCESS_KEY_ID") {
+        credentials.insert("aws_access_key_id".to_string(), access_key_id.into());
+    }
+    if let Ok(secret_access_key) = env::var("AWS_SECRET_ACCESS_KEY") {
+        credentials.insert(
+            "aws_secret_access_key".to_string(),
+            secret_access_key.into(),
+        );
+    }
+    if let Ok(session_token) = env::var("AWS_SESSION_TOKEN") {
+        credentials.insert("aws_session_token".to_string(), session_token.into());
+    }
+    if let Ok(region) = env::var("AWS_REGION") {
+        credentials.insert("region".to_string(), region.into());
+    }
+    credentials
+}
+
+impl ProvideCredentials<AwsCredentials> for AwsEnv {
+    fn name(&self) -> &str {
+        "env"
+    }
+
+    fn provide(&self) -> Result<Option<AwsCredentials>> {
+        Ok(AwsCredentials::from_values(extract_aws_env()))
+    }
+
+    fn describe(&self) -> Result<Option<String>> {
+        let credentials = extract_aws_env();
+        let complete = [
+            "aws_access_key_id",
+            "aws_secret_access_key",
+            "aws_session_token",
+            "region",
+        ]
+        .iter()
+        .all(|key| credentials.contains_key(*key));
+        Ok(complete.then(|| {
+            format!(
+                "the AWS_* environment variables with access key {}",
+                redact_partially(credentials["aws_access_key_id"].expose())
+            )
+        }))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::env;
+
+    use crate::{lock_env, providers::provider::ProvideCredentials};
+
+    use super::AwsEnv;
+
+    #[test]
+    fn test_provide_complete_env() {
+        let _env = lock_env();
+        env::set_var("AWS_ACCESS_KEY_ID", "keyid");
+        env::set_var("AWS_SECRET_ACCESS_KEY", "secret");
+        env::set_var("AWS_SESSION_TOKEN", "token");
+        env::set_var("AWS_REGION", "region");
+        let credentials = AwsEnv::new()
+            .provide()
+            .expect("env should be read")
+            .expect("credentials should be complete");
+        assert_eq!(credentials.access_key_id, "keyid");
+        assert_eq!(credentials.secret_access_key.expose(), "secret");
+        assert_eq!(credentials.session_token.expose(), "token");
+        assert_eq!(credentials.region, "region");
+        env::remove_var("AWS_ACCESS_KEY_ID");
+        env::remove_var("AWS_SECRET_ACCESS_KEY");
+        env::remove_var("AWS_SESSION_TOKEN");
+        env::remove_var("AWS_REGION");
+    }
+
+    #[test]
+    fn test_provide_partial_env() {
+        let _env = lock_env();
+        env::set_var("AWS_ACCESS_KEY_ID", "keyid");
+        env::set_var("AWS_SECRET_ACCESS_KEY", "secret");
+        let credentials = AwsEnv::new().provide().expect("env should be read");
+        assert!(credentials.is_none());
+        env::remove_var("AWS_ACCESS_KEY_ID");
+        env::remove_var("AWS_SECRET_ACCESS_KEY");
+    }
+}
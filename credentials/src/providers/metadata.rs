@@ -0,0 +1,410 @@
+use std::{env, fs, time::Duration};
+
+use anyhow::{anyhow, Context, Result};
+use chrono::{DateTime, Utc};
+use serde::Deserialize;
+
+use crate::secret::Secret;
+
+use super::{aws::AwsCredentials, provider::ProvideCredentials};
+
+const ECS_ENDPOINT: &str = "http://169.254.170.2";
+const IMDS_ENDPOINT: &str = "http://169.254.169.254";
+const IMDS_TOKEN_TTL_SECONDS: &str = "21600";
+/// Hosts of the ecs and eks pod identity agents, allowed over http like loopback ones.
+const CONTAINER_HOSTS: [&str; 3] = ["169.254.170.2", "169.254.170.23", "[fd00:ec2::23]"];
+
+/// Credentials document returned by both the ecs and the ec2 metadata endpoints.
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct MetadataCredentials {
+    access_key_id: String,
+    secret_access_key: Secret,
+    token: Secret,
+    expiration: Option<DateTime<Utc>>,
+}
+
+impl MetadataCredentials {
+    fn into_aws_credentials(self, region: String) -> AwsCredentials {
+        AwsCredentials {
+            access_key_id: self.access_key_id,
+            secret_access_key: self.secret_access_key,
+            session_token: self.token,
+            region,
+            expiration: self.expiration,
+        }
+    }
+}
+
+fn agent() -> ureq::Agent {
+    ureq::AgentBuilder::new()
+        .timeout_connect(Duration::from_secs(1))
+        .timeout(Duration::from_secs(5))
+        .build()
+}
+
+fn region_from_env() -> Option<String> {
+    env::var("AWS_REGION")
+        .or_else(|_| env::var("AWS_DEFAULT_REGION"))
+        .ok()
+}
+
+/// Reads credentials from the ecs container endpoint set through
+/// `AWS_CONTAINER_CREDENTIALS_RELATIVE_URI` or `AWS_CONTAINER_CREDENTIALS_FULL_URI`.
+pub struct EcsContainer {
+    endpoint: String,
+}
+
+impl Default for EcsContainer {
+    fn default() -> Self {
+        EcsContainer {
+            endpoint: ECS_ENDPOINT.to_string(),
+        }
+    }
+}
+
+impl EcsContainer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Overrides the host the relative uri is appended to.
+    pub fn with_endpoint(mut self, endpoint: String) -> Self {
+        self.endpoint = endpoint;
+        self
+    }
+
+    fn credentials_uri(&self) -> Result<Option<String>> {
+        if let Ok(relative_uri) = env::var("AWS_CONTAINER_CREDENTIALS_RELATIVE_URI") {
+            return Ok(Some(format!(
+                "{}{}",
+                self.endpoint.trim_end_matches('/'),
+                relative_uri
+            )));
+        }
+        let Ok(full_uri) = env::var("AWS_CONTAINER_CREDENTIALS_FULL_URI") else {
+            return Ok(None);
+        };
+        check_full_uri(&full_uri)?;
+        Ok(Some(full_uri))
+    }
+
+    fn authorization(&self) -> Result<Option<String>> {
+        if let Ok(token_file) = env::var("AWS_CONTAINER_AUTHORIZATION_TOKEN_FILE") {
+            let token = fs::read_to_string(&token_file)
+                .with_context(|| format!("Failed to read {}", token_file))?;
+            return Ok(Some(token.trim().to_string()));
+        }
+        Ok(env::var("AWS_CONTAINER_AUTHORIZATION_TOKEN").ok())
+    }
+}
+
+impl ProvideCredentials<AwsCredentials> for EcsContainer {
+    fn name(&self) -> &str {
+        "container"
+    }
+
+    fn provide(&self) -> Result<Option<AwsCredentials>> {
+        let Some(uri) = self.credentials_uri()? else {
+            return Ok(None);
+        };
+        let mut request = agent().get(&uri);
+        if let Some(authorization) = self.authorization()? {
+            request = request.set("Authorization", &authorization);
+        }
+        let credentials: MetadataCredentials = request
+            .call()
+            .with_context(|| format!("Failed to call container credentials endpoint {}", uri))?
+            .into_json()
+            .with_context(|| "Invalid response from container credentials endpoint")?;
+        let region =
+            region_from_env().ok_or_else(|| anyhow!("Missing AWS_REGION in the container"))?;
+        Ok(Some(credentials.into_aws_credentials(region)))
+    }
+
+    fn describe(&self) -> Result<Option<String>> {
+        Ok(self
+            .credentials_uri()?
+            .map(|uri| format!("calls the container endpoint {}", uri)))
+    }
+}
+
+/// Like the aws sdks, the container token is only sent over https, or over http to a
+/// loopback address or to the ecs and eks agents.
+fn check_full_uri(uri: &str) -> Result<()> {
+    if uri.starts_with("https://") {
+        return Ok(());
+    }
+    let host = uri
+        .strip_prefix("http://")
+        .map(|rest| rest.split(['/', '?', '#']).next().unwrap_or_default())
+        .map(|authority| match authority.rsplit_once(':') {
+            Some((host, port)) if !port.contains(']') => host,
+            _ => authority,
+        });
+    let allowed = host.is_some_and(|host| {
+        host == "localhost"
+            || host == "[::1]"
+            || host
+                .parse::<std::net::Ipv4Addr>()
+                .is_ok_and(|address| address.is_loopback())
+            || CONTAINER_HOSTS.contains(&host)
+    });
+    if allowed {
+        Ok(())
+    } else {
+        Err(anyhow!(
+            "AWS_CONTAINER_CREDENTIALS_FULL_URI {} has to use https, or http to a loopback or container agent address",
+            uri
+        ))
+    }
+}
+
+/// Reads the instance role credentials from the ec2 instance metadata service, using IMDSv2.
+pub struct Ec2Imds {
+    endpoint: String,
+}
+
+impl Default for Ec2Imds {
+    fn default() -> Self {
+        let endpoint = env::var("AWS_EC2_METADATA_SERVICE_ENDPOINT")
+            .unwrap_or_else(|_| IMDS_ENDPOINT.to_string());
+        Ec2Imds { endpoint }
+    }
+}
+
+impl Ec2Imds {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_endpoint(mut self, endpoint: String) -> Self {
+        self.endpoint = endpoint;
+        self
+    }
+
+    fn url(&self, path: &str) -> String {
+        format!("{}{}", self.endpoint.trim_end_matches('/'), path)
+    }
+
+    fn get(&self, agent: &ureq::Agent, token: &Secret, path: &str) -> Result<ureq::Response> {
+        agent
+            .get(&self.url(path))
+            .set("X-aws-ec2-metadata-token", token.expose())
+            .call()
+            .with_context(|| format!("Failed to read instance metadata {}", path))
+    }
+}
+
+impl ProvideCredentials<AwsCredentials> for Ec2Imds {
+    fn name(&self) -> &str {
+        "instance"
+    }
+
+    fn provide(&self) -> Result<Option<AwsCredentials>> {
+        if env::var("AWS_EC2_METADATA_DISABLED").is_ok_and(|disabled| disabled == "true") {
+            return Ok(None);
+        }
+        let agent = agent();
+        let token = match agent
+            .put(&self.url("/latest/api/token"))
+            .set(
+                "X-aws-ec2-metadata-token-ttl-seconds",
+                IMDS_TOKEN_TTL_SECONDS,
+            )
+            .call()
+        {
+            Ok(response) => Secret::new(response.into_string()?),
+            // not running on ec2, nothing to provide
+            Err(ureq::Error::Transport(_)) => return Ok(None),
+            Err(e) => return Err(anyhow!(e)).with_context(|| "Failed to get IMDSv2 token"),
+        };
+        let role = self
+            .get(
+                &agent,
+                &token,
+                "/latest/meta-data/iam/security-credentials/",
+            )?
+            .into_string()?;
+        let role = role
+            .lines()
+            .next()
+            .filter(|role| !role.is_empty())
+            .ok_or_else(|| anyhow!("No instance role attached to this instance"))?;
+        let credentials: MetadataCredentials = self
+            .get(
+                &agent,
+                &token,
+                &format!("/latest/meta-data/iam/security-credentials/{}", role),
+            )?
+            .into_json()
+            .with_context(|| "Invalid instance role credentials")?;
+        let region = match region_from_env() {
+            Some(region) => region,
+            None => self
+                .get(&agent, &token, "/latest/meta-data/placement/region")?
+                .into_string()?,
+        };
+        Ok(Some(credentials.into_aws_credentials(region)))
+    }
+
+    fn describe(&self) -> Result<Option<String>> {
+        if env::var("AWS_EC2_METADATA_DISABLED").is_ok_and(|disabled| disabled == "true") {
+            return Ok(None);
+        }
+        Ok(Some(format!(
+            "asks the instance metadata at {} for the instance role, when running on ec2",
+            self.endpoint
+        )))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::{env, net::TcpListener};
+
+    use crate::{lock_env, providers::provider::ProvideCredentials, serve};
+
+    use super::{check_full_uri, Ec2Imds, EcsContainer};
+
+    const CREDENTIALS: &str = r#"{"Code":"Success","Type":"AWS-HMAC","AccessKeyId":"metadataid","SecretAccessKey":"metadatasecret","Token":"metadatatoken","Expiration":"2999-01-01T00:00:00Z"}"#;
+
+    #[test]
+    fn test_ecs_not_configured() {
+        let _env = lock_env();
+        let container = EcsContainer::new();
+        assert!(container.provide().expect("nothing to call").is_none());
+    }
+
+    #[test]
+    fn test_ecs_relative_uri() {
+        let _env = lock_env();
+        let (endpoint, requests) = serve(vec![CREDENTIALS]);
+        env::set_var(
+            "AWS_CONTAINER_CREDENTIALS_RELATIVE_URI",
+            "/v2/credentials/id",
+        );
+        env::set_var("AWS_CONTAINER_AUTHORIZATION_TOKEN", "containertoken");
+        env::set_var("AWS_REGION", "eu-west-1");
+        let result = EcsContainer::new().with_endpoint(endpoint).provide();
+        env::remove_var("AWS_CONTAINER_CREDENTIALS_RELATIVE_URI");
+        env::remove_var("AWS_CONTAINER_AUTHORIZATION_TOKEN");
+        env::remove_var("AWS_REGION");
+
+        let credentials = result
+            .expect("endpoint should answer")
+            .expect("container is configured");
+        assert_eq!(credentials.access_key_id, "metadataid");
+        assert_eq!(credentials.secret_access_key.expose(), "metadatasecret");
+        assert_eq!(credentials.session_token.expose(), "metadatatoken");
+        assert_eq!(credentials.region, "eu-west-1");
+        assert!(credentials.expiration.is_some());
+
+        let requests = requests.join().unwrap();
+        assert!(requests[0].starts_with("get /v2/credentials/id "));
+        assert!(requests[0].contains("authorization: containertoken"));
+    }
+
+    #[test]
+    fn test_ecs_full_uri() {
+        let _env = lock_env();
+        let (endpoint, requests) = serve(vec![CREDENTIALS]);
+        env::set_var(
+            "AWS_CONTAINER_CREDENTIALS_FULL_URI",
+            format!("{}/full/credentials", endpoint),
+        );
+        env::set_var("AWS_REGION", "eu-west-1");
+        let result = EcsContainer::new().provide();
+        env::remove_var("AWS_CONTAINER_CREDENTIALS_FULL_URI");
+        env::remove_var("AWS_REGION");
+
+        assert!(result.expect("endpoint should answer").is_some());
+        assert!(requests.join().unwrap()[0].starts_with("get /full/credentials "));
+    }
+
+    #[test]
+    fn test_ecs_full_uri_rejected() {
+        let _env = lock_env();
+        env::set_var(
+            "AWS_CONTAINER_CREDENTIALS_FULL_URI",
+            "http://credentials.example.com/full/credentials",
+        );
+        env::set_var("AWS_CONTAINER_AUTHORIZATION_TOKEN", "containertoken");
+        let result = EcsContainer::new().provide();
+        env::remove_var("AWS_CONTAINER_CREDENTIALS_FULL_URI");
+        env::remove_var("AWS_CONTAINER_AUTHORIZATION_TOKEN");
+
+        assert!(result.unwrap_err().to_string().contains("has to use https"));
+        for allowed in [
+            "https://credentials.example.com/full",
+            "http://127.0.0.1:8080/full",
+            "http://localhost/full",
+            "http://[::1]:80/full",
+            "http://169.254.170.23/v1/credentials",
+        ] {
+            assert!(check_full_uri(allowed).is_ok(), "{} is allowed", allowed);
+        }
+        for rejected in [
+            "http://169.254.170.2.example.com/full",
+            "http://127.0.0.1.example.com/full",
+            "ftp://127.0.0.1/full",
+        ] {
+            assert!(
+                check_full_uri(rejected).is_err(),
+                "{} is rejected",
+                rejected
+            );
+        }
+    }
+
+    #[test]
+    fn test_imds() {
+        let _env = lock_env();
+        env::remove_var("AWS_REGION");
+        env::remove_var("AWS_DEFAULT_REGION");
+        env::remove_var("AWS_EC2_METADATA_DISABLED");
+        let (endpoint, requests) = serve(vec![
+            "imdstoken",
+            "InstanceRole\n",
+            CREDENTIALS,
+            "us-east-2",
+        ]);
+        let credentials = Ec2Imds::new()
+            .with_endpoint(endpoint)
+            .provide()
+            .expect("imds should answer")
+            .expect("instance has a role");
+        assert_eq!(credentials.access_key_id, "metadataid");
+        assert_eq!(credentials.session_token.expose(), "metadatatoken");
+        assert_eq!(credentials.region, "us-east-2");
+
+        let requests = requests.join().unwrap();
+        assert!(requests[0].starts_with("put /latest/api/token "));
+        assert!(requests[0].contains("x-aws-ec2-metadata-token-ttl-seconds: 21600"));
+        assert!(requests[1].starts_with("get /latest/meta-data/iam/security-credentials/ "));
+        assert!(requests[1].contains("x-aws-ec2-metadata-token: imdstoken"));
+        assert!(
+            requests[2].starts_with("get /latest/meta-data/iam/security-credentials/instancerole ")
+        );
+        assert!(requests[3].starts_with("get /latest/meta-data/placement/region "));
+    }
+
+    #[test]
+    fn test_imds_unreachable() {
+        let _env = lock_env();
+        let listener = TcpListener::bind("127.0.0.1:0").expect("should bind local port");
+        let endpoint = format!("http://{}", listener.local_addr().unwrap());
+        drop(listener);
+        let result = Ec2Imds::new().with_endpoint(endpoint).provide();
+        assert!(result.expect("unreachable imds is skipped").is_none());
+    }
+
+    #[test]
+    fn test_imds_disabled() {
+        let _env = lock_env();
+        env::set_var("AWS_EC2_METADATA_DISABLED", "true");
+        let result = Ec2Imds::new().provide();
+        env::remove_var("AWS_EC2_METADATA_DISABLED");
+        assert!(result.expect("disabled imds is skipped").is_none());
+    }
+}
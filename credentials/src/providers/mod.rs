@@ -1,2 +1,6 @@
 pub mod aws;
+pub mod env;
+pub mod metadata;
+pub mod process;
 pub mod provider;
+pub mod sso;
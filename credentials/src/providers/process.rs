@@ -0,0 +1,236 @@
+use std::{env, process::Command};
+
+use anyhow::{anyhow, Context, Result};
+use chrono::{DateTime, Utc};
+use serde::Deserialize;
+
+use crate::{ini::Ini, secret::Secret};
+
+use super::{
+    aws::AwsCredentials,
+    provider::{config_profile_section, ProvideCredentials},
+};
+
+/// Runs the `credential_process` configured for the profile and reads its json output.
+pub struct CredentialProcess<'a> {
+    profile: &'a str,
+    config_path: String,
+    credentials_path: String,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct ProcessOutput {
+    version: u8,
+    access_key_id: String,
+    secret_access_key: Secret,
+    session_token: Option<Secret>,
+    expiration: Option<DateTime<Utc>>,
+}
+
+impl<'a> CredentialProcess<'a> {
+    pub fn new(
+        profile: Option<&'a str>,
+        config_path: Option<String>,
+        credentials_path: Option<String>,
+    ) -> Self {
+        let profile = profile.unwrap_or("default");
+        let config_path =
+            config_path.unwrap_or_else(|| shellexpand::tilde("~/.aws/config").to_string());
+        let credentials_path = credentials_path
+            .unwrap_or_else(|| shellexpand::tilde("~/.aws/credentials").to_string());
+        CredentialProcess {
+            profile,
+            config_path,
+            credentials_path,
+        }
+    }
+
+    /// Returns the `credential_process` command and the region set for the profile.
+    fn read_profile(&self) -> Result<(Option<String>, Option<String>)> {
+        let mut command = None;
+        let mut region = None;
+        let sources = [
+            (&self.config_path, config_profile_section(self.profile)),
+            (&self.credentials_path, self.profile.to_string()),
+        ];
+        for (path, section) in sources {
+            let Some(ini) = Ini::load(path)? else {
+                continue;
+            };
+            if let Some(values) = ini.section(&section) {
+                command = command.or_else(|| values.get("credential_process").cloned());
+                region = region.or_else(|| values.get("region").cloned());
+            }
+        }
+        Ok((command, region))
+    }
+
+    fn run_process(&self, command: &str) -> Result<ProcessOutput> {
+        let output = if cfg!(windows) {
+            Command::new("cmd").args(["/C", command]).output()
+        } else {
+            Command::new("sh").args(["-c", command]).output()
+        }
+        .with_context(|| format!("Failed to run credential_process {}", command))?;
+        if !output.status.success() {
+            return Err(anyhow!(
+                "credential_process for profile {} failed: {}",
+                self.profile,
+                String::from_utf8_lossy(&output.stderr).trim()
+            ));
+        }
+        let output: ProcessOutput = serde_json::from_slice(&output.stdout)
+            .with_context(|| "Invalid credential_process output")?;
+        if output.version != 1 {
+            return Err(anyhow!(
+                "Unsupported credential_process output version {}",
+                output.version
+            ));
+        }
+        Ok(output)
+    }
+}
+
+impl<'a> ProvideCredentials<AwsCredentials> for CredentialProcess<'a> {
+    fn name(&self) -> &str {
+        "credential_process"
+    }
+
+    fn describe(&self) -> Result<Option<String>> {
+        let (command, _) = self.read_profile()?;
+        Ok(command.map(|command| format!("runs `{}`", command)))
+    }
+
+    fn provide(&self) -> Result<Option<AwsCredentials>> {
+        let (Some(command), region) = self.read_profile()? else {
+            return Ok(None);
+        };
+        let output = self.run_process(&command)?;
+        if output
+            .expiration
+            .is_some_and(|expiration| expiration <= Utc::now())
+        {
+            return Err(anyhow!(
+                "credential_process for profile {} returned expired credentials",
+                self.profile
+            ));
+        }
+        let region = env::var("AWS_REGION")
+            .ok()
+            .or(region)
+            .ok_or_else(|| anyhow!("Missing region for profile {}", self.profile))?;
+        Ok(Some(AwsCredentials {
+            access_key_id: output.access_key_id,
+            secret_access_key: output.secret_access_key,
+            session_token: output
+                .session_token
+                .ok_or_else(|| anyhow!("Missing aws credentials"))?,
+            region,
+            expiration: output.expiration,
+        }))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::fs;
+
+    use crate::{generated_test_files_path, lock_env, providers::provider::ProvideCredentials};
+
+    use super::CredentialProcess;
+
+    const PROCESS_OUTPUT: &str = r#"{"Version":1,"AccessKeyId":"processid","SecretAccessKey":"processsecret","SessionToken":"processtoken","Expiration":"2999-01-01T00:00:00Z"}"#;
+
+    fn write_config(test_name: &str, output: &str) -> String {
+        let output_path = generated_test_files_path!(format!("{}_output.json", test_name));
+        fs::write(&output_path, output).expect("should be able to write output in test");
+        let config_path = generated_test_files_path!(format!("{}_config", test_name));
+        let config = format!(
+            "[profile process]\ncredential_process = cat {}\nregion = eu-west-1\n[profile failing]\ncredential_process = sh -c 'echo denied >&2; exit 1'\nregion = eu-west-1\n",
+            output_path
+        );
+        fs::write(&config_path, config).expect("should be able to write config in test");
+        config_path
+    }
+
+    #[test]
+    fn test_provide() {
+        let _env = lock_env();
+        let config_path = write_config("process_provide", PROCESS_OUTPUT);
+        let process = CredentialProcess::new(
+            Some("process"),
+            Some(config_path),
+            Some("credentials_do_not_exist".to_string()),
+        );
+        let credentials = process
+            .provide()
+            .expect("process should run")
+            .expect("profile has a credential_process");
+        assert_eq!(credentials.access_key_id, "processid");
+        assert_eq!(credentials.secret_access_key.expose(), "processsecret");
+        assert_eq!(credentials.session_token.expose(), "processtoken");
+        assert_eq!(credentials.region, "eu-west-1");
+    }
+
+    #[test]
+    fn test_provide_not_configured() {
+        let _env = lock_env();
+        let config_path = write_config("process_provide_not_configured", PROCESS_OUTPUT);
+        let process = CredentialProcess::new(
+            Some("other"),
+            Some(config_path),
+            Some("credentials_do_not_exist".to_string()),
+        );
+        assert!(process.provide().expect("nothing to run").is_none());
+        assert!(process.describe().expect("nothing to run").is_none());
+    }
+
+    #[test]
+    fn test_describe_does_not_run() {
+        let config_path = write_config("process_describe_does_not_run", PROCESS_OUTPUT);
+        let process = CredentialProcess::new(
+            Some("failing"),
+            Some(config_path),
+            Some("credentials_do_not_exist".to_string()),
+        );
+        assert_eq!(
+            process
+                .describe()
+                .expect("describe should not run the failing process"),
+            Some("runs `sh -c 'echo denied >&2; exit 1'`".to_string())
+        );
+    }
+
+    #[test]
+    fn test_provide_failure_process_fails() {
+        let _env = lock_env();
+        let config_path = write_config("process_provide_failure_process_fails", PROCESS_OUTPUT);
+        let process = CredentialProcess::new(
+            Some("failing"),
+            Some(config_path),
+            Some("credentials_do_not_exist".to_string()),
+        );
+        let result = process.provide();
+        assert!(result.is_err());
+        assert_eq!(
+            result.unwrap_err().to_string(),
+            "credential_process for profile failing failed: denied"
+        );
+    }
+
+    #[test]
+    fn test_provide_failure_expired() {
+        let _env = lock_env();
+        let config_path = write_config(
+            "process_provide_failure_expired",
+            r#"{"Version":1,"AccessKeyId":"id","SecretAccessKey":"secret","SessionToken":"token","Expiration":"2000-01-01T00:00:00Z"}"#,
+        );
+        let process = CredentialProcess::new(
+            Some("process"),
+            Some(config_path),
+            Some("credentials_do_not_exist".to_string()),
+        );
+        assert!(process.provide().is_err());
+    }
+}
@@ -3,3 +3,24 @@ use anyhow::Result;
 pub trait ParseCredentials<T> {
     fn parse(&self) -> Result<T>;
 }
+
+/// A link of a `CredentialsChain`.
+///
+/// `provide` returns `Ok(None)` when the source is not configured, so that the chain
+/// moves on to the next link, and an error when it is configured but unusable.
+/// `describe` tells what `provide` would use without running or calling anything,
+/// `None` when the source is not configured.
+pub trait ProvideCredentials<T> {
+    fn name(&self) -> &str;
+    fn provide(&self) -> Result<Option<T>>;
+    fn describe(&self) -> Result<Option<String>>;
+}
+
+/// Name of the section holding `profile` in `~/.aws/config`.
+pub(crate) fn config_profile_section(profile: &str) -> String {
+    if profile == "default" {
+        "default".to_string()
+    } else {
+        format!("profile {}", profile)
+    }
+}
@@ -0,0 +1,415 @@
+use std::{collections::HashMap, env, fs, path::PathBuf};
+
+use anyhow::{anyhow, Context};
+use chrono::{DateTime, Utc};
+use serde::Deserialize;
+
+use crate::{ini::Ini, secret::Secret};
+
+use super::{
+    aws::AwsCredentials,
+    provider::{config_profile_section, ParseCredentials, ProvideCredentials},
+};
+
+const PORTAL_ENDPOINT_ENV: &str = "AWS_SSO_PORTAL_ENDPOINT";
+
+pub struct AwsSso<'a> {
+    profile: &'a str,
+    config_path: String,
+    cache_dir: String,
+    portal_endpoint: Option<String>,
+}
+
+#[derive(Debug, PartialEq)]
+struct SsoSettings {
+    start_url: String,
+    session_name: Option<String>,
+    sso_region: String,
+    account_id: String,
+    role_name: String,
+    region: String,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct SsoToken {
+    access_token: Secret,
+    expires_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct RoleCredentialsResponse {
+    role_credentials: RoleCredentials,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct RoleCredentials {
+    access_key_id: String,
+    secret_access_key: Secret,
+    session_token: Secret,
+    expiration: i64,
+}
+
+impl<'a> AwsSso<'a> {
+    pub fn new(
+        profile: Option<&'a str>,
+        config_path: Option<String>,
+        cache_dir: Option<String>,
+    ) -> Self {
+        let profile = profile.unwrap_or("default");
+        let config_path =
+            config_path.unwrap_or_else(|| shellexpand::tilde("~/.aws/config").to_string());
+        let cache_dir =
+            cache_dir.unwrap_or_else(|| shellexpand::tilde("~/.aws/sso/cache").to_string());
+        AwsSso {
+            profile,
+            config_path,
+            cache_dir,
+            portal_endpoint: None,
+        }
+    }
+
+    /// Overrides the portal used to exchange the cached token,
+    /// by default `https://portal.sso.<sso_region>.amazonaws.com`.
+    pub fn with_portal_endpoint(mut self, portal_endpoint: String) -> Self {
+        self.portal_endpoint = Some(portal_endpoint);
+        self
+    }
+
+    /// True when the profile in the aws config file has `sso_start_url` or `sso_session` set.
+    pub fn is_configured(&self) -> bool {
+        Ini::load(&self.config_path)
+            .ok()
+            .flatten()
+            .and_then(|ini| {
+                ini.section(&config_profile_section(self.profile))
+                    .map(|profile| {
+                        profile.contains_key("sso_start_url") || profile.contains_key("sso_session")
+                    })
+            })
+            .unwrap_or(false)
+    }
+
+    fn extract_sso_settings(&self, ini: &Ini) -> anyhow::Result<SsoSettings> {
+        let empty = HashMap::new();
+        let profile = ini
+            .section(&config_profile_section(self.profile))
+            .unwrap_or(&empty);
+        let session_name = profile.get("sso_session").cloned();
+        let session = match &session_name {
+            Some(name) => ini
+                .section(&format!("sso-session {}", name))
+                .ok_or_else(|| anyhow!("Missing [sso-session {}] in aws config", name))?,
+            None => &empty,
+        };
+        let get = |key: &str| {
+            session
+                .get(key)
+                .or_else(|| profile.get(key))
+                .cloned()
+                .ok_or_else(|| anyhow!("Missing {} for sso profile {}", key, self.profile))
+        };
+        let sso_region = get("sso_region")?;
+        Ok(SsoSettings {
+            start_url: get("sso_start_url")?,
+            account_id: get("sso_account_id")?,
+            role_name: get("sso_role_name")?,
+            region: profile
+                .get("region")
+                .cloned()
+                .unwrap_or_else(|| sso_region.clone()),
+            sso_region,
+            session_name,
+        })
+    }
+
+    fn cache_file_path(&self, settings: &SsoSettings) -> PathBuf {
+        let cache_key = settings
+            .session_name
+            .as_deref()
+            .unwrap_or(&settings.start_url);
+        let hash = sha1_smol::Sha1::from(cache_key).digest().to_string();
+        PathBuf::from(&self.cache_dir).join(format!("{}.json", hash))
+    }
+
+    fn read_cached_token(&self, settings: &SsoSettings) -> anyhow::Result<SsoToken> {
+        let login_hint = format!("run `aws sso login --profile {}`", self.profile);
+        let cache_file = fs::read_to_string(self.cache_file_path(settings)).with_context(|| {
+            format!(
+                "No cached SSO token for profile {}, {}",
+                self.profile, login_hint
+            )
+        })?;
+        let token: SsoToken = serde_json::from_str(&cache_file)
+            .with_context(|| format!("Invalid SSO token cache, {}", login_hint))?;
+        if token.expires_at <= Utc::now() {
+            return Err(anyhow!(
+                "SSO token for profile {} has expired, {}",
+                self.profile,
+                login_hint
+            ));
+        }
+        Ok(token)
+    }
+
+    fn exchange_token(
+        &self,
+        settings: &SsoSettings,
+        token: &SsoToken,
+    ) -> anyhow::Result<RoleCredentials> {
+        let portal_endpoint = self
+            .portal_endpoint
+            .clone()
+            .or_else(|| env::var(PORTAL_ENDPOINT_ENV).ok())
+            .unwrap_or_else(|| format!("https://portal.sso.{}.amazonaws.com", settings.sso_region));
+        let url = format!(
+            "{}/federation/credentials",
+            portal_endpoint.trim_end_matches('/')
+        );
+        let response = ureq::get(&url)
+            .query("account_id", &settings.account_id)
+            .query("role_name", &settings.role_name)
+            .set("x-amz-sso_bearer_token", token.access_token.expose())
+            .call();
+        match response {
+            Ok(response) => {
+                let body: RoleCredentialsResponse = response
+                    .into_json()
+                    .with_context(|| "Invalid response from SSO portal")?;
+                Ok(body.role_credentials)
+            }
+            Err(ureq::Error::Status(401 | 403, _)) => Err(anyhow!(
+                "SSO token for profile {} was rejected, run `aws sso login --profile {}`",
+                self.profile,
+                self.profile
+            )),
+            Err(e) => {
+                Err(anyhow!(e)).with_context(|| "Failed to get role credentials from SSO portal")
+            }
+        }
+    }
+}
+
+impl<'a> ParseCredentials<AwsCredentials> for AwsSso<'a> {
+    fn parse(&self) -> anyhow::Result<AwsCredentials> {
+        let ini = Ini::load(&self.config_path)?.ok_or_else(|| anyhow!("File does not exist"))?;
+        let settings = self.extract_sso_settings(&ini)?;
+        let token = self.read_cached_token(&settings)?;
+        let role_credentials = self.exchange_token(&settings, &token)?;
+        Ok(AwsCredentials {
+            access_key_id: role_credentials.access_key_id,
+            secret_access_key: role_credentials.secret_access_key,
+            session_token: role_credentials.session_token,
+            region: settings.region,
+            expiration: DateTime::from_timestamp_millis(role_credentials.expiration),
+        })
+    }
+}
+
+impl<'a> ProvideCredentials<AwsCredentials> for AwsSso<'a> {
+    fn name(&self) -> &str {
+        "sso"
+    }
+
+    fn provide(&self) -> anyhow::Result<Option<AwsCredentials>> {
+        if !self.is_configured() {
+            return Ok(None);
+        }
+        self.parse().map(Some)
+    }
+
+    fn describe(&self) -> anyhow::Result<Option<String>> {
+        if !self.is_configured() {
+            return Ok(None);
+        }
+        let ini = Ini::load(&self.config_path)?
+            .ok_or_else(|| anyhow!("Failed to read {}", self.config_path))?;
+        let settings = self.extract_sso_settings(&ini)?;
+        Ok(Some(format!(
+            "exchanges the cached token of {} for role {} of account {}",
+            settings.start_url, settings.role_name, settings.account_id
+        )))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::{
+        fs::{self, File},
+        io::Write,
+    };
+
+    use chrono::{Duration, Utc};
+
+    use crate::{
+        generated_test_files_path, ini::Ini, providers::provider::ParseCredentials, serve,
+    };
+
+    use super::AwsSso;
+
+    const CONFIG_FILE: &str = "[default]
+    region=eu-west-1
+
+    [profile legacy]
+    sso_start_url=https://legacy.awsapps.com/start
+    sso_region=us-east-1
+    sso_account_id=111111111111
+    sso_role_name=ReadOnly
+    region=eu-central-1
+
+    [profile session]
+    sso_session=team
+    sso_account_id=222222222222
+    sso_role_name=Admin
+
+    [profile session-readonly]
+    sso_session=other
+
+    [sso-session team]
+    sso_start_url=https://team.awsapps.com/start
+    sso_region=eu-west-1
+    ";
+
+    const ROLE_CREDENTIALS: &str = r#"{"roleCredentials":{"accessKeyId":"ssoid","secretAccessKey":"ssosecret","sessionToken":"ssotoken","expiration":1700000000000}}"#;
+
+    fn write_test_setup(
+        test_name: &str,
+        cache_key: &str,
+        expires_in: Duration,
+    ) -> (String, String) {
+        let config_path = generated_test_files_path!(format!("{}_config", test_name));
+        File::create(&config_path)
+            .and_then(|mut file| file.write_all(CONFIG_FILE.as_bytes()))
+            .expect("should be able to write config file in test");
+        let cache_dir = generated_test_files_path!(format!("{}_cache", test_name));
+        fs::create_dir_all(&cache_dir).expect("should be able to create cache dir in test");
+        let cache_file = format!(
+            "{}/{}.json",
+            cache_dir,
+            sha1_smol::Sha1::from(cache_key).digest()
+        );
+        let token = format!(
+            r#"{{"startUrl":"https://team.awsapps.com/start","region":"eu-west-1","accessToken":"cachedtoken","expiresAt":"{}"}}"#,
+            (Utc::now() + expires_in).format("%Y-%m-%dT%H:%M:%SZ")
+        );
+        fs::write(cache_file, token).expect("should be able to write token in test");
+        (config_path, cache_dir)
+    }
+
+    #[test]
+    fn test_is_configured() {
+        let (config_path, cache_dir) =
+            write_test_setup("sso_is_configured", "team", Duration::hours(1));
+        let legacy = AwsSso::new(
+            Some("legacy"),
+            Some(config_path.clone()),
+            Some(cache_dir.clone()),
+        );
+        assert!(legacy.is_configured());
+        let session = AwsSso::new(
+            Some("session"),
+            Some(config_path.clone()),
+            Some(cache_dir.clone()),
+        );
+        assert!(session.is_configured());
+        let default = AwsSso::new(None, Some(config_path), Some(cache_dir));
+        assert!(!default.is_configured());
+    }
+
+    #[test]
+    fn test_extract_sso_settings_from_session() {
+        let aws_sso = AwsSso::new(Some("session"), None, None);
+        let settings = aws_sso
+            .extract_sso_settings(&Ini::parse(CONFIG_FILE).unwrap())
+            .expect("settings should be extracted");
+        assert_eq!(settings.start_url, "https://team.awsapps.com/start");
+        assert_eq!(settings.session_name.as_deref(), Some("team"));
+        assert_eq!(settings.sso_region, "eu-west-1");
+        assert_eq!(settings.account_id, "222222222222");
+        assert_eq!(settings.role_name, "Admin");
+        assert_eq!(settings.region, "eu-west-1");
+    }
+
+    #[test]
+    fn test_extract_sso_settings_missing_session() {
+        let aws_sso = AwsSso::new(Some("session-readonly"), None, None);
+        let result = aws_sso.extract_sso_settings(&Ini::parse(CONFIG_FILE).unwrap());
+        assert!(result.is_err());
+        assert_eq!(
+            result.unwrap_err().to_string(),
+            "Missing [sso-session other] in aws config"
+        );
+    }
+
+    #[test]
+    fn test_parse_legacy_profile() {
+        let (config_path, cache_dir) = write_test_setup(
+            "sso_parse_legacy_profile",
+            "https://legacy.awsapps.com/start",
+            Duration::hours(1),
+        );
+        let (endpoint, requests) = serve(vec![ROLE_CREDENTIALS]);
+        let aws_sso = AwsSso::new(Some("legacy"), Some(config_path), Some(cache_dir))
+            .with_portal_endpoint(endpoint);
+        let credentials = aws_sso.parse().expect("credentials should be exchanged");
+
+        assert_eq!(credentials.access_key_id, "ssoid");
+        assert_eq!(credentials.secret_access_key.expose(), "ssosecret");
+        assert_eq!(credentials.session_token.expose(), "ssotoken");
+        assert_eq!(credentials.region, "eu-central-1");
+
+        let request = &requests.join().unwrap()[0];
+        assert!(request
+            .starts_with("get /federation/credentials?account_id=111111111111&role_name=readonly"));
+        assert!(request.contains("x-amz-sso_bearer_token: cachedtoken"));
+    }
+
+    #[test]
+    fn test_parse_session_profile() {
+        let (config_path, cache_dir) =
+            write_test_setup("sso_parse_session_profile", "team", Duration::hours(1));
+        let (endpoint, _) = serve(vec![ROLE_CREDENTIALS]);
+        let aws_sso = AwsSso::new(Some("session"), Some(config_path), Some(cache_dir))
+            .with_portal_endpoint(endpoint);
+        let credentials = aws_sso.parse().expect("credentials should be exchanged");
+
+        assert_eq!(credentials.access_key_id, "ssoid");
+        assert_eq!(credentials.region, "eu-west-1");
+    }
+
+    #[test]
+    fn test_parse_failure_expired_token() {
+        let (config_path, cache_dir) = write_test_setup(
+            "sso_parse_failure_expired_token",
+            "team",
+            Duration::hours(-1),
+        );
+        let aws_sso = AwsSso::new(Some("session"), Some(config_path), Some(cache_dir));
+        let result = aws_sso.parse();
+        assert!(result.is_err());
+        assert_eq!(
+            result.unwrap_err().to_string(),
+            "SSO token for profile session has expired, run `aws sso login --profile session`"
+        );
+    }
+
+    #[test]
+    fn test_parse_failure_rejected_token() {
+        let (config_path, cache_dir) = write_test_setup(
+            "sso_parse_failure_rejected_token",
+            "team",
+            Duration::hours(1),
+        );
+        let (endpoint, _) = serve(vec!["HTTP/1.1 401 Unauthorized\r\n\r\n{}"]);
+        let aws_sso = AwsSso::new(Some("session"), Some(config_path), Some(cache_dir))
+            .with_portal_endpoint(endpoint);
+        let result = aws_sso.parse();
+        assert!(result.is_err());
+        assert!(result
+            .unwrap_err()
+            .to_string()
+            .contains("run `aws sso login --profile session`"));
+    }
+}
@@ -0,0 +1,108 @@
+use anyhow::{anyhow, Context, Result};
+use chrono::Utc;
+
+use crate::{
+    providers::aws::AwsCredentials,
+    sigv4::{self, hash_hex, host_of, uri_encode, Request, AMZ_DATE_FORMAT},
+};
+
+/// Splits `s3://bucket/some/key` into the bucket and the key.
+pub fn parse_s3_url(url: &str) -> Result<(&str, &str)> {
+    url.strip_prefix("s3://")
+        .and_then(|rest| rest.split_once('/'))
+        .filter(|(bucket, key)| !bucket.is_empty() && !key.is_empty())
+        .ok_or_else(|| anyhow!("{} is not in the format s3://bucket/key", url))
+}
+
+/// Downloads a text object. Without an endpoint the regional virtual hosted url is used,
+/// a custom endpoint is called path style, as in `{endpoint}/{bucket}/{key}`.
+pub fn get_object(
+    credentials: &AwsCredentials,
+    url: &str,
+    endpoint: Option<&str>,
+) -> Result<String> {
+    let (bucket, key) = parse_s3_url(url)?;
+    let key = key.split('/').map(uri_encode).collect::<Vec<_>>().join("/");
+    let (base, path) = match endpoint {
+        Some(endpoint) => (
+            endpoint.trim_end_matches('/').to_string(),
+            format!("/{}/{}", bucket, key),
+        ),
+        None => (
+            format!("https://{}.s3.{}.amazonaws.com", bucket, credentials.region),
+            format!("/{}", key),
+        ),
+    };
+    let host = host_of(&base);
+    let now = Utc::now();
+    let payload_hash = hash_hex(b"");
+    let request = Request {
+        method: "GET",
+        host: &host,
+        path: &path,
+        query: "",
+        headers: vec![("x-amz-content-sha256", &payload_hash)],
+        payload_hash: payload_hash.clone(),
+    };
+    let authorization = sigv4::sign(credentials, "s3", &request, &now);
+    let mut call = ureq::get(&format!("{}{}", base, path))
+        .set("X-Amz-Date", &now.format(AMZ_DATE_FORMAT).to_string())
+        .set("X-Amz-Content-Sha256", &payload_hash)
+        .set("Authorization", &authorization);
+    if !credentials.session_token.expose().is_empty() {
+        call = call.set("X-Amz-Security-Token", credentials.session_token.expose());
+    }
+    match call.call() {
+        Ok(response) => response
+            .into_string()
+            .with_context(|| format!("Invalid content in {}", url)),
+        Err(ureq::Error::Status(code, _)) => {
+            Err(anyhow!("Failed to download {}, s3 answered {}", url, code))
+        }
+        Err(e) => Err(anyhow!(e)).with_context(|| format!("Failed to download {}", url)),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{providers::aws::AwsCredentials, serve};
+
+    use super::{get_object, parse_s3_url};
+
+    #[test]
+    fn test_parse_s3_url() {
+        assert_eq!(
+            parse_s3_url("s3://bucket/team/patterns.toml").unwrap(),
+            ("bucket", "team/patterns.toml")
+        );
+        assert!(parse_s3_url("s3://bucket").is_err());
+        assert!(parse_s3_url("/local/patterns.toml").is_err());
+    }
+
+    #[test]
+    fn test_get_object() {
+        let (endpoint, requests) = serve(vec![
+            "[patterns.events]\nvalue = \"s3://bucket/events/{date}.parquet\"\n",
+        ]);
+        let credentials = AwsCredentials {
+            access_key_id: "AKIDEXAMPLE".to_string(),
+            secret_access_key: "secret".into(),
+            session_token: "".into(),
+            region: "eu-west-1".to_string(),
+            expiration: None,
+        };
+        let content = get_object(
+            &credentials,
+            "s3://bucket/team/patterns v1.toml",
+            Some(&endpoint),
+        )
+        .expect("object should be downloaded");
+        assert!(content.contains("[patterns.events]"));
+
+        let request = &requests.join().unwrap()[0];
+        assert!(request.starts_with("get /bucket/team/patterns%20v1.toml "));
+        assert!(request.contains("authorization: aws4-hmac-sha256 credential=akidexample/"));
+        assert!(request.contains("/eu-west-1/s3/aws4_request"));
+        assert!(!request.contains("x-amz-security-token"));
+    }
+}
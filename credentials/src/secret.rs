@@ -0,0 +1,76 @@
+use std::fmt;
+
+use serde::Deserialize;
+use zeroize::Zeroize;
+
+pub(crate) const REDACTED: &str = "********";
+
+/// A secret value that is redacted when formatted and zeroed when dropped.
+///
+/// The value is only reachable through `expose`.
+#[derive(Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(transparent)]
+pub struct Secret(String);
+
+impl Secret {
+    pub fn new(value: String) -> Self {
+        Secret(value)
+    }
+
+    pub fn expose(&self) -> &str {
+        &self.0
+    }
+}
+
+impl From<String> for Secret {
+    fn from(value: String) -> Self {
+        Secret(value)
+    }
+}
+
+impl From<&str> for Secret {
+    fn from(value: &str) -> Self {
+        Secret(value.to_string())
+    }
+}
+
+impl PartialEq<str> for Secret {
+    fn eq(&self, other: &str) -> bool {
+        self.0 == other
+    }
+}
+
+impl fmt::Debug for Secret {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Secret({})", REDACTED)
+    }
+}
+
+impl fmt::Display for Secret {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(REDACTED)
+    }
+}
+
+impl Drop for Secret {
+    fn drop(&mut self) {
+        self.0.zeroize();
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::Secret;
+
+    #[test]
+    fn test_secret_is_redacted() {
+        let secret = Secret::from("supersecret");
+        assert_eq!(format!("{}", secret), "********");
+        assert_eq!(format!("{:?}", secret), "Secret(********)");
+        assert_eq!(
+            format!("{:?}", Some(secret.clone())),
+            "Some(Secret(********))"
+        );
+        assert_eq!(secret.expose(), "supersecret");
+    }
+}
@@ -0,0 +1,157 @@
+use chrono::{DateTime, Utc};
+use hmac_sha256::{Hash, HMAC};
+
+use crate::providers::aws::AwsCredentials;
+
+pub const AMZ_DATE_FORMAT: &str = "%Y%m%dT%H%M%SZ";
+
+/// The parts of a request covered by the signature. `path` and `query` must already be
+/// uri encoded and `headers` lowercase, host and x-amz-date are added when signing.
+pub struct Request<'a> {
+    pub method: &'a str,
+    pub host: &'a str,
+    pub path: &'a str,
+    pub query: &'a str,
+    pub headers: Vec<(&'a str, &'a str)>,
+    pub payload_hash: String,
+}
+
+pub fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+pub fn hash_hex(payload: &[u8]) -> String {
+    hex(&Hash::hash(payload))
+}
+
+/// Encodes a path segment or query value, keeping only the unreserved characters.
+pub fn uri_encode(value: &str) -> String {
+    value
+        .bytes()
+        .map(|byte| match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                (byte as char).to_string()
+            }
+            _ => format!("%{:02X}", byte),
+        })
+        .collect()
+}
+
+/// The host of an endpoint like `https://sts.us-east-1.amazonaws.com/`.
+pub fn host_of(endpoint: &str) -> String {
+    endpoint
+        .split_once("://")
+        .map(|(_, rest)| rest)
+        .unwrap_or(endpoint)
+        .split('/')
+        .next()
+        .unwrap_or_default()
+        .to_string()
+}
+
+/// Signature version 4 of the request, returned as the value of the authorization header.
+pub fn sign(
+    credentials: &AwsCredentials,
+    service: &str,
+    request: &Request,
+    now: &DateTime<Utc>,
+) -> String {
+    let amz_date = now.format(AMZ_DATE_FORMAT).to_string();
+    let date = now.format("%Y%m%d").to_string();
+    let mut headers = request.headers.clone();
+    headers.push(("host", request.host));
+    headers.push(("x-amz-date", &amz_date));
+    if !credentials.session_token.expose().is_empty() {
+        headers.push(("x-amz-security-token", credentials.session_token.expose()));
+    }
+    headers.sort();
+    let canonical_headers: String = headers
+        .iter()
+        .map(|(name, value)| format!("{}:{}\n", name, value.trim()))
+        .collect();
+    let signed_headers = headers
+        .iter()
+        .map(|(name, _)| *name)
+        .collect::<Vec<_>>()
+        .join(";");
+    let canonical_request = format!(
+        "{}\n{}\n{}\n{}\n{}\n{}",
+        request.method,
+        request.path,
+        request.query,
+        canonical_headers,
+        signed_headers,
+        request.payload_hash
+    );
+    let scope = format!("{}/{}/{}/aws4_request", date, credentials.region, service);
+    let string_to_sign = format!(
+        "AWS4-HMAC-SHA256\n{}\n{}\n{}",
+        amz_date,
+        scope,
+        hash_hex(canonical_request.as_bytes())
+    );
+    let key = HMAC::mac(
+        date.as_bytes(),
+        format!("AWS4{}", credentials.secret_access_key.expose()).as_bytes(),
+    );
+    let key = HMAC::mac(credentials.region.as_bytes(), key);
+    let key = HMAC::mac(service.as_bytes(), key);
+    let key = HMAC::mac(b"aws4_request", key);
+    let signature = hex(&HMAC::mac(string_to_sign.as_bytes(), key));
+    format!(
+        "AWS4-HMAC-SHA256 Credential={}/{}, SignedHeaders={}, Signature={}",
+        credentials.access_key_id, scope, signed_headers, signature
+    )
+}
+
+#[cfg(test)]
+mod test {
+    use chrono::{TimeZone, Utc};
+
+    use crate::providers::aws::AwsCredentials;
+
+    use super::{hash_hex, host_of, sign, uri_encode, Request};
+
+    /// The get-vanilla case of the AWS signature version 4 test suite.
+    #[test]
+    fn test_sign_get_vanilla() {
+        let credentials = AwsCredentials {
+            access_key_id: "AKIDEXAMPLE".to_string(),
+            secret_access_key: "wJalrXUtnFEMI/K7MDENG+bPxRfiCYEXAMPLEKEY".into(),
+            session_token: "".into(),
+            region: "us-east-1".to_string(),
+            expiration: None,
+        };
+        let request = Request {
+            method: "GET",
+            host: "example.amazonaws.com",
+            path: "/",
+            query: "",
+            headers: vec![],
+            payload_hash: hash_hex(b""),
+        };
+        let now = Utc.with_ymd_and_hms(2015, 8, 30, 12, 36, 0).unwrap();
+        assert_eq!(
+            sign(&credentials, "service", &request, &now),
+            "AWS4-HMAC-SHA256 Credential=AKIDEXAMPLE/20150830/us-east-1/service/aws4_request, SignedHeaders=host;x-amz-date, Signature=5fa00fa31553b73ebf1942676e86291e8372ff2a2260956d9b8aae1d763fbf31"
+        );
+    }
+
+    #[test]
+    fn test_uri_encode() {
+        assert_eq!(
+            uri_encode("team patterns+v1.toml"),
+            "team%20patterns%2Bv1.toml"
+        );
+        assert_eq!(uri_encode("a-b_c~d"), "a-b_c~d");
+    }
+
+    #[test]
+    fn test_host_of() {
+        assert_eq!(
+            host_of("https://sts.us-east-1.amazonaws.com/"),
+            "sts.us-east-1.amazonaws.com"
+        );
+        assert_eq!(host_of("http://127.0.0.1:9000"), "127.0.0.1:9000");
+    }
+}
@@ -0,0 +1,105 @@
+use anyhow::{anyhow, Context, Result};
+use chrono::{DateTime, Utc};
+
+use crate::{
+    providers::aws::AwsCredentials,
+    sigv4::{self, hash_hex, host_of, Request, AMZ_DATE_FORMAT},
+};
+
+const GET_CALLER_IDENTITY_BODY: &str = "Action=GetCallerIdentity&Version=2011-06-15";
+const CONTENT_TYPE: &str = "application/x-www-form-urlencoded; charset=utf-8";
+
+pub fn default_sts_endpoint(region: &str) -> String {
+    format!("https://sts.{}.amazonaws.com", region)
+}
+
+/// Calls sts GetCallerIdentity and returns the arn the credentials belong to.
+pub fn get_caller_identity(credentials: &AwsCredentials, endpoint: Option<&str>) -> Result<String> {
+    let endpoint = endpoint
+        .map(|endpoint| endpoint.to_string())
+        .unwrap_or_else(|| default_sts_endpoint(&credentials.region));
+    let host = host_of(&endpoint);
+    let amz_date = Utc::now();
+    let authorization = sign(credentials, &host, &amz_date);
+    let mut request = ureq::post(&format!("{}/", endpoint.trim_end_matches('/')))
+        .set("Content-Type", CONTENT_TYPE)
+        .set("X-Amz-Date", &amz_date.format(AMZ_DATE_FORMAT).to_string())
+        .set("Authorization", &authorization);
+    if !credentials.session_token.expose().is_empty() {
+        request = request.set("X-Amz-Security-Token", credentials.session_token.expose());
+    }
+    let response = request.send_string(GET_CALLER_IDENTITY_BODY);
+    let body = match response {
+        Ok(response) => response
+            .into_string()
+            .with_context(|| "Invalid response from sts")?,
+        Err(ureq::Error::Status(code, response)) => {
+            return Err(anyhow!(
+                "sts rejected the credentials with status {}: {}",
+                code,
+                response.into_string().unwrap_or_default()
+            ))
+        }
+        Err(e) => return Err(anyhow!(e)).with_context(|| "Failed to call sts"),
+    };
+    body.split_once("<Arn>")
+        .and_then(|(_, rest)| rest.split_once("</Arn>"))
+        .map(|(arn, _)| arn.to_string())
+        .ok_or_else(|| anyhow!("Missing Arn in sts response"))
+}
+
+/// Signature version 4 of the GetCallerIdentity request.
+fn sign(credentials: &AwsCredentials, host: &str, now: &DateTime<Utc>) -> String {
+    let request = Request {
+        method: "POST",
+        host,
+        path: "/",
+        query: "",
+        headers: vec![("content-type", CONTENT_TYPE)],
+        payload_hash: hash_hex(GET_CALLER_IDENTITY_BODY.as_bytes()),
+    };
+    sigv4::sign(credentials, "sts", &request, now)
+}
+
+#[cfg(test)]
+mod test {
+    use chrono::{TimeZone, Utc};
+
+    use crate::{providers::aws::AwsCredentials, serve};
+
+    use super::{get_caller_identity, sign};
+
+    fn test_credentials() -> AwsCredentials {
+        AwsCredentials {
+            access_key_id: "AKIDEXAMPLE".to_string(),
+            secret_access_key: "wJalrXUtnFEMI/K7MDENG+bPxRfiCYEXAMPLEKEY".into(),
+            session_token: "token".into(),
+            region: "us-east-1".to_string(),
+            expiration: None,
+        }
+    }
+
+    #[test]
+    fn test_sign() {
+        let now = Utc.with_ymd_and_hms(2024, 2, 1, 17, 2, 0).unwrap();
+        let authorization = sign(&test_credentials(), "sts.us-east-1.amazonaws.com", &now);
+        assert_eq!(
+            authorization,
+            "AWS4-HMAC-SHA256 Credential=AKIDEXAMPLE/20240201/us-east-1/sts/aws4_request, SignedHeaders=content-type;host;x-amz-date;x-amz-security-token, Signature=aea1a245363f79210dd3fea0d3ef321a0db97137302062276985e3d243cd3a09"
+        );
+    }
+
+    #[test]
+    fn test_get_caller_identity() {
+        let (endpoint, requests) = serve(vec![
+            "<GetCallerIdentityResponse><GetCallerIdentityResult><Arn>arn:aws:sts::123456789012:assumed-role/Admin/me</Arn></GetCallerIdentityResult></GetCallerIdentityResponse>",
+        ]);
+        let arn = get_caller_identity(&test_credentials(), Some(&endpoint))
+            .expect("caller identity should be returned");
+        assert_eq!(arn, "arn:aws:sts::123456789012:assumed-role/Admin/me");
+        let requests = requests.join().unwrap();
+        let (request, body) = requests[0].split_once("\r\n\r\n").unwrap();
+        assert!(request.contains("authorization: aws4-hmac-sha256 credential=akidexample/"));
+        assert_eq!(body, "Action=GetCallerIdentity&Version=2011-06-15");
+    }
+}
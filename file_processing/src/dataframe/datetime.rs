@@ -53,6 +53,7 @@ fn extract_date_parts(value: &str) -> Result<DateParts> {
 }
 
 #[cfg(test)]
+#[allow(clippy::zero_prefixed_literal)]
 mod tests {
     use super::*;
     #[test]
@@ -21,6 +21,7 @@ pub fn get_index_expr_if_needed(
 }
 
 #[cfg(test)]
+#[allow(clippy::assertions_on_constants)]
 mod tests {
     use super::*;
     use polars::lazy::dsl::Expr;
@@ -1,5 +1,61 @@
-use anyhow::Result;
-use polars::{frame::DataFrame, lazy::frame::LazyFrame};
+use std::io::Write;
+
+use anyhow::{anyhow, Context, Result};
+use clap::ValueEnum;
+use polars::{
+    frame::DataFrame,
+    io::{csv::CsvWriter, parquet::ParquetWriter, SerWriter},
+    lazy::frame::LazyFrame,
+};
+
+/// Format of the output file.
+#[derive(Debug, Default, Clone, Copy, PartialEq, ValueEnum)]
+pub enum OutputFormat {
+    #[default]
+    Csv,
+    Parquet,
+}
+
+impl OutputFormat {
+    pub fn parse(format: &str) -> Result<Self> {
+        OutputFormat::from_str(format, true)
+            .map_err(|_| anyhow!("Unknown output format {}, use csv or parquet", format))
+    }
+
+    /// Parquet for `.parq` and `.parquet` files, csv otherwise.
+    pub fn of(path: &str) -> Self {
+        let path = path.to_lowercase();
+        if path.ends_with(".parq") || path.ends_with(".parquet") {
+            OutputFormat::Parquet
+        } else {
+            OutputFormat::Csv
+        }
+    }
+}
+
+/// Writes the rows as csv with a header, to stdout for instance.
+pub fn write_csv(df: &mut DataFrame, writer: impl Write) -> Result<()> {
+    CsvWriter::new(writer)
+        .finish(df)
+        .with_context(|| anyhow!("Failed to write csv output"))
+}
+
+pub fn write_output(df: &mut DataFrame, path: &str, format: OutputFormat) -> Result<()> {
+    let file = std::fs::File::create(path).with_context(|| anyhow!("Failed to create file"))?;
+    match format {
+        OutputFormat::Csv => {
+            CsvWriter::new(file)
+                .finish(df)
+                .with_context(|| anyhow!("Failed to write csv output file"))?;
+        }
+        OutputFormat::Parquet => {
+            ParquetWriter::new(file)
+                .finish(df)
+                .with_context(|| anyhow!("Failed to write parquet output file"))?;
+        }
+    }
+    Ok(())
+}
 
 pub trait ScanFile {
     fn scan(&self) -> Result<LazyFrame>;
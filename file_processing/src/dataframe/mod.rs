@@ -3,5 +3,8 @@ pub mod expressions;
 pub mod file;
 pub mod operations;
 pub mod parq;
+pub mod plan;
 pub mod processor;
+pub mod stats;
+#[allow(clippy::module_inception)]
 mod test;
@@ -1,8 +1,178 @@
+use anyhow::{anyhow, Result};
 use polars::lazy::{
-    dsl::{col, Expr},
+    dsl::{col, len, lit, Expr},
     frame::LazyFrame,
 };
 
+use super::datetime::to_datetime_expression;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CompareOp {
+    Eq,
+    NotEq,
+    Gt,
+    GtEq,
+    Lt,
+    LtEq,
+}
+
+impl CompareOp {
+    pub fn parse(op: &str) -> Result<Self> {
+        let op = match op {
+            "=" | "==" => CompareOp::Eq,
+            "!=" => CompareOp::NotEq,
+            ">" => CompareOp::Gt,
+            ">=" => CompareOp::GtEq,
+            "<" => CompareOp::Lt,
+            "<=" => CompareOp::LtEq,
+            _ => return Err(anyhow!("Unknown operator {}, use = != > >= < <=", op)),
+        };
+        Ok(op)
+    }
+
+    pub fn apply(self, left: Expr, right: Expr) -> Expr {
+        match self {
+            CompareOp::Eq => left.eq(right),
+            CompareOp::NotEq => left.neq(right),
+            CompareOp::Gt => left.gt(right),
+            CompareOp::GtEq => left.gt_eq(right),
+            CompareOp::Lt => left.lt(right),
+            CompareOp::LtEq => left.lt_eq(right),
+        }
+    }
+}
+
+/// A number, a datetime like `2024-02-01 17:01:00`, a boolean or else a string.
+pub fn value_expr(value: &str) -> Expr {
+    if let Ok(value) = value.parse::<i64>() {
+        return lit(value);
+    }
+    if let Ok(value) = value.parse::<f64>() {
+        return lit(value);
+    }
+    if let Ok(datetime) = to_datetime_expression(value) {
+        return datetime;
+    }
+    match value {
+        "true" => lit(true),
+        "false" => lit(false),
+        _ => lit(value.to_string()),
+    }
+}
+
+/// A `column op value` filter, the value may be quoted.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Filter {
+    pub column: String,
+    pub op: CompareOp,
+    pub value: String,
+}
+
+impl Filter {
+    pub fn parse(filter: &str) -> Result<Self> {
+        let mut parts = filter.trim().splitn(3, char::is_whitespace);
+        match (parts.next(), parts.next(), parts.next()) {
+            (Some(column), Some(op), Some(value)) if !column.is_empty() => Ok(Filter {
+                column: column.to_string(),
+                op: CompareOp::parse(op)?,
+                value: unquote(value).to_string(),
+            }),
+            _ => Err(anyhow!(
+                "Filter {} needs a column, an operator and a value",
+                filter
+            )),
+        }
+    }
+
+    pub fn expr(&self) -> Expr {
+        self.op.apply(col(&self.column), value_expr(&self.value))
+    }
+}
+
+/// An aggregation like `sum(amount)` or `count()`, its result is named `amount_sum` or `count`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Aggregation {
+    pub function: String,
+    pub column: Option<String>,
+}
+
+impl Aggregation {
+    const FUNCTIONS: [&'static str; 6] = ["count", "sum", "mean", "min", "max", "n_unique"];
+
+    pub fn parse(aggregation: &str) -> Result<Self> {
+        let (function, column) = aggregation
+            .trim()
+            .strip_suffix(')')
+            .and_then(|call| call.split_once('('))
+            .ok_or_else(|| {
+                anyhow!(
+                    "Aggregation {} is not in the format function(column)",
+                    aggregation
+                )
+            })?;
+        let function = function.trim();
+        if !Self::FUNCTIONS.contains(&function) {
+            return Err(anyhow!(
+                "Unknown aggregation {}, use one of {}",
+                function,
+                Self::FUNCTIONS.join(" ")
+            ));
+        }
+        let column = Some(column.trim())
+            .filter(|column| !column.is_empty())
+            .map(String::from);
+        if column.is_none() && function != "count" {
+            return Err(anyhow!("Aggregation {} needs a column", aggregation));
+        }
+        Ok(Aggregation {
+            function: function.to_string(),
+            column,
+        })
+    }
+
+    pub fn expr(&self) -> Expr {
+        let Some(column) = &self.column else {
+            return len().alias("count");
+        };
+        let expr = col(column);
+        let expr = match self.function.as_str() {
+            "count" => expr.count(),
+            "sum" => expr.sum(),
+            "mean" => expr.mean(),
+            "min" => expr.min(),
+            "max" => expr.max(),
+            _ => expr.n_unique(),
+        };
+        expr.alias(&format!("{}_{}", column, self.function))
+    }
+}
+
+/// Groups the rows by `group_by` and aggregates each group, or every row when there
+/// is nothing to group by.
+pub fn aggregate(df: LazyFrame, group_by: &[String], aggregations: &[String]) -> Result<LazyFrame> {
+    let aggregations = aggregations
+        .iter()
+        .map(|aggregation| Ok(Aggregation::parse(aggregation)?.expr()))
+        .collect::<Result<Vec<_>>>()?;
+    if group_by.is_empty() {
+        return Ok(df.select(aggregations));
+    }
+    let group_by: Vec<Expr> = group_by.iter().map(|column| col(column)).collect();
+    Ok(df.group_by_stable(group_by).agg(aggregations))
+}
+
+fn unquote(value: &str) -> &str {
+    let value = value.trim();
+    ['"', '\'']
+        .iter()
+        .find_map(|quote| {
+            value
+                .strip_prefix(*quote)
+                .and_then(|value| value.strip_suffix(*quote))
+        })
+        .unwrap_or(value)
+}
+
 pub fn filter_columns(df: LazyFrame, columns: &Option<Vec<Expr>>) -> LazyFrame {
     if let Some(exprs) = columns {
         df.select(exprs)
@@ -11,6 +181,39 @@ pub fn filter_columns(df: LazyFrame, columns: &Option<Vec<Expr>>) -> LazyFrame {
     }
 }
 
+/// Splits a sort column like `amount:desc` into the column and whether it is descending.
+pub fn parse_sort_column(sort_column: &str) -> Result<(&str, bool)> {
+    let (column, descending) = match sort_column.rsplit_once(':') {
+        Some((column, "desc")) => (column, true),
+        Some((column, "asc")) => (column, false),
+        Some(_) => {
+            return Err(anyhow!(
+                "Sort {} is not in the format column[:asc|:desc]",
+                sort_column
+            ))
+        }
+        None => (sort_column, false),
+    };
+    if column.is_empty() {
+        return Err(anyhow!("Sort {} has no column", sort_column));
+    }
+    Ok((column, descending))
+}
+
+pub fn sort_columns(df: LazyFrame, sort_columns: &[String]) -> Result<LazyFrame> {
+    if sort_columns.is_empty() {
+        return Ok(df);
+    }
+    let mut exprs = Vec::new();
+    let mut descending = Vec::new();
+    for sort_column in sort_columns {
+        let (column, column_descending) = parse_sort_column(sort_column)?;
+        exprs.push(col(column));
+        descending.push(column_descending);
+    }
+    Ok(df.sort_by_exprs(exprs, descending, false, false))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -51,4 +254,89 @@ mod tests {
         let filtered_df = filter_columns(lazy_df.clone(), &columns).collect().unwrap();
         assert_eq!(filtered_df, df);
     }
+
+    #[test]
+    fn test_sort_columns() {
+        let df = DataFrame::new(vec![
+            Series::new("A", &[1, 2, 1]),
+            Series::new("B", &[4, 5, 6]),
+        ])
+        .unwrap();
+
+        let sort = vec!["A".to_string(), "B:desc".to_string()];
+        let sorted = sort_columns(df.clone().lazy(), &sort)
+            .unwrap()
+            .collect()
+            .unwrap();
+        let expected_df = DataFrame::new(vec![
+            Series::new("A", &[1, 1, 2]),
+            Series::new("B", &[6, 4, 5]),
+        ])
+        .unwrap();
+        assert_eq!(sorted, expected_df);
+
+        assert!(sort_columns(df.lazy(), &["A:down".to_string()]).is_err());
+        assert_eq!(parse_sort_column("a:b:asc").unwrap(), ("a:b", false));
+    }
+
+    #[test]
+    fn test_filter() {
+        let filter = Filter::parse("transaction_time >= '2024-02-01 17:01:00'").unwrap();
+        assert_eq!(
+            filter,
+            Filter {
+                column: "transaction_time".to_string(),
+                op: CompareOp::GtEq,
+                value: "2024-02-01 17:01:00".to_string(),
+            }
+        );
+        assert!(Filter::parse("open ~ 3").is_err());
+        assert!(Filter::parse("open >").is_err());
+
+        let df = DataFrame::new(vec![Series::new("A", &[1, 2, 3])]).unwrap();
+        let filtered = df
+            .lazy()
+            .filter(Filter::parse("A != 2").unwrap().expr())
+            .collect()
+            .unwrap();
+        assert_eq!(filtered.column("A").unwrap().len(), 2);
+    }
+
+    #[test]
+    fn test_aggregate() {
+        assert_eq!(
+            Aggregation::parse(" sum( amount ) ").unwrap(),
+            Aggregation {
+                function: "sum".to_string(),
+                column: Some("amount".to_string()),
+            }
+        );
+        assert!(Aggregation::parse("sum").is_err());
+        assert!(Aggregation::parse("median(amount)").is_err());
+        assert!(Aggregation::parse("max()").is_err());
+
+        let df = DataFrame::new(vec![
+            Series::new("A", &["x", "y", "x"]),
+            Series::new("B", &[1, 2, 3]),
+        ])
+        .unwrap();
+        let aggregations = ["count()".to_string(), "sum(B)".to_string()];
+        let grouped = aggregate(df.clone().lazy(), &["A".to_string()], &aggregations)
+            .unwrap()
+            .collect()
+            .unwrap();
+        let expected_df = DataFrame::new(vec![
+            Series::new("A", &["x", "y"]),
+            Series::new("count", &[2u32, 1]),
+            Series::new("B_sum", &[4, 2]),
+        ])
+        .unwrap();
+        assert_eq!(grouped, expected_df);
+
+        let total = aggregate(df.lazy(), &[], &["max(B)".to_string()])
+            .unwrap()
+            .collect()
+            .unwrap();
+        assert_eq!(total.shape(), (1, 1));
+    }
 }
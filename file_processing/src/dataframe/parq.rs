@@ -1,30 +1,66 @@
-use std::path::PathBuf;
+use std::{
+    path::{Path, PathBuf},
+    time::Instant,
+};
 
 use super::{
     expressions::get_index_expr_if_needed,
-    file::{HandleOutput, ScanFile},
-    operations::filter_columns,
+    file::{write_output, HandleOutput, OutputFormat, ScanFile},
+    operations::{aggregate, filter_columns, parse_sort_column, sort_columns, Aggregation, Filter},
     processor::Runnable,
+    stats::{expand_glob, peak_memory, row_groups, RunStats},
 };
 use anyhow::{anyhow, Context, Ok, Result};
 use cloud::AmazonS3ConfigKey as Key;
 use credentials::get_credentials;
+use log::{debug, info, log_enabled, warn, Level};
 use polars::{
     frame::DataFrame,
-    io::{cloud, csv::CsvWriter, SerWriter},
+    io::cloud,
     lazy::{
-        dsl::col,
+        dsl::{col, concat},
         frame::{LazyFrame, ScanArgsParquet},
     },
+    prelude::UnionArgs,
 };
 
 pub struct ParqProcessor<'a> {
     pub index_name: Option<String>,
     pub index_value: Option<String>,
     pub cols: Option<Vec<String>>,
-    pub file_name: PathBuf,
+    pub file_names: Vec<PathBuf>,
     pub profile: Option<&'a str>,
+    pub sort: Option<Vec<String>>,
+    /// Filters like `amount > 100`, every one of them has to match.
+    pub filters: Vec<String>,
+    /// Columns the aggregations are grouped by.
+    pub group_by: Vec<String>,
+    /// Aggregations like `sum(amount)`, they replace the column selection.
+    pub aggregations: Vec<String>,
+    pub limit: Option<u32>,
     output_file: Option<String>,
+    output_format: OutputFormat,
+    print_results: bool,
+}
+
+fn expand_file_name(file_name: PathBuf) -> PathBuf {
+    if file_name.starts_with("~") {
+        let expanded_path =
+            shellexpand::tilde(&file_name.to_string_lossy().into_owned()).to_string();
+        PathBuf::from(expanded_path)
+    } else {
+        file_name
+    }
+}
+
+fn scan_file(file_name: &Path, args: ScanArgsParquet) -> Result<LazyFrame> {
+    debug!("Scanning {}", file_name.display());
+    if file_name.starts_with("s3://") {
+        LazyFrame::scan_parquet(file_name, args)
+            .with_context(|| "File does not exist. Might need to pass --profile option".to_string())
+    } else {
+        LazyFrame::scan_parquet(file_name, args).with_context(|| "File does not exist".to_string())
+    }
 }
 
 impl<'a> ParqProcessor<'a> {
@@ -32,58 +68,128 @@ impl<'a> ParqProcessor<'a> {
         index_name: Option<String>,
         index_value: Option<String>,
         cols: Option<Vec<String>>,
-        file_name: PathBuf,
+        file_names: Vec<PathBuf>,
         profile: Option<&'a str>,
         output_file: Option<String>,
     ) -> Self {
-        let file_name = if file_name.starts_with("~") {
-            let expanded_path =
-                shellexpand::tilde(&file_name.to_string_lossy().into_owned()).to_string();
-            PathBuf::from(expanded_path)
-        } else {
-            file_name
-        };
         Self {
             index_name,
             index_value,
             cols,
-            file_name,
+            file_names: file_names.into_iter().map(expand_file_name).collect(),
             profile,
+            sort: None,
+            filters: Vec::new(),
+            group_by: Vec::new(),
+            aggregations: Vec::new(),
+            limit: None,
             output_file,
+            output_format: OutputFormat::default(),
+            print_results: true,
         }
     }
+
+    /// Sorts the results by these columns, given as `name` or `name:desc`.
+    pub fn with_sort(mut self, sort: Option<Vec<String>>) -> Self {
+        self.sort = sort;
+        self
+    }
+
+    /// Keeps the rows matching every filter, given as `column op value`.
+    pub fn with_filters(mut self, filters: Vec<String>) -> Self {
+        self.filters = filters;
+        self
+    }
+
+    /// Aggregates the filtered rows, grouped by `group_by` when it is not empty.
+    /// The results are sorted and limited once aggregated.
+    pub fn with_aggregations(mut self, group_by: Vec<String>, aggregations: Vec<String>) -> Self {
+        self.group_by = group_by;
+        self.aggregations = aggregations;
+        self
+    }
+
+    /// Keeps only the first rows, after filtering and sorting.
+    pub fn with_limit(mut self, limit: Option<u32>) -> Self {
+        self.limit = limit;
+        self
+    }
+
+    /// Whether results, or the output file they are written to, are printed. True by default.
+    pub fn with_print_results(mut self, print_results: bool) -> Self {
+        self.print_results = print_results;
+        self
+    }
+
+    pub fn with_output_format(mut self, output_format: OutputFormat) -> Self {
+        self.output_format = output_format;
+        self
+    }
+
+    fn scan_args(&self) -> Result<ScanArgsParquet> {
+        if !self
+            .file_names
+            .iter()
+            .any(|file_name| file_name.starts_with("s3://"))
+        {
+            return Ok(Default::default());
+        }
+        debug!(
+            "Reading from s3 with profile {}",
+            self.profile.unwrap_or("default")
+        );
+        let credentials = get_credentials("aws", self.profile, None)?;
+        let cloud_options = cloud::CloudOptions::default().with_aws([
+            (Key::AccessKeyId, credentials.access_key_id.as_str()),
+            (Key::SecretAccessKey, credentials.secret_access_key.expose()),
+            (Key::Region, credentials.region.as_str()),
+            (Key::Token, credentials.session_token.expose()),
+        ]);
+        Ok(ScanArgsParquet {
+            cloud_options: Some(cloud_options),
+            ..Default::default()
+        })
+    }
 }
 
 impl ScanFile for ParqProcessor<'_> {
+    /// Scans a single file as is, several files are scanned as one union
+    /// skipping the ones that cannot be scanned, like missing files or globs matching nothing.
     fn scan(&self) -> Result<LazyFrame> {
-        if self.file_name.starts_with("s3://") {
-            let credentials = get_credentials("aws", self.profile, None)?;
-            let cloud_options = cloud::CloudOptions::default().with_aws([
-                (Key::AccessKeyId, &credentials.access_key_id),
-                (Key::SecretAccessKey, &credentials.secret_access_key),
-                (Key::Region, &credentials.region),
-                (Key::Token, &credentials.session_token),
-            ]);
-            let args = ScanArgsParquet {
-                cloud_options: Some(cloud_options),
-                ..Default::default()
-            };
-            LazyFrame::scan_parquet(&self.file_name, args).with_context(|| {
-                "File does not exist. Might need to pass --profile option".to_string()
-            })
-        } else {
-            LazyFrame::scan_parquet(&self.file_name, Default::default())
-                .with_context(|| "File does not exist".to_string())
+        self.scan_with(self.scan_args()?)
+    }
+}
+
+impl ParqProcessor<'_> {
+    fn scan_with(&self, args: ScanArgsParquet) -> Result<LazyFrame> {
+        match self.file_names.as_slice() {
+            [] => Err(anyhow!("No file to scan")),
+            [file_name] => scan_file(file_name, args),
+            file_names => {
+                let mut frames = Vec::new();
+                for file_name in file_names {
+                    match scan_file(file_name, args.clone()) {
+                        Result::Ok(frame) => frames.push(frame),
+                        Err(e) => warn!("Skipping {}: {}", file_name.display(), e),
+                    }
+                }
+                if frames.is_empty() {
+                    return Err(anyhow!("None of the {} files exist", file_names.len()));
+                }
+                concat(frames, UnionArgs::default())
+                    .with_context(|| "Failed to combine the scanned files")
+            }
         }
     }
 }
 
-impl Runnable for ParqProcessor<'_> {
-    fn run(&self) -> Result<DataFrame> {
+impl ParqProcessor<'_> {
+    /// The search filter, filters, aggregations, sort, limit and column selection over the scan,
+    /// not run yet.
+    fn lazy_frame(&self, lf1: LazyFrame) -> Result<LazyFrame> {
         let index_name = &self.index_name;
         let index_value = &self.index_value;
 
-        let lf1 = self.scan()?;
         let exprs = self
             .cols
             .as_ref()
@@ -91,30 +197,146 @@ impl Runnable for ParqProcessor<'_> {
         let lf1 = match (index_name, index_value) {
             (Some(idx_name), Some(idx_value)) => {
                 let index_expr = get_index_expr_if_needed(idx_name, idx_value)?;
-                let lf1 = filter_columns(lf1.filter(index_expr), &exprs);
-                Ok(lf1)
+                Ok(lf1.filter(index_expr))
             }
             (None, Some(_)) | (Some(_), None) => Err(anyhow!(
                 "Search failed. Either index-name or index-value is missing"
             )),
-            _ => Ok(filter_columns(lf1, &exprs)),
+            _ => Ok(lf1),
+        }?;
+        let lf1 = self.filters.iter().try_fold(lf1, |lf1, filter| {
+            Ok(lf1.filter(Filter::parse(filter)?.expr()))
+        })?;
+        let aggregated = !self.aggregations.is_empty();
+        let lf1 = if aggregated {
+            aggregate(lf1, &self.group_by, &self.aggregations)?
+        } else {
+            lf1
+        };
+        let lf1 = sort_columns(lf1, self.sort.as_deref().unwrap_or_default())?;
+        let lf1 = match self.limit {
+            Some(limit) => lf1.limit(limit),
+            None => lf1,
         };
+        if aggregated {
+            return Ok(lf1);
+        }
+        Ok(filter_columns(lf1, &exprs))
+    }
 
-        self.handle(lf1?.collect()?)
+    /// The columns read out of the files, None when all of them are.
+    fn columns_read(&self) -> Result<Option<Vec<String>>> {
+        let mut columns = if self.aggregations.is_empty() {
+            let Some(columns) = self.cols.clone() else {
+                return Ok(None);
+            };
+            columns
+        } else {
+            let mut columns = self.group_by.clone();
+            for aggregation in &self.aggregations {
+                columns.extend(Aggregation::parse(aggregation)?.column);
+            }
+            columns
+        };
+        let filters = self
+            .filters
+            .iter()
+            .map(|filter| Filter::parse(filter))
+            .collect::<Result<Vec<_>>>()?;
+        let mut needed: Vec<&str> = self.index_name.as_deref().into_iter().collect();
+        needed.extend(filters.iter().map(|filter| filter.column.as_str()));
+        if self.aggregations.is_empty() {
+            for sort_column in self.sort.iter().flatten() {
+                needed.push(parse_sort_column(sort_column)?.0);
+            }
+        }
+        for column in needed {
+            if !columns.iter().any(|name| name == column) {
+                columns.push(column.to_string());
+            }
+        }
+        Ok(Some(columns))
+    }
+
+    /// Adds up the row groups of the files from their metadata. Files that cannot be
+    /// read are left out, the scan warns about them.
+    fn add_files(
+        &self,
+        stats: &mut RunStats,
+        cloud_options: Option<&cloud::CloudOptions>,
+    ) -> Result<()> {
+        let columns = self.columns_read()?;
+        let index = self.index_name.as_deref().zip(self.index_value.as_deref());
+        for pattern in &self.file_names {
+            for file_name in expand_glob(pattern, cloud_options)? {
+                match row_groups(&file_name, cloud_options, columns.as_deref(), index) {
+                    Result::Ok(row_groups) => {
+                        stats.add_file(file_name.starts_with("s3://"), &row_groups)
+                    }
+                    Err(e) => debug!("Leaving {} out of the stats: {:#}", file_name.display(), e),
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Runnable for ParqProcessor<'_> {
+    fn run(&self) -> Result<DataFrame> {
+        let lf1 = self.lazy_frame(self.scan()?)?;
+        if log_enabled!(Level::Debug) {
+            debug!("Query plan:\n{}", lf1.describe_optimized_plan()?);
+        }
+        let start = Instant::now();
+        let df = lf1.collect()?;
+        info!("Collected {} rows in {:.2?}", df.height(), start.elapsed());
+        self.handle(df)
+    }
+
+    fn explain(&self) -> Result<String> {
+        Ok(self.lazy_frame(self.scan()?)?.describe_optimized_plan()?)
+    }
+
+    fn run_with_stats(&self) -> Result<(DataFrame, RunStats)> {
+        let mut stats = RunStats::default();
+        let start = Instant::now();
+        let args = self.scan_args()?;
+        self.add_files(&mut stats, args.cloud_options.as_ref())?;
+        let lf1 = self.lazy_frame(self.scan_with(args)?)?;
+        let collect_start = Instant::now();
+        let (df, profile) = lf1.profile()?;
+        stats.add_profile(&profile, collect_start.elapsed())?;
+        let write_start = Instant::now();
+        let df = self.handle(df)?;
+        stats.write = write_start.elapsed();
+        stats.wall = start.elapsed();
+        stats.rows_matched = df.height();
+        stats.peak_memory = peak_memory();
+        stats.output_size = match &self.output_file {
+            Some(output_file) => std::fs::metadata(output_file)
+                .with_context(|| format!("Failed to read the size of {}", output_file))?
+                .len(),
+            None => df.estimated_size() as u64,
+        };
+        Ok((df, stats))
     }
 }
 
 impl HandleOutput for ParqProcessor<'_> {
     fn handle(&self, mut df: DataFrame) -> Result<DataFrame> {
         if let Some(output_file_path) = self.output_file.to_owned() {
-            let file = std::fs::File::create(&output_file_path)
-                .with_context(|| anyhow!("Failed to create file"))?;
-            let mut writer = CsvWriter::new(file);
-            writer
-                .finish(&mut df)
-                .with_context(|| anyhow!("Failed to write csv output file"))?;
-            println!("Results are available in {}", &output_file_path);
-        } else {
+            let start = Instant::now();
+            write_output(&mut df, &output_file_path, self.output_format)?;
+            info!(
+                "Wrote {} rows to {} in {:.2?}",
+                df.height(),
+                output_file_path,
+                start.elapsed()
+            );
+            if self.print_results {
+                println!("Results are available in {}", &output_file_path);
+            }
+        } else if self.print_results {
             println!("{}", df);
         }
         Ok(df)
@@ -122,6 +344,11 @@ impl HandleOutput for ParqProcessor<'_> {
 }
 
 #[cfg(test)]
+#[allow(
+    clippy::needless_return,
+    clippy::single_range_in_vec_init,
+    clippy::manual_unwrap_or_default
+)]
 mod tests {
     use polars::frame::DataFrame;
     use polars::io::parquet::ParquetWriter;
@@ -132,7 +359,7 @@ mod tests {
     use rand::distributions::Alphanumeric;
     use rand::{thread_rng, Rng};
 
-    use crate::generated_test_files_path;
+    use crate::{dataframe::plan::scan_pushdowns, generated_test_files_path};
 
     use super::*;
 
@@ -183,13 +410,34 @@ mod tests {
         return Ok(random_file_path);
     }
 
+    /// Writes a `value` column and `num_cols` random ones of `rows` rows each.
+    fn write_rows_test_file(test_name: &str, rows: u32, num_cols: u32) -> Result<String> {
+        let file_path = generated_test_files_path!(format!("test_{}.parq", test_name));
+        let test_file = std::fs::File::create(&file_path)
+            .with_context(|| format!("failed to create {} test file", file_path))?;
+        let mut columns = vec!["value".to_string()];
+        columns.extend((0..num_cols).map(|index| format!("column_{}", index)));
+        let series = columns
+            .iter()
+            .map(|name| {
+                let values = (0..rows).map(|_| thread_rng().gen()).collect::<Vec<u32>>();
+                Series::new(name, values)
+            })
+            .collect::<Vec<_>>();
+        let mut df = DataFrame::new(series)?;
+        ParquetWriter::new(test_file)
+            .finish(&mut df)
+            .with_context(|| format!("failed to save test df {}", &file_path))?;
+        Ok(file_path)
+    }
+
     #[test]
     fn test_scan() {
         let test_file_path = write_test_file("parq_processor_scan".to_string(), 10, None);
         assert!(test_file_path.is_ok());
         let test_file_path = test_file_path.unwrap();
         let test_file_path = PathBuf::from(test_file_path);
-        let processor = ParqProcessor::new(None, None, None, test_file_path, None, None);
+        let processor = ParqProcessor::new(None, None, None, vec![test_file_path], None, None);
         let result = processor.scan();
         assert!(result.is_ok());
         let lazy_frame = result.unwrap();
@@ -202,10 +450,156 @@ mod tests {
         assert!(test_file_path.is_ok());
         let test_file_path = test_file_path.unwrap();
         let test_file_path = PathBuf::from(test_file_path);
-        let processor = ParqProcessor::new(None, None, None, test_file_path, None, None);
+        let processor = ParqProcessor::new(None, None, None, vec![test_file_path], None, None);
         let result = processor.run();
         assert!(result.is_ok());
         let lazy_frame = result.unwrap();
         assert_eq!(lazy_frame.schema().iter_fields().len(), 10); // Replace 0 with the expected number of fields
     }
+
+    #[test]
+    fn test_scan_union_skips_missing_files() {
+        let first = write_rows_test_file("parq_processor_union_first", 10, 0).unwrap();
+        let second = write_rows_test_file("parq_processor_union_second", 10, 0).unwrap();
+        let missing = generated_test_files_path!("test_parq_processor_union_missing.parq");
+        let processor = ParqProcessor::new(
+            None,
+            None,
+            None,
+            vec![first.into(), missing.clone().into(), second.into()],
+            None,
+            None,
+        );
+        let df = processor.scan().unwrap().collect().unwrap();
+        assert_eq!(df.height(), 20);
+
+        let glob = generated_test_files_path!("test_parq_processor_union_*.parq");
+        let processor = ParqProcessor::new(
+            None,
+            None,
+            None,
+            vec![glob.into(), missing.clone().into()],
+            None,
+            None,
+        );
+        let df = processor.scan().unwrap().collect().unwrap();
+        assert_eq!(df.height(), 20);
+
+        let processor = ParqProcessor::new(None, None, None, vec![missing.into(); 2], None, None);
+        assert!(processor.scan().is_err());
+    }
+
+    #[test]
+    fn test_explain_pushes_columns_into_scan() {
+        let test_file_path = write_test_file(
+            "parq_processor_explain".to_string(),
+            2,
+            Some(vec!["value".to_string()]),
+        )
+        .unwrap();
+        let processor = ParqProcessor::new(
+            None,
+            None,
+            Some(vec!["value".to_string()]),
+            vec![test_file_path.clone().into()],
+            None,
+            None,
+        );
+        let scans = scan_pushdowns(&processor.explain().unwrap());
+        assert_eq!(scans.len(), 1);
+        assert!(scans[0].source.ends_with(&test_file_path));
+        assert_eq!(scans[0].projection.as_deref(), Some("1/3"));
+    }
+
+    #[test]
+    fn test_run_with_stats() {
+        let test_file_path = write_rows_test_file("parq_processor_stats", 10, 3).unwrap();
+        let processor = ParqProcessor::new(
+            None,
+            None,
+            Some(vec!["value".to_string()]),
+            vec![test_file_path.into()],
+            None,
+            None,
+        )
+        .with_limit(Some(4))
+        .with_print_results(false);
+        let (df, stats) = processor.run_with_stats().unwrap();
+        assert_eq!(df.height(), 4);
+        assert_eq!(stats.files, 1);
+        assert_eq!(
+            (
+                stats.rows_total,
+                stats.rows_scanned_estimate,
+                stats.rows_matched
+            ),
+            (10, 10, 4)
+        );
+        assert_eq!(
+            (
+                stats.row_groups_read_estimate,
+                stats.row_groups_skipped_estimate
+            ),
+            (1, 0)
+        );
+        assert!(stats.bytes_read_local_estimate > 0 && stats.bytes_read_s3_estimate == 0);
+        assert!(stats.wall >= stats.scan + stats.write);
+        assert!(stats.output_size > 0);
+    }
+
+    #[test]
+    fn test_run_sorted_parquet_output() {
+        let test_file_path = write_rows_test_file("parq_processor_sorted", 10, 2).unwrap();
+        let output_file = generated_test_files_path!("test_parq_processor_sorted_output.parq");
+        let processor = ParqProcessor::new(
+            None,
+            None,
+            Some(vec!["value".to_string()]),
+            vec![test_file_path.into()],
+            None,
+            Some(output_file.clone()),
+        )
+        .with_sort(Some(vec!["value:desc".to_string()]))
+        .with_limit(Some(4))
+        .with_output_format(OutputFormat::Parquet);
+        let df = processor.run().unwrap();
+        assert_eq!(df.shape(), (4, 1));
+
+        let written = LazyFrame::scan_parquet(&output_file, Default::default())
+            .unwrap()
+            .collect()
+            .unwrap();
+        assert_eq!(written, df);
+        let values = written.column("value").unwrap().u32().unwrap();
+        let values = values.into_no_null_iter().collect::<Vec<_>>();
+        assert!(values.windows(2).all(|pair| pair[0] >= pair[1]));
+    }
+
+    #[test]
+    fn test_run_filtered_aggregation() {
+        let test_file_path = write_rows_test_file("parq_processor_aggregation", 10, 1).unwrap();
+        let processor =
+            ParqProcessor::new(None, None, None, vec![test_file_path.into()], None, None)
+                .with_filters(vec!["value >= 0".to_string()])
+                .with_aggregations(
+                    vec![],
+                    vec!["count()".to_string(), "max(value)".to_string()],
+                )
+                .with_print_results(false);
+        let df = processor.run().unwrap();
+        assert_eq!(df.shape(), (1, 2));
+        assert_eq!(df.column("count").unwrap().u32().unwrap().get(0), Some(10));
+
+        let processor = ParqProcessor::new(
+            None,
+            None,
+            None,
+            vec![generated_test_files_path!("test_parq_processor_aggregation.parq").into()],
+            None,
+            None,
+        )
+        .with_filters(vec!["value < 0".to_string()])
+        .with_print_results(false);
+        assert_eq!(processor.run().unwrap().height(), 0);
+    }
 }
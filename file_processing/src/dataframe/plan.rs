@@ -0,0 +1,68 @@
+/// What polars pushed down into a file scan of an optimised plan.
+#[derive(Debug, Default, PartialEq)]
+pub struct ScanPushdown {
+    pub source: String,
+    /// Columns read out of the ones in the file, as `read/total`. None when all are read.
+    pub projection: Option<String>,
+    /// Predicate applied while reading, None when every row is read.
+    pub predicate: Option<String>,
+}
+
+/// Reads the scans out of the text of `describe_optimized_plan`.
+///
+/// Polars has no api exposing the pushdowns, so this relies on the plan format of
+/// polars 0.38 (`Parquet SCAN`, `PROJECT x/y COLUMNS`, `SELECTION:` lines) and has to be
+/// checked, along with its tests, whenever polars is upgraded.
+pub fn scan_pushdowns(plan: &str) -> Vec<ScanPushdown> {
+    let mut scans: Vec<ScanPushdown> = Vec::new();
+    for line in plan.lines().map(str::trim) {
+        if let Some((_, source)) = line.split_once("SCAN ") {
+            scans.push(ScanPushdown {
+                source: source.trim().to_string(),
+                ..Default::default()
+            });
+            continue;
+        }
+        let Some(scan) = scans.last_mut() else {
+            continue;
+        };
+        if let Some(projection) = line
+            .strip_prefix("PROJECT ")
+            .and_then(|projection| projection.strip_suffix(" COLUMNS"))
+        {
+            scan.projection = Some(projection.to_string());
+        } else if let Some(predicate) = line.strip_prefix("SELECTION:") {
+            let predicate = predicate.trim();
+            if !predicate.is_empty() && predicate != "None" {
+                scan.predicate = Some(predicate.to_string());
+            }
+        }
+    }
+    scans
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_scan_pushdowns() {
+        let plan = "simple π 1/2 [\"open\", ... 1 other columns]\n\n    Parquet SCAN s3://bucket/file.parq\n    PROJECT 2/14 COLUMNS\n    SELECTION: [(col(\"transaction_time\")) == (2024)]\nUNION\n  Parquet SCAN /data/other.parq\n  PROJECT */14 COLUMNS\n  SELECTION: None";
+        assert_eq!(
+            scan_pushdowns(plan),
+            vec![
+                ScanPushdown {
+                    source: "s3://bucket/file.parq".to_string(),
+                    projection: Some("2/14".to_string()),
+                    predicate: Some("[(col(\"transaction_time\")) == (2024)]".to_string()),
+                },
+                ScanPushdown {
+                    source: "/data/other.parq".to_string(),
+                    projection: Some("*/14".to_string()),
+                    predicate: None,
+                },
+            ]
+        );
+        assert!(scan_pushdowns("DF [\"a\"]; PROJECT */1 COLUMNS").is_empty());
+    }
+}
@@ -3,6 +3,8 @@ use std::path::PathBuf;
 use anyhow::Result;
 use polars::frame::DataFrame;
 
+use super::stats::RunStats;
+
 #[derive(Debug)]
 pub struct Processor {
     pub index_name: Option<String>,
@@ -15,4 +17,8 @@ pub struct Processor {
 pub trait Runnable {
     // returns the file name
     fn run(&self) -> Result<DataFrame>;
+    /// The optimised plan `run` would execute, without reading any rows.
+    fn explain(&self) -> Result<String>;
+    /// Like `run`, also reporting what was read and how long each step took.
+    fn run_with_stats(&self) -> Result<(DataFrame, RunStats)>;
 }
@@ -0,0 +1,376 @@
+use std::{
+    fs::{self, File},
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use anyhow::{anyhow, Context, Result};
+use polars::{
+    frame::DataFrame,
+    io::{
+        async_glob,
+        cloud::CloudOptions,
+        parquet::{FileMetaDataRef, ParquetAsyncReader, ParquetReader},
+        pl_async::get_runtime,
+    },
+    lazy::{
+        dsl::{col, lit},
+        frame::IntoLazy,
+    },
+    prelude::{ArrowSchema, DataType, NamedFrom, SerReader, Series},
+};
+
+use super::datetime::to_datetime_expression;
+
+/// What a run read and wrote, and how long each step took.
+///
+/// Polars does not report the row groups it reads, the `*_estimate` fields are worked out
+/// from the parquet metadata instead.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct RunStats {
+    pub files: usize,
+    /// Rows in the files.
+    pub rows_total: usize,
+    /// Rows in the row groups estimated to be read.
+    pub rows_scanned_estimate: usize,
+    /// Rows in the results.
+    pub rows_matched: usize,
+    /// Compressed size of the column chunks estimated to be read out of local files.
+    pub bytes_read_local_estimate: u64,
+    /// Compressed size of the column chunks estimated to be read out of s3.
+    pub bytes_read_s3_estimate: u64,
+    pub row_groups_read_estimate: usize,
+    /// Row groups whose min and max of the index column rule out the searched value.
+    pub row_groups_skipped_estimate: usize,
+    /// Reading the files in the parquet scans, filters pushed into the scans included.
+    pub scan: Duration,
+    /// Filters run after the scans.
+    pub filter: Duration,
+    /// The rest of collecting the results, like sorting and combining files.
+    pub collect: Duration,
+    pub write: Duration,
+    /// Everything from resolving credentials to writing the results.
+    pub wall: Duration,
+    /// Peak resident memory of the process, where the platform reports it.
+    pub peak_memory: Option<u64>,
+    /// Size of the output file, or of the results in memory when printed.
+    pub output_size: u64,
+}
+
+/// A row group of a parquet file as described by its metadata.
+#[derive(Debug, PartialEq)]
+pub(crate) struct RowGroup {
+    pub rows: usize,
+    /// Compressed size of the column chunks a run needs.
+    pub bytes: u64,
+    pub read: bool,
+}
+
+impl RunStats {
+    /// Adds up the row groups of a file.
+    pub(crate) fn add_file(&mut self, s3: bool, row_groups: &[RowGroup]) {
+        self.files += 1;
+        for row_group in row_groups {
+            self.rows_total += row_group.rows;
+            if !row_group.read {
+                self.row_groups_skipped_estimate += 1;
+                continue;
+            }
+            self.row_groups_read_estimate += 1;
+            self.rows_scanned_estimate += row_group.rows;
+            if s3 {
+                self.bytes_read_s3_estimate += row_group.bytes;
+            } else {
+                self.bytes_read_local_estimate += row_group.bytes;
+            }
+        }
+    }
+
+    /// Splits the time of a profiled collect into scan, filter and the rest.
+    pub(crate) fn add_profile(&mut self, profile: &DataFrame, collect: Duration) -> Result<()> {
+        let nodes = profile.column("node")?.str()?;
+        let starts = profile.column("start")?.u64()?;
+        let ends = profile.column("end")?.u64()?;
+        let mut scans = Vec::new();
+        let mut filters = Vec::new();
+        for ((node, start), end) in nodes.into_iter().zip(starts).zip(ends) {
+            let (Some(node), Some(start), Some(end)) = (node, start, end) else {
+                continue;
+            };
+            if node.starts_with("parquet") {
+                scans.push((start, end));
+            } else if node.contains("filter") {
+                filters.push((start, end));
+            }
+        }
+        self.scan = busy_time(scans);
+        self.filter = busy_time(filters);
+        self.collect = collect.saturating_sub(self.scan + self.filter);
+        Ok(())
+    }
+}
+
+/// Time covered by the `(start, end)` intervals in microseconds, overlaps counted once
+/// as the files of a union are read side by side.
+fn busy_time(mut intervals: Vec<(u64, u64)>) -> Duration {
+    intervals.sort_unstable();
+    let mut busy = 0;
+    let mut covered_until = 0;
+    for (start, end) in intervals {
+        let start = start.max(covered_until);
+        if end > start {
+            busy += end - start;
+            covered_until = end;
+        }
+    }
+    Duration::from_micros(busy)
+}
+
+/// The files a path stands for, globs expanded like the parquet scan does.
+pub(crate) fn expand_glob(
+    file_name: &Path,
+    cloud_options: Option<&CloudOptions>,
+) -> Result<Vec<PathBuf>> {
+    let path = file_name.to_string_lossy();
+    if !path.contains(['*', '?', '[']) {
+        return Ok(vec![file_name.to_path_buf()]);
+    }
+    if file_name.starts_with("s3://") {
+        return Ok(async_glob(&path, cloud_options)?
+            .into_iter()
+            .map(PathBuf::from)
+            .collect());
+    }
+    glob::glob(&path)
+        .map_err(|e| anyhow!("Invalid glob {}: {}", path, e))?
+        .map(|entry| entry.with_context(|| format!("Failed to expand {}", path)))
+        .collect()
+}
+
+fn read_metadata(
+    file_name: &Path,
+    cloud_options: Option<&CloudOptions>,
+) -> Result<(FileMetaDataRef, ArrowSchema)> {
+    if file_name.starts_with("s3://") {
+        let uri = file_name.to_string_lossy();
+        get_runtime().block_on(async {
+            let mut reader = ParquetAsyncReader::from_uri(&uri, cloud_options, None, None).await?;
+            let metadata = reader.get_metadata().await?.clone();
+            let schema = reader.schema().await?;
+            Ok((metadata, schema.as_ref().clone()))
+        })
+    } else {
+        let file = File::open(file_name)
+            .with_context(|| format!("Failed to open {}", file_name.display()))?;
+        let mut reader = ParquetReader::new(file);
+        let metadata = reader.get_metadata()?.clone();
+        let schema = reader.schema()?;
+        Ok((metadata, schema.as_ref().clone()))
+    }
+}
+
+/// A plain encoded INT64 statistic, how parquet stores timestamps.
+fn plain_i64(value: &[u8]) -> Option<i64> {
+    Some(i64::from_le_bytes(value.try_into().ok()?))
+}
+
+/// Whether each row group may hold rows where the datetime column `index_name` is
+/// `index_value`, going by its min and max. Row groups without statistics are read.
+fn may_match(
+    metadata: &FileMetaDataRef,
+    schema: &ArrowSchema,
+    index_name: &str,
+    index_value: &str,
+) -> Result<Vec<bool>> {
+    let everything = vec![true; metadata.row_groups.len()];
+    let Some(field) = schema.fields.iter().find(|field| field.name == index_name) else {
+        return Ok(everything);
+    };
+    let dtype = DataType::from(&field.data_type);
+    if !matches!(dtype, DataType::Datetime(..)) {
+        return Ok(everything);
+    }
+    let (mins, maxs): (Vec<Option<i64>>, Vec<Option<i64>>) = metadata
+        .row_groups
+        .iter()
+        .map(|row_group| {
+            let statistics = row_group
+                .columns()
+                .iter()
+                .find(|column| column.descriptor().path_in_schema == [index_name])
+                .and_then(|column| column.metadata().statistics.as_ref());
+            let bound = |value: Option<&Vec<u8>>| value.and_then(|value| plain_i64(value));
+            (
+                statistics.and_then(|statistics| bound(statistics.min_value.as_ref())),
+                statistics.and_then(|statistics| bound(statistics.max_value.as_ref())),
+            )
+        })
+        .unzip();
+    let mins = Series::new("min", mins).cast(&dtype)?;
+    let maxs = Series::new("max", maxs).cast(&dtype)?;
+    let value = to_datetime_expression(index_value)?;
+    let matches = DataFrame::new(vec![mins, maxs])?
+        .lazy()
+        .select([col("min")
+            .lt_eq(value.clone())
+            .and(col("max").gt_eq(value))
+            .fill_null(lit(true))
+            .alias("read")])
+        .collect()
+        .with_context(|| format!("Failed to compare the statistics of {}", index_name))?;
+    Ok(matches
+        .column("read")?
+        .bool()?
+        .into_iter()
+        .map(|read| read.unwrap_or(true))
+        .collect())
+}
+
+/// The row groups of a parquet file, `columns` are the ones a run needs, None for all of them.
+pub(crate) fn row_groups(
+    file_name: &Path,
+    cloud_options: Option<&CloudOptions>,
+    columns: Option<&[String]>,
+    index: Option<(&str, &str)>,
+) -> Result<Vec<RowGroup>> {
+    let (metadata, schema) = read_metadata(file_name, cloud_options)?;
+    let read = match index {
+        Some((index_name, index_value)) => may_match(&metadata, &schema, index_name, index_value)?,
+        None => vec![true; metadata.row_groups.len()],
+    };
+    Ok(metadata
+        .row_groups
+        .iter()
+        .zip(read)
+        .map(|(row_group, read)| RowGroup {
+            rows: row_group.num_rows(),
+            bytes: row_group
+                .columns()
+                .iter()
+                .filter(|column| {
+                    columns.is_none_or(|columns| {
+                        column
+                            .descriptor()
+                            .path_in_schema
+                            .first()
+                            .is_some_and(|name| columns.contains(name))
+                    })
+                })
+                .map(|column| column.compressed_size() as u64)
+                .sum(),
+            read,
+        })
+        .collect())
+}
+
+/// Peak resident memory of the process in bytes, read from /proc on linux.
+pub fn peak_memory() -> Option<u64> {
+    fs::read_to_string("/proc/self/status")
+        .ok()
+        .and_then(|status| parse_peak_memory(&status))
+}
+
+fn parse_peak_memory(status: &str) -> Option<u64> {
+    status
+        .lines()
+        .find_map(|line| line.strip_prefix("VmHWM:"))
+        .and_then(|value| value.trim().strip_suffix("kB"))
+        .and_then(|kilobytes| kilobytes.trim().parse::<u64>().ok())
+        .map(|kilobytes| kilobytes * 1024)
+}
+
+#[cfg(test)]
+mod tests {
+    use polars::{io::parquet::ParquetWriter, prelude::TimeUnit};
+
+    use crate::generated_test_files_path;
+
+    use super::*;
+
+    #[test]
+    fn test_row_groups_skipped_on_statistics() {
+        let path = generated_test_files_path!("test_stats_row_groups.parq");
+        let seconds: [i64; 4] = [1706806800, 1706806860, 1706810400, 1706812200];
+        let transaction_time = Series::new(
+            "transaction_time",
+            seconds.map(|seconds| seconds * 1_000_000),
+        )
+        .cast(&DataType::Datetime(TimeUnit::Microseconds, None))
+        .unwrap();
+        let mut df =
+            DataFrame::new(vec![transaction_time, Series::new("open", [1, 2, 3, 4])]).unwrap();
+        ParquetWriter::new(File::create(&path).unwrap())
+            .with_statistics(true)
+            .with_row_group_size(Some(2))
+            .finish(&mut df)
+            .unwrap();
+
+        let index = Some(("transaction_time", "2024-02-01 17:01:00"));
+        let read: Vec<bool> = row_groups(Path::new(&path), None, None, index)
+            .unwrap()
+            .iter()
+            .map(|row_group| row_group.read)
+            .collect();
+        assert_eq!(read, vec![true, false]);
+
+        let all = row_groups(Path::new(&path), None, None, None).unwrap();
+        let open = row_groups(Path::new(&path), None, Some(&["open".to_string()]), None).unwrap();
+        assert_eq!(all.iter().map(|row_group| row_group.rows).sum::<usize>(), 4);
+        assert!(open[0].bytes > 0 && open[0].bytes < all[0].bytes);
+    }
+
+    #[test]
+    fn test_expand_glob() {
+        let path = generated_test_files_path!("test_stats_glob_1.parq");
+        File::create(&path).unwrap();
+        let pattern = generated_test_files_path!("test_stats_glob_*.parq");
+        assert_eq!(
+            expand_glob(Path::new(&pattern), None).unwrap(),
+            vec![PathBuf::from(&path)]
+        );
+        assert_eq!(
+            expand_glob(Path::new("s3://bucket/file.parq"), None).unwrap(),
+            vec![PathBuf::from("s3://bucket/file.parq")]
+        );
+    }
+
+    #[test]
+    fn test_busy_time() {
+        assert_eq!(busy_time(vec![]), Duration::ZERO);
+        assert_eq!(
+            busy_time(vec![(10, 30), (0, 15), (40, 50), (45, 48)]),
+            Duration::from_micros(40)
+        );
+    }
+
+    #[test]
+    fn test_add_file() {
+        let mut stats = RunStats::default();
+        let row_group = |rows, bytes, read| RowGroup { rows, bytes, read };
+        stats.add_file(false, &[row_group(10, 100, true), row_group(5, 50, false)]);
+        stats.add_file(true, &[row_group(20, 300, true)]);
+        assert_eq!(stats.files, 2);
+        assert_eq!((stats.rows_total, stats.rows_scanned_estimate), (35, 30));
+        assert_eq!(
+            (
+                stats.row_groups_read_estimate,
+                stats.row_groups_skipped_estimate
+            ),
+            (2, 1)
+        );
+        assert_eq!(
+            (
+                stats.bytes_read_local_estimate,
+                stats.bytes_read_s3_estimate
+            ),
+            (100, 300)
+        );
+    }
+
+    #[test]
+    fn test_parse_peak_memory() {
+        let status = "Name:\twdapty\nVmPeak:\t  20480 kB\nVmHWM:\t    1024 kB\n";
+        assert_eq!(parse_peak_memory(status), Some(1024 * 1024));
+        assert_eq!(parse_peak_memory("Name:\twdapty\n"), None);
+    }
+}
@@ -1,5 +1,5 @@
 use anyhow::Result;
-use dataframe::{parq::ParqProcessor, processor::Runnable};
+use dataframe::{parq::ParqProcessor, processor::Runnable, stats::RunStats};
 use polars::frame::DataFrame;
 
 pub mod dataframe;
@@ -14,4 +14,16 @@ impl Runnable for Processors<'_> {
             Processors::Parq(parq_processor) => parq_processor.run(),
         }
     }
+
+    fn explain(&self) -> Result<String> {
+        match self {
+            Processors::Parq(parq_processor) => parq_processor.explain(),
+        }
+    }
+
+    fn run_with_stats(&self) -> Result<(DataFrame, RunStats)> {
+        match self {
+            Processors::Parq(parq_processor) => parq_processor.run_with_stats(),
+        }
+    }
 }
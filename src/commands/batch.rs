@@ -0,0 +1,347 @@
+use std::{
+    collections::BTreeMap,
+    fs,
+    path::Path,
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        Mutex,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+use anyhow::{anyhow, Context, Result};
+use log::info;
+use serde::Deserialize;
+use serde_json::json;
+
+use super::{
+    config::{Config, PatternConfig, PatternDefaults, QueryConfig},
+    output::print_records,
+    query::{get_query, get_query_pattern, resolve_query, Query},
+    variables::PatternVariables,
+};
+
+pub const DEFAULT_PARALLELISM: usize = 4;
+
+/// Jobs run by `wdapty batch`, read from a toml file.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct Manifest {
+    /// How many jobs run at the same time, 4 by default.
+    pub parallelism: Option<usize>,
+    /// Variables shared by every job.
+    pub vars: BTreeMap<String, String>,
+    pub jobs: Vec<BatchJob>,
+}
+
+/// A saved query, or a search, download or aggregation over a pattern or a file.
+/// Values given on the job win over the ones of the saved query, its filters are added
+/// to the ones of the query. Results are written to the output file, jobs need one.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct BatchJob {
+    pub name: Option<String>,
+    pub query: Option<String>,
+    pub pattern: Option<String>,
+    /// Path of the file to read, it takes placeholders like a pattern.
+    pub file_name: Option<String>,
+    pub index_value: Option<String>,
+    /// Filters like `amount > 100`, every one of them has to match.
+    pub filters: Vec<String>,
+    /// Columns the aggregations are grouped by.
+    pub group_by: Vec<String>,
+    /// Aggregations like `sum(amount)` or `count()`, they replace the columns.
+    pub aggregations: Vec<String>,
+    pub limit: Option<u32>,
+    pub output_file: Option<String>,
+    #[serde(flatten)]
+    pub defaults: PatternDefaults,
+    /// Variables of this job, they win over the shared ones.
+    pub vars: BTreeMap<String, String>,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum JobStatus {
+    Succeeded {
+        rows: usize,
+    },
+    Failed(String),
+    /// Not started because an earlier job failed with `--fail-fast`.
+    Skipped,
+}
+
+#[derive(Debug)]
+pub struct JobReport {
+    pub name: String,
+    pub status: JobStatus,
+    pub duration: Duration,
+}
+
+impl Manifest {
+    pub fn load(path: &Path) -> Result<Self> {
+        let content = fs::read_to_string(path)
+            .with_context(|| format!("Failed to read manifest {}", path.display()))?;
+        let manifest: Manifest = toml::from_str(&content)
+            .with_context(|| format!("Failed to parse manifest {}", path.display()))?;
+        if manifest.jobs.is_empty() {
+            return Err(anyhow!("Manifest {} has no jobs", path.display()));
+        }
+        Ok(manifest)
+    }
+
+    /// Names of the patterns the jobs read, directly or through their saved query.
+    pub fn patterns(&self, config: &Config) -> Vec<String> {
+        self.jobs
+            .iter()
+            .filter_map(|job| {
+                job.pattern.clone().or_else(|| {
+                    job.query
+                        .as_ref()
+                        .and_then(|query| config.queries.get(query))
+                        .map(|query| query.pattern.clone())
+                })
+            })
+            .collect()
+    }
+}
+
+impl BatchJob {
+    pub fn label(&self, index: usize) -> String {
+        self.name
+            .clone()
+            .or_else(|| self.query.clone())
+            .unwrap_or_else(|| format!("job-{}", index + 1))
+    }
+
+    fn resolve(&self, name: &str, config: &Config, variables: &PatternVariables) -> Result<Query> {
+        let mut query = match (&self.query, &self.pattern, &self.file_name) {
+            (Some(query), None, None) => get_query(config, query)?.clone(),
+            (None, Some(pattern), None) => QueryConfig {
+                pattern: pattern.to_string(),
+                ..Default::default()
+            },
+            (None, None, Some(_)) => QueryConfig::default(),
+            _ => {
+                return Err(anyhow!(
+                    "Job {} needs exactly one of query, pattern or file_name",
+                    name
+                ))
+            }
+        };
+        query.index_value = self.index_value.clone().or(query.index_value);
+        query.filters.extend(self.filters.iter().cloned());
+        query.limit = self.limit.or(query.limit);
+        query.output_file = self.output_file.clone().or(query.output_file);
+        if query.output_file.is_none() {
+            return Err(anyhow!(
+                "Job {} needs an output_file, batch jobs do not print their results",
+                name
+            ));
+        }
+        query.defaults.update(self.defaults.clone());
+        let file_pattern;
+        let pattern = match &self.file_name {
+            Some(file_name) => {
+                file_pattern = PatternConfig {
+                    value: file_name.to_string(),
+                    ..Default::default()
+                };
+                &file_pattern
+            }
+            None => get_query_pattern(config, name, &query)?,
+        };
+        let mut resolved =
+            resolve_query(name, &query, pattern, variables, PatternDefaults::default())?;
+        resolved.group_by = self.group_by.clone();
+        resolved.aggregations = self.aggregations.clone();
+        Ok(resolved)
+    }
+
+    fn run(&self, name: &str, config: &Config, variables: &PatternVariables) -> Result<usize> {
+        let mut query = self.resolve(name, config, variables)?;
+        query.quiet = true;
+        let df = query.run(config)?;
+        Ok(df.height())
+    }
+}
+
+/// Runs the jobs of the manifest on up to `parallelism` threads. A failed job does not stop
+/// the others unless `fail_fast` is set, then the jobs not started yet are skipped.
+/// Variables given on the command line win over the ones of the manifest and nothing
+/// is prompted for.
+pub fn run_batch(
+    manifest: &Manifest,
+    config: &Config,
+    variables: &PatternVariables,
+    parallelism: usize,
+    fail_fast: bool,
+) -> Vec<JobReport> {
+    let next_job = AtomicUsize::new(0);
+    let failed = AtomicBool::new(false);
+    let reports: Vec<Mutex<Option<JobReport>>> =
+        manifest.jobs.iter().map(|_| Mutex::new(None)).collect();
+    thread::scope(|scope| {
+        for _ in 0..parallelism.clamp(1, manifest.jobs.len().max(1)) {
+            scope.spawn(|| loop {
+                let index = next_job.fetch_add(1, Ordering::SeqCst);
+                let Some(job) = manifest.jobs.get(index) else {
+                    break;
+                };
+                let name = job.label(index);
+                let start = Instant::now();
+                let status = if fail_fast && failed.load(Ordering::SeqCst) {
+                    JobStatus::Skipped
+                } else {
+                    let job_variables = variables
+                        .clone()
+                        .with_fallbacks(job.vars.clone())
+                        .with_fallbacks(manifest.vars.clone())
+                        .without_prompts();
+                    match job.run(&name, config, &job_variables) {
+                        Ok(rows) => JobStatus::Succeeded { rows },
+                        Err(e) => {
+                            failed.store(true, Ordering::SeqCst);
+                            JobStatus::Failed(format!("{:#}", e))
+                        }
+                    }
+                };
+                info!("Job {} {:?} after {:.2?}", name, status, start.elapsed());
+                *reports[index].lock().unwrap() = Some(JobReport {
+                    name,
+                    status,
+                    duration: start.elapsed(),
+                });
+            });
+        }
+    });
+    reports
+        .into_iter()
+        .filter_map(|report| report.into_inner().unwrap())
+        .collect()
+}
+
+/// Prints one line per job and the totals, failing when any job failed.
+pub fn print_batch_report(reports: &[JobReport]) -> Result<()> {
+    let records: Vec<_> = reports
+        .iter()
+        .map(|report| {
+            let (status, rows, error) = match &report.status {
+                JobStatus::Succeeded { rows } => ("succeeded", Some(*rows), None),
+                JobStatus::Failed(error) => ("failed", None, Some(error)),
+                JobStatus::Skipped => ("skipped", None, None),
+            };
+            json!({
+                "job": report.name,
+                "status": status,
+                "rows": rows,
+                "duration_secs": report.duration.as_secs_f64(),
+                "error": error,
+            })
+        })
+        .collect();
+    let count = |matches: fn(&JobStatus) -> bool| {
+        reports
+            .iter()
+            .filter(|report| matches(&report.status))
+            .count()
+    };
+    let failed = count(|status| matches!(status, JobStatus::Failed(_)));
+    print_records(&records, || {
+        let width = reports
+            .iter()
+            .map(|report| report.name.len())
+            .max()
+            .unwrap_or_default()
+            .max("Job".len());
+        println!(
+            "{:<width$}  {:<9}  {:>10}  {:>9}  Error",
+            "Job",
+            "Status",
+            "Rows",
+            "Duration",
+            width = width
+        );
+        for report in reports {
+            let (status, rows, error) = match &report.status {
+                JobStatus::Succeeded { rows } => ("succeeded", rows.to_string(), ""),
+                JobStatus::Failed(error) => ("failed", "-".to_string(), error.as_str()),
+                JobStatus::Skipped => ("skipped", "-".to_string(), ""),
+            };
+            println!(
+                "{:<width$}  {:<9}  {:>10}  {:>8.2}s  {}",
+                report.name,
+                status,
+                rows,
+                report.duration.as_secs_f64(),
+                error,
+                width = width
+            );
+        }
+        println!(
+            "{} jobs: {} succeeded, {} failed, {} skipped",
+            reports.len(),
+            count(|status| matches!(status, JobStatus::Succeeded { .. })),
+            failed,
+            count(|status| *status == JobStatus::Skipped)
+        );
+    })?;
+    if failed > 0 {
+        return Err(anyhow!("{} of {} jobs failed", failed, reports.len()));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use assert_fs::{prelude::*, TempDir};
+
+    use crate::commands::{config::Config, variables::PatternVariables};
+
+    use super::{run_batch, JobStatus, Manifest};
+
+    #[test]
+    fn test_load_manifest() {
+        let dir = TempDir::new().unwrap();
+        let manifest = dir.child("manifest.toml");
+        manifest
+            .write_str(
+                "parallelism = 2\n\n[vars]\ndate = \"2024-02-01\"\n\n[[jobs]]\nquery = \"latest\"\n\n[[jobs]]\nname = \"raw\"\nfile_name = \"/data/{date}.parq\"\ncols = [\"a\"]\noutput_file = \"raw.csv\"\n",
+            )
+            .unwrap();
+        let manifest = Manifest::load(manifest.path()).unwrap();
+        assert_eq!(manifest.parallelism, Some(2));
+        assert_eq!(manifest.jobs.len(), 2);
+        assert_eq!(manifest.jobs[0].label(0), "latest");
+        assert_eq!(manifest.jobs[1].label(1), "raw");
+        assert_eq!(manifest.jobs[1].defaults.cols, Some(vec!["a".to_string()]));
+
+        let empty = dir.child("empty.toml");
+        empty.write_str("parallelism = 2\n").unwrap();
+        assert!(Manifest::load(empty.path()).is_err());
+    }
+
+    #[test]
+    fn test_run_batch_fail_fast() {
+        let manifest: Manifest = toml::from_str(
+            "[[jobs]]\nname = \"missing\"\nfile_name = \"/does/not/exist/{date}.parq\"\noutput_file = \"out.csv\"\n\n[[jobs]]\nname = \"invalid\"\npattern = \"a\"\nfile_name = \"b\"\n\n[[jobs]]\nname = \"printed\"\nfile_name = \"/data.parq\"\n",
+        )
+        .unwrap();
+        let config = Config::default();
+        let variables = PatternVariables::default();
+
+        let reports = run_batch(&manifest, &config, &variables, 2, false);
+        assert_eq!(reports.len(), 3);
+        assert!(matches!(&reports[0].status, JobStatus::Failed(error) if error.contains("date")));
+        assert!(
+            matches!(&reports[1].status, JobStatus::Failed(error) if error.contains("exactly one"))
+        );
+        assert!(
+            matches!(&reports[2].status, JobStatus::Failed(error) if error.contains("output_file"))
+        );
+
+        let reports = run_batch(&manifest, &config, &variables, 1, true);
+        assert!(matches!(reports[0].status, JobStatus::Failed(_)));
+        assert_eq!(reports[1].status, JobStatus::Skipped);
+    }
+}
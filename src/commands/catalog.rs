@@ -0,0 +1,402 @@
+use std::{
+    collections::BTreeMap,
+    fs,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use anyhow::{anyhow, Context, Result};
+use clap::ValueEnum;
+use credentials::{get_credentials, s3::get_object};
+use log::warn;
+use serde::{Deserialize, Serialize};
+
+use super::config::{user_config_path, CatalogConfig, Config, PatternConfig};
+
+const CATALOG_CACHE_DIR: &str = "catalogs";
+const DEFAULT_REFRESH_MINUTES: u64 = 60;
+
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum)]
+pub enum PatternSetFormat {
+    Toml,
+    Json,
+}
+
+impl PatternSetFormat {
+    /// Json for `.json` files, toml otherwise.
+    pub fn of(path: &str) -> Self {
+        if path.to_lowercase().ends_with(".json") {
+            PatternSetFormat::Json
+        } else {
+            PatternSetFormat::Toml
+        }
+    }
+}
+
+/// Patterns as exported, imported and published in catalogs.
+#[derive(Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PatternSet {
+    pub patterns: BTreeMap<String, PatternConfig>,
+}
+
+impl PatternSet {
+    pub fn parse(content: &str, format: PatternSetFormat) -> Result<Self> {
+        match format {
+            PatternSetFormat::Toml => {
+                toml::from_str(content).with_context(|| "Invalid toml patterns")
+            }
+            PatternSetFormat::Json => {
+                serde_json::from_str(content).with_context(|| "Invalid json patterns")
+            }
+        }
+    }
+
+    pub fn render(&self, format: PatternSetFormat) -> Result<String> {
+        match format {
+            PatternSetFormat::Toml => {
+                toml::to_string_pretty(self).with_context(|| "Failed to serialize patterns")
+            }
+            PatternSetFormat::Json => {
+                serde_json::to_string_pretty(self).with_context(|| "Failed to serialize patterns")
+            }
+        }
+    }
+}
+
+/// What an import did to the existing patterns.
+#[derive(Debug, Default, PartialEq)]
+pub struct ImportReport {
+    pub added: Vec<String>,
+    pub unchanged: Vec<String>,
+    /// Name, existing value and imported value of the patterns defined differently.
+    pub conflicts: Vec<(String, String, String)>,
+}
+
+/// Adds the imported patterns, on conflicts the existing value is kept unless `replace` is set.
+pub fn merge_patterns(
+    patterns: &mut BTreeMap<String, PatternConfig>,
+    imported: PatternSet,
+    replace: bool,
+) -> ImportReport {
+    let mut report = ImportReport::default();
+    for (name, pattern) in imported.patterns {
+        match patterns.get(&name) {
+            None => {
+                report.added.push(name.to_string());
+                patterns.insert(name, pattern);
+            }
+            Some(existing) if *existing == pattern => report.unchanged.push(name),
+            Some(existing) => {
+                report.conflicts.push((
+                    name.to_string(),
+                    existing.value.to_string(),
+                    pattern.value.to_string(),
+                ));
+                if replace {
+                    patterns.insert(name, pattern);
+                }
+            }
+        }
+    }
+    report
+}
+
+pub fn export_patterns(format: PatternSetFormat) -> Result<String> {
+    let config = Config::load_user()?;
+    PatternSet {
+        patterns: config.patterns,
+    }
+    .render(format)
+}
+
+pub fn import_patterns(path: &Path, replace: bool) -> Result<ImportReport> {
+    let content = fs::read_to_string(path)
+        .with_context(|| format!("Failed to read patterns {}", path.display()))?;
+    let imported = PatternSet::parse(&content, PatternSetFormat::of(&path.to_string_lossy()))
+        .with_context(|| format!("Failed to import {}", path.display()))?;
+    let mut config = Config::load_user()?;
+    let report = merge_patterns(&mut config.patterns, imported, replace);
+    if !report.added.is_empty() || (replace && !report.conflicts.is_empty()) {
+        config.save()?;
+    }
+    Ok(report)
+}
+
+fn is_fresh(path: &Path, refresh: Duration) -> bool {
+    fs::metadata(path)
+        .and_then(|metadata| metadata.modified())
+        .ok()
+        .and_then(|modified| modified.elapsed().ok())
+        .is_some_and(|age| age < refresh)
+}
+
+/// When the s3 catalogs are downloaded, local catalogs are always read as they are.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CatalogFetch {
+    /// Read them from the cache without reaching s3
+    CacheOnly,
+    /// Download the ones older than their refresh time
+    Stale,
+    /// Download all of them
+    Always,
+}
+
+/// Reads a catalog, s3 ones are downloaded into `cache_dir` as `fetch` allows and read
+/// from there.
+pub fn load_catalog(
+    name: &str,
+    catalog: &CatalogConfig,
+    profile: Option<&str>,
+    cache_dir: &Path,
+    fetch: CatalogFetch,
+) -> Result<PatternSet> {
+    let format = PatternSetFormat::of(&catalog.source);
+    if !catalog.source.starts_with("s3://") {
+        let path = shellexpand::tilde(&catalog.source).to_string();
+        let content = fs::read_to_string(&path)
+            .with_context(|| format!("Failed to read catalog {} from {}", name, path))?;
+        return PatternSet::parse(&content, format)
+            .with_context(|| format!("Failed to parse catalog {}", name));
+    }
+    let cache_path = cache_dir.join(format!(
+        "{}.{}",
+        name,
+        match format {
+            PatternSetFormat::Toml => "toml",
+            PatternSetFormat::Json => "json",
+        }
+    ));
+    let refresh =
+        Duration::from_secs(60 * catalog.refresh_minutes.unwrap_or(DEFAULT_REFRESH_MINUTES));
+    let download = match fetch {
+        CatalogFetch::CacheOnly => false,
+        CatalogFetch::Stale => !is_fresh(&cache_path, refresh),
+        CatalogFetch::Always => true,
+    };
+    if !download && !cache_path.exists() {
+        return Err(anyhow!(
+            "Catalog {} is not downloaded yet, run `wdapty patterns refresh`",
+            name
+        ));
+    }
+    if download {
+        let downloaded = get_credentials("aws", profile, None)
+            .and_then(|credentials| {
+                get_object(&credentials, &catalog.source, catalog.endpoint.as_deref())
+            })
+            .and_then(|content| {
+                PatternSet::parse(&content, format)?;
+                Ok(content)
+            });
+        match downloaded {
+            Ok(content) => {
+                fs::create_dir_all(cache_dir).with_context(|| {
+                    format!("Failed to create catalog cache {}", cache_dir.display())
+                })?;
+                fs::write(&cache_path, &content)
+                    .with_context(|| format!("Failed to cache catalog {}", name))?;
+            }
+            Err(e) if cache_path.exists() => warn!(
+                "Using the cached catalog {}, failed to refresh it: {:#}",
+                name, e
+            ),
+            Err(e) => {
+                return Err(e).with_context(|| format!("Failed to download catalog {}", name))
+            }
+        }
+    }
+    let content = fs::read_to_string(&cache_path)
+        .with_context(|| format!("Failed to read cached catalog {}", cache_path.display()))?;
+    PatternSet::parse(&content, format).with_context(|| format!("Failed to parse catalog {}", name))
+}
+
+fn catalog_cache_dir() -> Result<PathBuf> {
+    let (config_path, _) = user_config_path()?;
+    Ok(config_path
+        .parent()
+        .unwrap_or_else(|| Path::new("."))
+        .join(CATALOG_CACHE_DIR))
+}
+
+/// The patterns of every catalog in the config, a catalog that cannot be read is skipped
+/// with a warning so the rest of the config stays usable.
+pub fn load_catalogs(config: &Config, fetch: CatalogFetch) -> BTreeMap<String, PatternConfig> {
+    let mut patterns = BTreeMap::new();
+    if config.catalogs.is_empty() {
+        return patterns;
+    }
+    let cache_dir = match catalog_cache_dir() {
+        Ok(cache_dir) => cache_dir,
+        Err(e) => {
+            warn!("Skipping catalogs: {:#}", e);
+            return patterns;
+        }
+    };
+    for (name, catalog) in &config.catalogs {
+        let profile = config.resolve_profile(catalog.profile.clone());
+        match load_catalog(name, catalog, profile.as_deref(), &cache_dir, fetch) {
+            Ok(catalog_patterns) => {
+                for (pattern_name, pattern) in catalog_patterns.patterns {
+                    patterns.entry(pattern_name).or_insert(pattern);
+                }
+            }
+            Err(e) => warn!("Skipping catalog {}: {:#}", name, e),
+        }
+    }
+    patterns
+}
+
+/// Fills in the names the config does not define with the catalog patterns. The s3 catalogs
+/// are read from the cache, stale ones are downloaded only when one of `patterns` is missing
+/// from the config, and nothing is read when none is.
+pub fn merge_catalogs(config: &mut Config, patterns: &[&str]) {
+    let fetch = if patterns.is_empty() {
+        CatalogFetch::CacheOnly
+    } else if patterns
+        .iter()
+        .any(|pattern| !config.patterns.contains_key(*pattern))
+    {
+        CatalogFetch::Stale
+    } else {
+        return;
+    };
+    for (name, pattern) in load_catalogs(config, fetch) {
+        config.patterns.entry(name).or_insert(pattern);
+    }
+}
+
+/// `Config::load` with the catalogs merged in for `patterns`, see `merge_catalogs`.
+pub fn load_with_catalogs(patterns: &[&str]) -> Result<Config> {
+    let mut config = Config::load()?;
+    merge_catalogs(&mut config, patterns);
+    Ok(config)
+}
+
+/// Downloads every s3 catalog again, returning the number of patterns of each catalog.
+pub fn refresh_catalogs() -> Result<Vec<(String, Result<usize>)>> {
+    let config = Config::load()?;
+    let cache_dir = catalog_cache_dir()?;
+    Ok(config
+        .catalogs
+        .iter()
+        .map(|(name, catalog)| {
+            let profile = config.resolve_profile(catalog.profile.clone());
+            let patterns = load_catalog(
+                name,
+                catalog,
+                profile.as_deref(),
+                &cache_dir,
+                CatalogFetch::Always,
+            )
+            .map(|catalog_patterns| catalog_patterns.patterns.len());
+            (name.to_string(), patterns)
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::BTreeMap;
+
+    use assert_fs::{prelude::*, TempDir};
+
+    use crate::commands::config::{CatalogConfig, PatternConfig};
+
+    use super::{
+        load_catalog, merge_patterns, CatalogFetch, ImportReport, PatternSet, PatternSetFormat,
+    };
+
+    fn pattern_set(patterns: &[(&str, &str)]) -> PatternSet {
+        PatternSet {
+            patterns: patterns
+                .iter()
+                .map(|(name, value)| {
+                    (
+                        name.to_string(),
+                        PatternConfig {
+                            value: value.to_string(),
+                            ..Default::default()
+                        },
+                    )
+                })
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn test_render_and_parse_pattern_set() {
+        let patterns = pattern_set(&[("events", "s3://bucket/{date}"), ("logs", "/data/logs")]);
+        for format in [PatternSetFormat::Toml, PatternSetFormat::Json] {
+            let rendered = patterns.render(format).unwrap();
+            assert_eq!(PatternSet::parse(&rendered, format).unwrap(), patterns);
+        }
+        assert_eq!(PatternSetFormat::of("team.JSON"), PatternSetFormat::Json);
+        assert_eq!(
+            PatternSetFormat::of("s3://bucket/team"),
+            PatternSetFormat::Toml
+        );
+    }
+
+    #[test]
+    fn test_merge_patterns() {
+        let mut existing: BTreeMap<String, PatternConfig> =
+            pattern_set(&[("events", "old"), ("logs", "/data/logs")]).patterns;
+        let imported = || pattern_set(&[("events", "new"), ("logs", "/data/logs"), ("x", "y")]);
+
+        let report = merge_patterns(&mut existing, imported(), false);
+        assert_eq!(
+            report,
+            ImportReport {
+                added: vec!["x".to_string()],
+                unchanged: vec!["logs".to_string()],
+                conflicts: vec![("events".to_string(), "old".to_string(), "new".to_string())],
+            }
+        );
+        assert_eq!(existing["events"].value, "old");
+
+        let report = merge_patterns(&mut existing, imported(), true);
+        assert_eq!(report.conflicts.len(), 1);
+        assert_eq!(existing["events"].value, "new");
+    }
+
+    #[test]
+    fn test_load_local_catalog() {
+        let dir = TempDir::new().unwrap();
+        let file = dir.child("team.json");
+        file.write_str(r#"{"patterns": {"events": {"value": "s3://team/{date}"}}}"#)
+            .unwrap();
+        let catalog = CatalogConfig {
+            source: file.path().display().to_string(),
+            ..Default::default()
+        };
+        let patterns =
+            load_catalog("team", &catalog, None, dir.path(), CatalogFetch::CacheOnly).unwrap();
+        assert_eq!(patterns.patterns["events"].value, "s3://team/{date}");
+
+        let missing = CatalogConfig {
+            source: dir.child("missing.toml").path().display().to_string(),
+            ..Default::default()
+        };
+        assert!(load_catalog("missing", &missing, None, dir.path(), CatalogFetch::Always).is_err());
+    }
+
+    #[test]
+    fn test_s3_catalog_read_from_cache_only() {
+        let dir = TempDir::new().unwrap();
+        let catalog = CatalogConfig {
+            source: "s3://team/patterns.toml".to_string(),
+            ..Default::default()
+        };
+        let error =
+            load_catalog("team", &catalog, None, dir.path(), CatalogFetch::CacheOnly).unwrap_err();
+        assert!(error.to_string().contains("not downloaded yet"));
+
+        dir.child("team.toml")
+            .write_str("[patterns.events]\nvalue = \"s3://team/{date}\"\n")
+            .unwrap();
+        let patterns =
+            load_catalog("team", &catalog, None, dir.path(), CatalogFetch::CacheOnly).unwrap();
+        assert_eq!(patterns.patterns["events"].value, "s3://team/{date}");
+    }
+}
@@ -1,75 +1,489 @@
 use std::{
-    fs::{self, create_dir_all, File, OpenOptions},
-    io::Write,
-    path::PathBuf,
+    collections::BTreeMap,
+    env,
+    fs::{self, create_dir_all},
+    path::{Path, PathBuf},
+    sync::OnceLock,
+    thread,
+    time::Duration,
 };
 
 use anyhow::{anyhow, Context, Result};
+use chrono::{Local, NaiveDateTime};
+use log::{debug, info, warn};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
 
-use super::pattern::does_pattern_match_pattern_format;
+pub const CONFIG_PATH: &str = "~/.wdapty/config.toml";
+pub const LEGACY_CONFIG_PATH: &str = "~/.wdapty/config.ini";
+pub const CONFIG_ENV: &str = "WDAPTY_CONFIG";
+pub const PROJECT_CONFIG_FILE: &str = ".wdapty.toml";
+pub const BACKUP_DIR: &str = "backups";
+const MAX_BACKUPS: usize = 20;
+const BACKUP_TIMESTAMP_FORMAT: &str = "%Y%m%dT%H%M%S%.3f";
+
+static CONFIG_OVERRIDE: OnceLock<PathBuf> = OnceLock::new();
+
+#[derive(Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    pub defaults: Defaults,
+    pub output: Output,
+    pub patterns: BTreeMap<String, PatternConfig>,
+    pub profiles: BTreeMap<String, ProfileConfig>,
+    pub catalogs: BTreeMap<String, CatalogConfig>,
+    pub queries: BTreeMap<String, QueryConfig>,
+}
+
+#[derive(Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Defaults {
+    pub profile: Option<String>,
+    pub execution_type: Option<String>,
+}
+
+#[derive(Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Output {
+    /// Directory relative output files are written to.
+    pub directory: Option<PathBuf>,
+    #[serde(flatten)]
+    pub display: Display,
+}
+
+/// How results printed in the terminal look, unset values keep the polars defaults.
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Display {
+    /// Rows printed, 0 prints all of them.
+    pub max_rows: Option<usize>,
+    /// Columns printed, 0 prints all of them.
+    pub max_cols: Option<usize>,
+    /// Longest text printed in a cell.
+    pub max_col_width: Option<usize>,
+    /// ascii, utf8, markdown or compact.
+    pub table_style: Option<String>,
+    pub header: Option<bool>,
+    /// Pages results taller than the terminal through $PAGER, on by default.
+    pub pager: Option<bool>,
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PatternConfig {
+    pub value: String,
+    #[serde(flatten)]
+    pub defaults: PatternDefaults,
+}
+
+/// How a pattern is processed unless the command line says otherwise.
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PatternDefaults {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub profile: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub execution_type: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cols: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub index_name: Option<String>,
+    /// Columns to sort by, `name` or `name:desc`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sort: Option<Vec<String>>,
+    /// Format of the output file, csv or parquet.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub output_format: Option<String>,
+}
+
+/// A search or download saved under a name, its index value, filters and output file take
+/// placeholders like the pattern does.
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+pub struct QueryConfig {
+    /// Name of the pattern to read.
+    pub pattern: String,
+    /// Value of the index column, the query is a download without it.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub index_value: Option<String>,
+    /// Filters like `amount > 100`, every one of them has to match.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub filters: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub limit: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub output_file: Option<String>,
+    /// Layered over the defaults of the pattern.
+    #[serde(flatten)]
+    pub defaults: PatternDefaults,
+}
+
+#[derive(Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ProfileConfig {
+    /// Name of the profile in the aws credentials file, when different from the wdapty one.
+    pub aws_profile: Option<String>,
+}
+
+/// A read-only set of patterns shared from a file or an s3 object.
+#[derive(Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CatalogConfig {
+    /// Local path or `s3://bucket/key` of a toml or json pattern set.
+    pub source: String,
+    /// Profile used to download an s3 catalog.
+    pub profile: Option<String>,
+    /// Endpoint to call instead of aws s3, e.g. a minio server.
+    pub endpoint: Option<String>,
+    /// How long a downloaded s3 catalog is used before a command reading one of its patterns
+    /// downloads it again, 60 by default.
+    pub refresh_minutes: Option<u64>,
+}
+
+impl PatternDefaults {
+    /// Replaces the defaults that are set in `other`.
+    pub fn update(&mut self, other: PatternDefaults) {
+        self.profile = other.profile.or(self.profile.take());
+        self.execution_type = other.execution_type.or(self.execution_type.take());
+        self.cols = other.cols.or(self.cols.take());
+        self.index_name = other.index_name.or(self.index_name.take());
+        self.sort = other.sort.or(self.sort.take());
+        self.output_format = other.output_format.or(self.output_format.take());
+    }
+
+    pub fn is_empty(&self) -> bool {
+        *self == PatternDefaults::default()
+    }
+}
+
+impl Display {
+    /// Replaces the settings that are set in `other`.
+    pub fn update(&mut self, other: Display) {
+        self.max_rows = other.max_rows.or(self.max_rows.take());
+        self.max_cols = other.max_cols.or(self.max_cols.take());
+        self.max_col_width = other.max_col_width.or(self.max_col_width.take());
+        self.table_style = other.table_style.or(self.table_style.take());
+        self.header = other.header.or(self.header.take());
+        self.pager = other.pager.or(self.pager.take());
+    }
+}
 
 pub fn expand_config_path(path: &str) -> Result<PathBuf> {
-    return shellexpand::tilde(path)
+    shellexpand::tilde(path)
         .parse::<PathBuf>()
-        .with_context(|| format!("Failed to expand config path {}", path));
+        .with_context(|| format!("Failed to expand config path {}", path))
 }
 
-pub fn read_config_file() -> Result<String> {
-    let config_path = expand_config_path("~/.wdapty/config.ini")?;
-    Ok(fs::read_to_string(config_path)?)
+/// Sets the config file passed with `--config`, it wins over every other location.
+pub fn set_config_override(path: PathBuf) {
+    let _ = CONFIG_OVERRIDE.set(path);
 }
 
-pub fn get_config_file(file_name: Option<&str>) -> Result<(String, File)> {
-    let app_config_path = if let Some(f_name) = file_name {
-        f_name
-    } else {
-        "~/.wdapty/config.ini"
-    };
-    let expanded_path: PathBuf = shellexpand::tilde(app_config_path).to_string().into();
-    let prefix = expanded_path
-        .parent()
-        .with_context(|| format!("Failed to extract parent from {}", expanded_path.display()))?;
-    create_dir_all(prefix)
-        .with_context(|| format!("Failed to create config dir {}", prefix.display()))?;
-    let file = OpenOptions::new()
-        .append(true)
-        .create(true)
-        .open(&expanded_path)
-        .with_context(|| format!("Failed at File::Create {}", &app_config_path))?;
-    Ok((app_config_path.to_string(), file))
+/// Returns the user config path, from `--config`, `WDAPTY_CONFIG`,
+/// `$XDG_CONFIG_HOME/wdapty/config.toml` or `~/.wdapty/config.toml` in this order,
+/// together with the legacy file to migrate when the location was not chosen explicitly.
+pub fn user_config_path() -> Result<(PathBuf, Option<PathBuf>)> {
+    if let Some(path) = CONFIG_OVERRIDE.get() {
+        return Ok((path.clone(), None));
+    }
+    if let Ok(path) = env::var(CONFIG_ENV) {
+        return Ok((expand_config_path(&path)?, None));
+    }
+    let legacy_path = Some(expand_config_path(LEGACY_CONFIG_PATH)?);
+    if let Some(xdg_config_home) = env::var_os("XDG_CONFIG_HOME").filter(|dir| !dir.is_empty()) {
+        let path = PathBuf::from(xdg_config_home)
+            .join("wdapty")
+            .join("config.toml");
+        return Ok((path, legacy_path));
+    }
+    Ok((expand_config_path(CONFIG_PATH)?, legacy_path))
 }
 
-pub fn save_vec_of_string_to_config(patterns: Vec<String>, mut file: File) -> Result<bool> {
-    for pattern in patterns {
-        save_string_to_config(pattern, &mut file)?;
+/// Looks for a `.wdapty.toml` in `start` and its parents.
+pub fn find_project_config(start: &Path) -> Option<PathBuf> {
+    start
+        .ancestors()
+        .map(|dir| dir.join(PROJECT_CONFIG_FILE))
+        .find(|path| path.is_file())
+}
+
+impl Output {
+    /// Places relative output files in the configured directory.
+    pub fn resolve(&self, output_file: String) -> String {
+        match &self.directory {
+            Some(directory) if Path::new(&output_file).is_relative() => {
+                directory.join(output_file).display().to_string()
+            }
+            _ => output_file,
+        }
     }
+}
+
+fn backup_dir(path: &Path) -> PathBuf {
+    path.parent()
+        .unwrap_or_else(|| Path::new("."))
+        .join(BACKUP_DIR)
+}
+
+fn backup_prefix(path: &Path) -> String {
+    let stem = path
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().into_owned())
+        .unwrap_or_else(|| "config".to_string());
+    format!("{}-", stem)
+}
 
-    Ok(true)
+/// True for the `<name>-<timestamp>.toml` files made by `backup`, so that the backups of
+/// `config-work.toml` are not taken for backups of `config.toml`.
+fn is_backup_name(name: &str, prefix: &str) -> bool {
+    name.strip_prefix(prefix)
+        .and_then(|name| name.strip_suffix(".toml"))
+        .is_some_and(|timestamp| {
+            NaiveDateTime::parse_from_str(timestamp, BACKUP_TIMESTAMP_FORMAT).is_ok()
+        })
+}
+
+/// The backups of the config in `path`, oldest first.
+pub fn list_backups(path: &Path) -> Result<Vec<PathBuf>> {
+    let dir = backup_dir(path);
+    if !dir.exists() {
+        return Ok(Vec::new());
+    }
+    let prefix = backup_prefix(path);
+    let mut backups = fs::read_dir(&dir)
+        .with_context(|| format!("Failed to read backups in {}", dir.display()))?
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|backup| {
+            backup
+                .file_name()
+                .is_some_and(|name| is_backup_name(&name.to_string_lossy(), &prefix))
+        })
+        .collect::<Vec<_>>();
+    backups.sort();
+    Ok(backups)
+}
+
+/// Copies the config in `path` to `backups/<name>-<timestamp>.toml`, keeping the latest ones.
+fn backup(path: &Path) -> Result<PathBuf> {
+    let dir = backup_dir(path);
+    create_dir_all(&dir)
+        .with_context(|| format!("Failed to create backup dir {}", dir.display()))?;
+    let backup_path = loop {
+        let candidate = dir.join(format!(
+            "{}{}.toml",
+            backup_prefix(path),
+            Local::now().format(BACKUP_TIMESTAMP_FORMAT)
+        ));
+        if !candidate.exists() {
+            break candidate;
+        }
+        thread::sleep(Duration::from_millis(1));
+    };
+    fs::copy(path, &backup_path)
+        .with_context(|| format!("Failed to back up config {}", path.display()))?;
+    let backups = list_backups(path)?;
+    for old_backup in backups
+        .iter()
+        .take(backups.len().saturating_sub(MAX_BACKUPS))
+    {
+        let _ = fs::remove_file(old_backup);
+    }
+    Ok(backup_path)
+}
+
+/// Writes to a temporary file next to `path` and renames it over `path`,
+/// so the config is never left half written.
+fn write_atomic(path: &Path, content: &str) -> Result<()> {
+    let file_name = path
+        .file_name()
+        .with_context(|| format!("Invalid config path {}", path.display()))?;
+    let tmp_path = path.with_file_name(format!(".{}.tmp", file_name.to_string_lossy()));
+    fs::write(&tmp_path, content)
+        .with_context(|| format!("Failed to write {}", tmp_path.display()))?;
+    fs::rename(&tmp_path, path).with_context(|| format!("Failed to save config {}", path.display()))
+}
+
+/// Restores the config in `path` from a backup, the latest one when none is given.
+/// `backup` is either a path or the file name of one of the backups.
+/// The config being replaced is backed up as well.
+pub fn restore_backup(path: &Path, backup_name: Option<&str>) -> Result<PathBuf> {
+    let backups = list_backups(path)?;
+    let backup_path = match backup_name {
+        Some(backup_name) if Path::new(backup_name).exists() => PathBuf::from(backup_name),
+        Some(backup_name) => backups
+            .into_iter()
+            .find(|backup| backup.file_name().is_some_and(|name| name == backup_name))
+            .ok_or_else(|| anyhow!("No backup named {} for {}", backup_name, path.display()))?,
+        None => backups
+            .into_iter()
+            .last()
+            .ok_or_else(|| anyhow!("No backups found for {}", path.display()))?,
+    };
+    let content = fs::read_to_string(&backup_path)
+        .with_context(|| format!("Failed to read backup {}", backup_path.display()))?;
+    toml::from_str::<Config>(&content)
+        .with_context(|| format!("Backup {} is not a valid config", backup_path.display()))?;
+    if path.exists() {
+        backup(path)?;
+    }
+    write_atomic(path, &content)?;
+    Ok(backup_path)
 }
 
-pub fn save_string_to_config_with_overwrite(patterns: Vec<String>, file: File) -> Result<bool> {
-    let _ = file.set_len(0);
-    let _ = save_vec_of_string_to_config(patterns, file);
-    Ok(true)
+/// Splits a `name=value` pattern definition, the name can only contain
+/// letters, numbers, `_`, `-` and `.`.
+pub fn parse_pattern_definition(definition: &str) -> Result<(String, String)> {
+    let name_format = Regex::new(r"^[A-Za-z0-9_\-.]+$").unwrap();
+    let (name, value) = definition
+        .split_once('=')
+        .map(|(name, value)| (name.trim(), value.trim()))
+        .filter(|(name, value)| name_format.is_match(name) && !value.is_empty())
+        .ok_or_else(|| {
+            anyhow!(
+                "Pattern {} is not compliant with pattern_format name=value",
+                definition
+            )
+        })?;
+    Ok((name.to_string(), value.to_string()))
 }
 
-pub fn save_string_to_config(pattern: String, file: &mut File) -> Result<(), anyhow::Error> {
-    if does_pattern_match_pattern_format(&pattern) {
-        println!("Saving pattern {} to config.ini", pattern);
-        file.write(format!("{}\n", pattern).as_bytes())
-            .with_context(|| format!("Failed to save pattern {}", pattern))?;
-        Ok(())
-    } else {
-        Err(anyhow!(
-            "Pattern {} is not compliant with pattern_format name=value",
-            pattern
-        ))
+impl Config {
+    /// The user config with the project config found from the current directory layered over it.
+    /// Use `load_user` when the config is going to be saved, and `catalog::load_with_catalogs`
+    /// when catalog patterns are needed.
+    pub fn load() -> Result<Config> {
+        let mut config = Config::load_user()?;
+        let project_config = env::current_dir()
+            .ok()
+            .and_then(|current_dir| find_project_config(&current_dir));
+        if let Some(project_config) = project_config {
+            config.merge(Config::load_from(&project_config, None)?);
+        }
+        Ok(config)
+    }
+
+    pub fn load_user() -> Result<Config> {
+        let (path, legacy_path) = user_config_path()?;
+        Config::load_from(&path, legacy_path.as_deref())
+    }
+
+    /// Reads the config in `path`, migrating the flat `name=value` file in
+    /// `legacy_path` the first time.
+    pub fn load_from(path: &Path, legacy_path: Option<&Path>) -> Result<Config> {
+        if path.exists() {
+            debug!("Reading config {}", path.display());
+            let content = fs::read_to_string(path)
+                .with_context(|| format!("Failed to read config {}", path.display()))?;
+            return toml::from_str(&content)
+                .with_context(|| format!("Failed to parse config {}", path.display()));
+        }
+        if let Some(legacy_path) = legacy_path.filter(|legacy_path| legacy_path.exists()) {
+            let content = fs::read_to_string(legacy_path)
+                .with_context(|| format!("Failed to read config {}", legacy_path.display()))?;
+            let config = Config::from_legacy(&content, legacy_path);
+            config.save_to(path)?;
+            info!("Migrated {} to {}", legacy_path.display(), path.display());
+            return Ok(config);
+        }
+        Ok(Config::default())
+    }
+
+    /// Reads the `name=value` lines of the legacy file, warning about the ones that are
+    /// skipped as the legacy file is not read anymore once migrated.
+    fn from_legacy(content: &str, legacy_path: &Path) -> Config {
+        let patterns = content
+            .lines()
+            .enumerate()
+            .filter(|(_, line)| !line.trim().is_empty())
+            .filter_map(|(index, line)| match parse_pattern_definition(line) {
+                Ok(pattern) => Some(pattern),
+                Err(e) => {
+                    warn!(
+                        "Skipping line {} of {} while migrating it: {}",
+                        index + 1,
+                        legacy_path.display(),
+                        e
+                    );
+                    None
+                }
+            })
+            .map(|(name, value)| {
+                (
+                    name,
+                    PatternConfig {
+                        value,
+                        ..Default::default()
+                    },
+                )
+            })
+            .collect();
+        Config {
+            patterns,
+            ..Default::default()
+        }
+    }
+
+    /// Layers `other` over this config, its values win.
+    pub fn merge(&mut self, other: Config) {
+        self.defaults.profile = other.defaults.profile.or(self.defaults.profile.take());
+        self.defaults.execution_type = other
+            .defaults
+            .execution_type
+            .or(self.defaults.execution_type.take());
+        self.output.directory = other.output.directory.or(self.output.directory.take());
+        self.output.display.update(other.output.display);
+        self.patterns.extend(other.patterns);
+        self.profiles.extend(other.profiles);
+        self.catalogs.extend(other.catalogs);
+        self.queries.extend(other.queries);
+    }
+
+    /// The defaults of a pattern, empty when there is no pattern or it has none.
+    pub fn pattern_defaults(&self, pattern: Option<&str>) -> PatternDefaults {
+        pattern
+            .and_then(|name| self.patterns.get(name))
+            .map(|pattern| pattern.defaults.clone())
+            .unwrap_or_default()
+    }
+
+    pub fn save(&self) -> Result<PathBuf> {
+        let (path, _) = user_config_path()?;
+        self.save_to(&path)?;
+        Ok(path)
+    }
+
+    pub fn save_to(&self, path: &Path) -> Result<()> {
+        let prefix = path
+            .parent()
+            .with_context(|| format!("Failed to extract parent from {}", path.display()))?;
+        create_dir_all(prefix)
+            .with_context(|| format!("Failed to create config dir {}", prefix.display()))?;
+        let content = toml::to_string_pretty(self).with_context(|| "Failed to serialize config")?;
+        if path.exists() {
+            backup(path)?;
+        }
+        write_atomic(path, &content)
+    }
+
+    /// Resolves a wdapty profile name to the one to look up in the aws credentials.
+    pub fn resolve_profile(&self, profile: Option<String>) -> Option<String> {
+        let profile = profile.or_else(|| self.defaults.profile.clone())?;
+        let aws_profile = self
+            .profiles
+            .get(&profile)
+            .and_then(|profile_config| profile_config.aws_profile.clone());
+        Some(aws_profile.unwrap_or(profile))
     }
 }
 
 #[cfg(test)]
 mod test {
-    use super::get_config_file;
+    use std::{fs, path::PathBuf};
+
+    use assert_fs::{prelude::*, TempDir};
+
+    use super::{
+        find_project_config, list_backups, parse_pattern_definition, restore_backup, Config,
+        PatternConfig, PatternDefaults, ProfileConfig,
+    };
 
     #[macro_export]
     macro_rules! integration_test_results_path {
@@ -84,9 +498,173 @@ mod test {
     }
 
     #[test]
-    fn test_get_config_file() {
-        let config_path = integration_test_results_path!("config.ini");
-        let result = get_config_file(Some(&config_path));
-        assert!(result.is_ok())
+    fn test_parse_pattern_definition() {
+        let (name, value) = parse_pattern_definition(
+            "amazingtestname=s3://somethingsomthineasdas//asas//assa/{asdasd}/{asdas}",
+        )
+        .expect("definition should be valid");
+        assert_eq!(name, "amazingtestname");
+        assert_eq!(
+            value,
+            "s3://somethingsomthineasdas//asas//assa/{asdasd}/{asdas}"
+        );
+
+        let (_, value) = parse_pattern_definition("amazing-test-name=/data/my reports/{PATH}.parq")
+            .expect("values can contain spaces");
+        assert_eq!(value, "/data/my reports/{PATH}.parq");
+
+        assert!(parse_pattern_definition("amazing test name={PATH}/test.parq").is_err());
+        assert!(parse_pattern_definition("amazingtestname=").is_err());
+        assert!(parse_pattern_definition("amazingtestname").is_err());
+    }
+
+    #[test]
+    fn test_save_and_load_config() {
+        let path = PathBuf::from(integration_test_results_path!("config_save_and_load.toml"));
+        let mut config = Config::default();
+        config.defaults.profile = Some("prod".to_string());
+        config.patterns.insert(
+            "events".to_string(),
+            PatternConfig {
+                value: "s3://bucket/events/{date}/part.parquet".to_string(),
+                defaults: PatternDefaults {
+                    profile: Some("prod".to_string()),
+                    cols: Some(vec!["id".to_string(), "amount".to_string()]),
+                    sort: Some(vec!["amount:desc".to_string()]),
+                    ..Default::default()
+                },
+            },
+        );
+        config.profiles.insert(
+            "prod".to_string(),
+            ProfileConfig {
+                aws_profile: Some("prod-readonly".to_string()),
+            },
+        );
+        config.save_to(&path).expect("config should be saved");
+
+        let content = fs::read_to_string(&path).unwrap();
+        assert!(content.contains("[patterns.events]"));
+        assert!(content.contains("[profiles.prod]"));
+        assert!(content.contains("sort = [\"amount:desc\"]"));
+        assert!(!content.contains("index_name"));
+
+        let loaded = Config::load_from(&path, None).expect("config should be loaded");
+        assert_eq!(loaded, config);
+        assert_eq!(
+            loaded.resolve_profile(None).as_deref(),
+            Some("prod-readonly")
+        );
+        assert_eq!(
+            loaded.resolve_profile(Some("dev".to_string())).as_deref(),
+            Some("dev")
+        );
+    }
+
+    #[test]
+    fn test_migrate_legacy_config() {
+        let path = PathBuf::from(integration_test_results_path!("config_migrated.toml"));
+        let legacy_path = PathBuf::from(integration_test_results_path!("config_legacy.ini"));
+        let _ = fs::remove_file(&path);
+        fs::write(
+            &legacy_path,
+            "events=s3://bucket/events/{date}/part.parquet\nnot a pattern\nlocal=~/data/{file}.parq\n",
+        )
+        .unwrap();
+
+        let config =
+            Config::load_from(&path, Some(&legacy_path)).expect("legacy should be migrated");
+        assert_eq!(config.patterns.len(), 2);
+        assert_eq!(
+            config.patterns.get("local").unwrap().value,
+            "~/data/{file}.parq"
+        );
+        assert!(path.exists());
+        assert!(legacy_path.exists());
+    }
+
+    #[test]
+    fn test_load_missing_config() {
+        let config = Config::load_from(
+            &PathBuf::from("config/does/not/exist"),
+            Some(&PathBuf::from("legacy/does/not/exist")),
+        )
+        .expect("missing config is empty");
+        assert_eq!(config, Config::default());
+    }
+
+    #[test]
+    fn test_find_project_config() {
+        let root = TempDir::new().unwrap();
+        let nested = root.child("repo/src/module");
+        nested.create_dir_all().unwrap();
+        assert_eq!(find_project_config(nested.path()), None);
+
+        root.child("repo/.wdapty.toml").touch().unwrap();
+        assert_eq!(
+            find_project_config(nested.path()),
+            Some(root.child("repo/.wdapty.toml").path().to_path_buf())
+        );
+    }
+
+    #[test]
+    fn test_merge_project_config() {
+        let mut user: Config = toml::from_str(
+            "[defaults]\nprofile = \"dev\"\nexecution_type = \"parq\"\n\n[output]\nmax_rows = 20\nmax_cols = 20\n\n[patterns.events]\nvalue = \"user/events\"\n\n[patterns.logs]\nvalue = \"user/logs\"\n",
+        )
+        .unwrap();
+        let project: Config = toml::from_str(
+            "[defaults]\nprofile = \"prod\"\n\n[output]\nmax_rows = 50\npager = false\n\n[patterns.events]\nvalue = \"project/events\"\n",
+        )
+        .unwrap();
+        user.merge(project);
+
+        assert_eq!(user.defaults.profile.as_deref(), Some("prod"));
+        assert_eq!(user.defaults.execution_type.as_deref(), Some("parq"));
+        assert_eq!(user.patterns.get("events").unwrap().value, "project/events");
+        assert_eq!(user.patterns.get("logs").unwrap().value, "user/logs");
+        assert_eq!(user.output.display.max_rows, Some(50));
+        assert_eq!(user.output.display.max_cols, Some(20));
+        assert_eq!(user.output.display.pager, Some(false));
+    }
+
+    #[test]
+    fn test_backup_and_restore() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.child("config.toml").path().to_path_buf();
+        let mut config = Config::default();
+        config.patterns.insert(
+            "events".to_string(),
+            PatternConfig {
+                value: "first".to_string(),
+                ..Default::default()
+            },
+        );
+        config.save_to(&path).unwrap();
+        assert!(list_backups(&path).unwrap().is_empty());
+
+        config.patterns.get_mut("events").unwrap().value = "second".to_string();
+        config.save_to(&path).unwrap();
+        let backups = list_backups(&path).unwrap();
+        assert_eq!(backups.len(), 1);
+        assert!(fs::read_to_string(&backups[0]).unwrap().contains("first"));
+        assert!(!dir.child(".config.toml.tmp").exists());
+
+        let restored = restore_backup(&path, None).expect("latest backup should be restored");
+        assert_eq!(restored, backups[0]);
+        let loaded = Config::load_from(&path, None).unwrap();
+        assert_eq!(loaded.patterns.get("events").unwrap().value, "first");
+        assert_eq!(list_backups(&path).unwrap().len(), 2);
+
+        assert!(restore_backup(&path, Some("config-missing.toml")).is_err());
+        let other = dir.child("other.toml").path().to_path_buf();
+        assert!(restore_backup(&other, None).is_err());
+
+        let work = dir.child("config-work.toml").path().to_path_buf();
+        config.save_to(&work).unwrap();
+        config.patterns.get_mut("events").unwrap().value = "work".to_string();
+        config.save_to(&work).unwrap();
+        assert_eq!(list_backups(&work).unwrap().len(), 1);
+        assert_eq!(list_backups(&path).unwrap().len(), 2);
     }
 }
@@ -4,23 +4,34 @@ use anyhow::Result;
 
 use crate::commands::input::request_user_input;
 
-use super::config::{get_config_file, save_vec_of_string_to_config};
+use super::config::{parse_pattern_definition, Config, PatternConfig};
 
 pub fn initialize(starting_patterns: Option<Vec<String>>) -> Result<String> {
-    let (app_config_path, file) = get_config_file(None)?;
+    let mut config = Config::load_user()?;
     let patterns: Vec<String> = if let Some(st_patterns) = starting_patterns {
         st_patterns
     } else {
         ask_user_for_patterns()
     };
 
-    save_vec_of_string_to_config(patterns, file)?;
+    for pattern in patterns {
+        let (name, value) = parse_pattern_definition(&pattern)?;
+        eprintln!("Saving pattern {} to config", pattern);
+        config.patterns.insert(
+            name,
+            PatternConfig {
+                value,
+                ..Default::default()
+            },
+        );
+    }
+    let app_config_path = config.save()?;
 
-    Ok(app_config_path)
+    Ok(app_config_path.display().to_string())
 }
 
 fn ask_user_for_patterns() -> Vec<String> {
-    println!("Fill in your config with yout pattern, must be in the format name=something, type x to exit");
+    eprintln!("Fill in your config with yout pattern, must be in the format name=something, type x to exit");
     let mut new_patterns = vec![];
     let prompt = "Paste your file pattern that you want to use one at the time";
     loop {
@@ -0,0 +1,119 @@
+use anyhow::{anyhow, Result};
+use credentials::{
+    chain::CredentialsChain,
+    inspect::{explain_profile, list_profiles, redact_partially},
+    sts::get_caller_identity,
+};
+use serde_json::json;
+
+use super::output::{print_record, print_records};
+
+pub fn print_profiles() -> Result<()> {
+    let profiles = list_profiles(None, None)?;
+    let records: Vec<_> = profiles
+        .iter()
+        .map(|profile| {
+            json!({ "name": profile.name, "sources": profile.sources, "fields": profile.fields })
+        })
+        .collect();
+    print_records(&records, || {
+        if profiles.is_empty() {
+            println!("No profiles found in ~/.aws/credentials or ~/.aws/config");
+        }
+        for profile in &profiles {
+            println!(
+                "Profile {} ({}) has fields {}",
+                profile.name,
+                profile.sources.join(", "),
+                profile.fields.join(", ")
+            );
+        }
+    })
+}
+
+pub fn check_profile(profile: Option<&str>, endpoint: Option<&str>, call: bool) -> Result<()> {
+    let profile_name = profile.unwrap_or("default");
+    let provided = CredentialsChain::aws_default(profile, None).provide()?;
+    let credentials = provided.credentials;
+    let access_key = redact_partially(&credentials.access_key_id);
+    if credentials.is_expired() {
+        return Err(anyhow!(
+            "Credentials for profile {} are expired",
+            profile_name
+        ));
+    }
+    let arn = if call || endpoint.is_some() {
+        Some(get_caller_identity(&credentials, endpoint)?)
+    } else {
+        None
+    };
+    let expiration = credentials
+        .expiration
+        .map(|expiration| expiration.to_rfc3339());
+    let record = json!({
+        "profile": profile_name,
+        "source": provided.source,
+        "access_key": access_key,
+        "expiration": expiration,
+        "arn": arn,
+    });
+    print_record(&record, || {
+        println!(
+            "Credentials for profile {} are complete, supplied by {} with access key {}",
+            profile_name, provided.source, access_key
+        );
+        if let Some(expiration) = &expiration {
+            println!("Credentials expire at {}", expiration);
+        }
+        if let Some(arn) = &arn {
+            println!("Test call succeeded, authenticated as {}", arn);
+        }
+    })
+}
+
+/// Explains the fields of the profile and which provider would answer, without running
+/// nor calling any of them.
+pub fn print_explanation(profile: Option<&str>) -> Result<()> {
+    let profile_name = profile.unwrap_or("default");
+    let explanations = explain_profile(profile, None, None)?;
+    let chain = CredentialsChain::aws_default(profile, None);
+    let provider = chain.describe();
+    let described = provider.as_ref().ok().and_then(Option::as_ref);
+    let record = json!({
+        "profile": profile_name,
+        "fields": explanations
+            .iter()
+            .map(|explanation| json!({
+                "field": explanation.field,
+                "value": explanation.value,
+                "source": explanation.source,
+            }))
+            .collect::<Vec<_>>(),
+        "providers": chain.link_names(),
+        "supplied_by": described.map(|(name, _)| name),
+        "supplied_with": described.map(|(_, description)| description),
+        "error": provider.as_ref().err().map(|e| format!("{:#}", e)),
+    });
+    print_record(&record, || {
+        println!("Fields used by the providers for {}:", profile_name);
+        for explanation in &explanations {
+            match (&explanation.source, &explanation.value) {
+                (Some(source), Some(value)) => {
+                    println!("  {} = {} from {}", explanation.field, value, source)
+                }
+                _ => println!("  {} is not set", explanation.field),
+            }
+        }
+        println!(
+            "Providers tried in order: {}",
+            chain.link_names().join(", ")
+        );
+        match &provider {
+            Ok(Some((name, description))) => {
+                println!("Credentials would be supplied by {}, {}", name, description)
+            }
+            Ok(None) => println!("No provider is configured for {}", profile_name),
+            Err(e) => println!("No provider supplies credentials: {:#}", e),
+        }
+    })
+}
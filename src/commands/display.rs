@@ -0,0 +1,188 @@
+use std::{
+    env,
+    io::{self, IsTerminal, Write},
+    process::{Command, Stdio},
+};
+
+use anyhow::{anyhow, Context, Result};
+use clap::ValueEnum;
+use crossterm::terminal;
+use log::warn;
+use polars::frame::DataFrame;
+
+use super::config::Display;
+
+const DEFAULT_PAGER: &str = "less -S";
+
+/// Borders of the printed tables.
+#[derive(Debug, Default, Clone, Copy, PartialEq, ValueEnum)]
+pub enum TableStyle {
+    Ascii,
+    #[default]
+    Utf8,
+    /// Markdown table without the column types and the shape.
+    Markdown,
+    /// No borders, the column types or the shape.
+    Compact,
+}
+
+impl TableStyle {
+    pub fn parse(style: &str) -> Result<Self> {
+        TableStyle::from_str(style, true).map_err(|_| {
+            anyhow!(
+                "Unknown table style {}, use ascii, utf8, markdown or compact",
+                style
+            )
+        })
+    }
+
+    fn polars_formatting(self) -> &'static str {
+        match self {
+            TableStyle::Ascii => "ASCII_FULL_CONDENSED",
+            TableStyle::Utf8 => "UTF8_FULL_CONDENSED",
+            TableStyle::Markdown => "ASCII_MARKDOWN",
+            TableStyle::Compact => "NOTHING",
+        }
+    }
+}
+
+/// 0 means no limit, which polars reads as a negative limit.
+fn limit(value: usize) -> String {
+    if value == 0 {
+        "-1".to_string()
+    } else {
+        value.to_string()
+    }
+}
+
+/// The polars environment variables giving the display settings, only the ones set are listed.
+fn format_variables(display: &Display) -> Result<Vec<(&'static str, String)>> {
+    let mut variables = Vec::new();
+    if let Some(max_rows) = display.max_rows {
+        variables.push(("POLARS_FMT_MAX_ROWS", limit(max_rows)));
+    }
+    if let Some(max_cols) = display.max_cols {
+        variables.push(("POLARS_FMT_MAX_COLS", limit(max_cols)));
+    }
+    if let Some(max_col_width) = display.max_col_width {
+        variables.push(("POLARS_FMT_STR_LEN", max_col_width.to_string()));
+    }
+    let style = display
+        .table_style
+        .as_deref()
+        .map(TableStyle::parse)
+        .transpose()?;
+    if let Some(style) = style {
+        variables.push((
+            "POLARS_FMT_TABLE_FORMATTING",
+            style.polars_formatting().into(),
+        ));
+    }
+    let bare = matches!(style, Some(TableStyle::Markdown | TableStyle::Compact));
+    if bare {
+        variables.push((
+            "POLARS_FMT_TABLE_HIDE_DATAFRAME_SHAPE_INFORMATION",
+            "1".into(),
+        ));
+    }
+    if display.header == Some(false) {
+        variables.push(("POLARS_FMT_TABLE_HIDE_COLUMN_NAMES", "1".into()));
+    }
+    if display.header == Some(false) || bare {
+        variables.push(("POLARS_FMT_TABLE_HIDE_COLUMN_DATA_TYPES", "1".into()));
+    }
+    Ok(variables)
+}
+
+/// Makes the data frames printed from now on follow the display settings. Polars reads them
+/// from the environment, so this runs once before any thread starts: setting variables while
+/// other threads may read them is unsound.
+pub fn apply_display(display: &Display) -> Result<()> {
+    for (name, value) in format_variables(display)? {
+        env::set_var(name, value);
+    }
+    Ok(())
+}
+
+fn taller_than_terminal(text: &str) -> bool {
+    terminal::size()
+        .map(|(_, height)| text.lines().count() >= height as usize)
+        .unwrap_or(false)
+}
+
+fn page(text: &str) -> Result<()> {
+    let pager = env::var("PAGER")
+        .ok()
+        .filter(|pager| !pager.trim().is_empty())
+        .unwrap_or_else(|| DEFAULT_PAGER.to_string());
+    let mut words = pager.split_whitespace();
+    let program = words.next().unwrap_or(DEFAULT_PAGER);
+    let mut child = Command::new(program)
+        .args(words)
+        .stdin(Stdio::piped())
+        .spawn()
+        .with_context(|| format!("Failed to start pager {}", pager))?;
+    if let Some(mut stdin) = child.stdin.take() {
+        // The pager closes its input when quit before the end.
+        match writeln!(stdin, "{}", text) {
+            Err(e) if e.kind() != io::ErrorKind::BrokenPipe => return Err(e.into()),
+            _ => {}
+        }
+    }
+    child.wait()?;
+    Ok(())
+}
+
+/// Prints the results, through the pager when they do not fit in the terminal.
+/// The other display settings are the ones `apply_display` set.
+pub fn print_table(df: &DataFrame, display: &Display) -> Result<()> {
+    let text = df.to_string();
+    if display.pager.unwrap_or(true) && io::stdout().is_terminal() && taller_than_terminal(&text) {
+        if let Err(e) = page(&text) {
+            warn!("{:#}, printing the results", e);
+        } else {
+            return Ok(());
+        }
+    }
+    println!("{}", text);
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use crate::commands::config::Display;
+
+    use super::{format_variables, TableStyle};
+
+    #[test]
+    fn test_format_variables() {
+        assert!(format_variables(&Display::default()).unwrap().is_empty());
+
+        let display = Display {
+            max_rows: Some(0),
+            max_col_width: Some(50),
+            table_style: Some("markdown".to_string()),
+            header: Some(false),
+            ..Default::default()
+        };
+        let variables = format_variables(&display).unwrap();
+        assert!(variables.contains(&("POLARS_FMT_MAX_ROWS", "-1".to_string())));
+        assert!(variables.contains(&("POLARS_FMT_STR_LEN", "50".to_string())));
+        assert!(variables.contains(&("POLARS_FMT_TABLE_FORMATTING", "ASCII_MARKDOWN".to_string())));
+        assert!(variables.contains(&("POLARS_FMT_TABLE_HIDE_COLUMN_NAMES", "1".to_string())));
+        assert_eq!(
+            variables
+                .iter()
+                .filter(|(name, _)| *name == "POLARS_FMT_TABLE_HIDE_COLUMN_DATA_TYPES")
+                .count(),
+            1
+        );
+
+        let invalid = Display {
+            table_style: Some("fancy".to_string()),
+            ..Default::default()
+        };
+        assert!(format_variables(&invalid).is_err());
+        assert_eq!(TableStyle::parse("ASCII").unwrap(), TableStyle::Ascii);
+    }
+}
@@ -0,0 +1,117 @@
+use anyhow::{anyhow, Result};
+use chrono::{
+    format::{Item, StrftimeItems},
+    NaiveDate,
+};
+use md5::{Digest, Md5};
+
+const DATE_FORMAT: &str = "%Y-%m-%d";
+
+/// A formatting function applied to a placeholder value, as in `{date|%Y/%m/%d}`.
+#[derive(Debug, PartialEq)]
+pub enum Format {
+    Lower,
+    Upper,
+    Year,
+    Month,
+    Day,
+    Strftime(String),
+    Pad(usize),
+    HashPrefix(usize),
+}
+
+fn parse_width(format: &str, width: &str) -> Result<usize> {
+    width
+        .trim()
+        .parse()
+        .map_err(|_| anyhow!("Format {} needs a number, got {}", format, width))
+}
+
+impl Format {
+    pub fn parse(format: &str) -> Result<Self> {
+        let format = format.trim();
+        if format.starts_with('%') {
+            if StrftimeItems::new(format).any(|item| item == Item::Error) {
+                return Err(anyhow!("Invalid date format {}", format));
+            }
+            return Ok(Format::Strftime(format.to_string()));
+        }
+        let parsed = match format.split_once(':') {
+            Some(("pad", width)) => Format::Pad(parse_width("pad", width)?),
+            Some(("hash_prefix", width)) => Format::HashPrefix(parse_width("hash_prefix", width)?),
+            None => match format {
+                "lower" => Format::Lower,
+                "upper" => Format::Upper,
+                "year" => Format::Year,
+                "month" => Format::Month,
+                "day" => Format::Day,
+                _ => return Err(anyhow!("Unknown format {}", format)),
+            },
+            Some(_) => return Err(anyhow!("Unknown format {}", format)),
+        };
+        Ok(parsed)
+    }
+
+    fn format_date(&self, value: &str, format: &str) -> Result<String> {
+        let date = NaiveDate::parse_from_str(value, DATE_FORMAT).map_err(|_| {
+            anyhow!(
+                "Format {:?} needs a date in the format YYYY-MM-DD, got {}",
+                self,
+                value
+            )
+        })?;
+        Ok(date.format(format).to_string())
+    }
+
+    pub fn apply(&self, value: &str) -> Result<String> {
+        let formatted = match self {
+            Format::Lower => value.to_lowercase(),
+            Format::Upper => value.to_uppercase(),
+            Format::Year => self.format_date(value, "%Y")?,
+            Format::Month => self.format_date(value, "%m")?,
+            Format::Day => self.format_date(value, "%d")?,
+            Format::Strftime(format) => self.format_date(value, format)?,
+            Format::Pad(width) => format!("{:0>width$}", value, width = width),
+            Format::HashPrefix(length) => {
+                let hash = format!("{:x}", Md5::digest(value.as_bytes()));
+                hash.chars().take(*length).collect()
+            }
+        };
+        Ok(formatted)
+    }
+}
+
+/// Applies the formats left to right.
+pub fn apply_formats(value: &str, formats: &[Format]) -> Result<String> {
+    formats
+        .iter()
+        .try_fold(value.to_string(), |value, format| format.apply(&value))
+}
+
+#[cfg(test)]
+mod test {
+    use super::{apply_formats, Format};
+
+    #[test]
+    fn test_parse_format() {
+        assert_eq!(Format::parse("lower").unwrap(), Format::Lower);
+        assert_eq!(Format::parse("pad:8").unwrap(), Format::Pad(8));
+        assert_eq!(
+            Format::parse("%Y/%m/%d").unwrap(),
+            Format::Strftime("%Y/%m/%d".to_string())
+        );
+        assert!(Format::parse("pad:eight").is_err());
+        assert!(Format::parse("capitalize").is_err());
+        assert!(Format::parse("%Y/%Q").is_err());
+    }
+
+    #[test]
+    fn test_apply_formats() {
+        let formats = [Format::parse("hash_prefix:2").unwrap()];
+        // md5 of 42 is a1d0c6e83f027327d8461063f4ac58a6
+        assert_eq!(apply_formats("42", &formats).unwrap(), "a1");
+        let formats = [Format::Upper, Format::Pad(6)];
+        assert_eq!(apply_formats("ab", &formats).unwrap(), "0000AB");
+        assert!(apply_formats("not-a-date", &[Format::Year]).is_err());
+    }
+}
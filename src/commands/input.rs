@@ -1,6 +1,6 @@
-use std::io;
+use std::io::{self, IsTerminal};
 
-use anyhow::Result;
+use anyhow::{anyhow, Result};
 
 pub fn request_user_input<R, W>(prompt: &str, mut reader: R, mut writer: W) -> Result<String>
 where
@@ -13,3 +13,27 @@ where
     reader.read_line(&mut input)?;
     Ok(input)
 }
+
+/// Asks a yes or no question, anything but `y` or `yes` is a no.
+pub fn confirm<R, W>(prompt: &str, reader: R, writer: W) -> Result<bool>
+where
+    R: io::BufRead,
+    W: io::Write,
+{
+    let answer = request_user_input(&format!("{} [y/N]", prompt), reader, writer)?;
+    Ok(matches!(answer.trim().to_lowercase().as_str(), "y" | "yes"))
+}
+
+/// Asks before removing `what` unless `yes` is set, refusing when stdin is not a terminal.
+pub fn confirm_removal(what: &str, yes: bool) -> Result<bool> {
+    if yes {
+        return Ok(true);
+    }
+    if !io::stdin().is_terminal() {
+        return Err(anyhow!(
+            "Refusing to remove {} without confirmation, pass --yes",
+            what
+        ));
+    }
+    confirm("Remove it?", io::stdin().lock(), io::stdout())
+}
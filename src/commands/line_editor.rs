@@ -0,0 +1,366 @@
+use std::{
+    fs::{self, OpenOptions},
+    io::{self, BufRead, IsTerminal, Write},
+    path::PathBuf,
+};
+
+use anyhow::{Context, Result};
+use crossterm::{
+    cursor::MoveToColumn,
+    event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers},
+    queue,
+    terminal::{self, Clear, ClearType},
+};
+use log::warn;
+
+const MAX_HISTORY: usize = 1000;
+
+#[derive(Debug, PartialEq)]
+enum Action {
+    Continue,
+    Submit,
+    Cancel,
+    Eof,
+    Complete,
+}
+
+/// The line being typed, `cursor` is a char index.
+#[derive(Debug, Default)]
+struct EditState {
+    line: Vec<char>,
+    cursor: usize,
+    /// Entry of the history shown, None while editing the typed line.
+    history_index: Option<usize>,
+    draft: Vec<char>,
+}
+
+/// The longest prefix shared by all the words.
+fn common_prefix(words: &[&String]) -> String {
+    let Some(first) = words.first() else {
+        return String::new();
+    };
+    let mut prefix: &str = first;
+    for word in &words[1..] {
+        let shared = prefix
+            .char_indices()
+            .zip(word.chars())
+            .find(|((_, a), b)| a != b)
+            .map(|((index, _), _)| index)
+            .unwrap_or_else(|| prefix.len().min(word.len()));
+        prefix = &prefix[..shared];
+    }
+    prefix.to_string()
+}
+
+impl EditState {
+    fn text(&self) -> String {
+        self.line.iter().collect()
+    }
+
+    fn set_line(&mut self, line: &[char]) {
+        self.line = line.to_vec();
+        self.cursor = self.line.len();
+    }
+
+    fn show_history(&mut self, history: &[String], index: Option<usize>) {
+        match index {
+            Some(index) => {
+                if self.history_index.is_none() {
+                    self.draft = self.line.clone();
+                }
+                self.set_line(&history[index].chars().collect::<Vec<_>>());
+            }
+            None => {
+                let draft = std::mem::take(&mut self.draft);
+                self.set_line(&draft);
+            }
+        }
+        self.history_index = index;
+    }
+
+    fn handle_key(&mut self, key: KeyEvent, history: &[String]) -> Action {
+        let ctrl = key.modifiers.contains(KeyModifiers::CONTROL);
+        match key.code {
+            KeyCode::Char('c') if ctrl => return Action::Cancel,
+            KeyCode::Char('d') if ctrl && self.line.is_empty() => return Action::Eof,
+            KeyCode::Char('d') if ctrl && self.cursor < self.line.len() => {
+                self.line.remove(self.cursor);
+            }
+            KeyCode::Char('a') if ctrl => self.cursor = 0,
+            KeyCode::Char('e') if ctrl => self.cursor = self.line.len(),
+            KeyCode::Char('u') if ctrl => {
+                self.line.drain(..self.cursor);
+                self.cursor = 0;
+            }
+            KeyCode::Char(_) if ctrl => {}
+            KeyCode::Char(c) => {
+                self.line.insert(self.cursor, c);
+                self.cursor += 1;
+            }
+            KeyCode::Backspace if self.cursor > 0 => {
+                self.cursor -= 1;
+                self.line.remove(self.cursor);
+            }
+            KeyCode::Delete if self.cursor < self.line.len() => {
+                self.line.remove(self.cursor);
+            }
+            KeyCode::Left => self.cursor = self.cursor.saturating_sub(1),
+            KeyCode::Right => self.cursor = (self.cursor + 1).min(self.line.len()),
+            KeyCode::Home => self.cursor = 0,
+            KeyCode::End => self.cursor = self.line.len(),
+            KeyCode::Up if !history.is_empty() => {
+                let index = match self.history_index {
+                    Some(index) => index.saturating_sub(1),
+                    None => history.len() - 1,
+                };
+                self.show_history(history, Some(index));
+            }
+            KeyCode::Down => {
+                if let Some(index) = self.history_index {
+                    let next = Some(index + 1).filter(|next| *next < history.len());
+                    self.show_history(history, next);
+                }
+            }
+            KeyCode::Tab => return Action::Complete,
+            KeyCode::Enter => return Action::Submit,
+            _ => {}
+        }
+        Action::Continue
+    }
+
+    /// Completes the word before the cursor, returning the candidates to show when
+    /// more than one matches and nothing could be added.
+    fn complete(&mut self, candidates: &[String]) -> Vec<String> {
+        let start = self.line[..self.cursor]
+            .iter()
+            .rposition(|c| c.is_whitespace())
+            .map(|index| index + 1)
+            .unwrap_or(0);
+        let word: String = self.line[start..self.cursor].iter().collect();
+        let matches: Vec<&String> = candidates
+            .iter()
+            .filter(|candidate| candidate.starts_with(&word))
+            .collect();
+        let replacement = match matches.as_slice() {
+            [] => return Vec::new(),
+            [single] => format!("{} ", single),
+            _ => {
+                let prefix = common_prefix(&matches);
+                if prefix.len() == word.len() {
+                    return matches.into_iter().cloned().collect();
+                }
+                prefix
+            }
+        };
+        self.line.splice(start..self.cursor, replacement.chars());
+        self.cursor = start + replacement.chars().count();
+        Vec::new()
+    }
+}
+
+/// Reads lines with history and tab completion when stdin is a terminal,
+/// plain lines otherwise so commands can be piped in.
+pub struct LineEditor {
+    history: Vec<String>,
+    history_path: Option<PathBuf>,
+}
+
+/// Leaves raw mode when dropped, also on errors and panics.
+pub(super) struct RawMode;
+
+impl RawMode {
+    pub(super) fn enable() -> Result<Self> {
+        terminal::enable_raw_mode().with_context(|| "Failed to enable raw mode")?;
+        Ok(RawMode)
+    }
+}
+
+impl Drop for RawMode {
+    fn drop(&mut self) {
+        let _ = terminal::disable_raw_mode();
+    }
+}
+
+fn render(out: &mut impl Write, prompt: &str, state: &EditState) -> Result<()> {
+    queue!(out, MoveToColumn(0), Clear(ClearType::CurrentLine))?;
+    write!(out, "{}{}", prompt, state.text())?;
+    let column = prompt.chars().count() + state.cursor;
+    queue!(out, MoveToColumn(column.try_into().unwrap_or(u16::MAX)))?;
+    out.flush()?;
+    Ok(())
+}
+
+impl LineEditor {
+    /// Keeps the history in `history_path`, when given.
+    pub fn new(history_path: Option<PathBuf>) -> Self {
+        let mut history: Vec<String> = history_path
+            .as_ref()
+            .and_then(|path| fs::read_to_string(path).ok())
+            .map(|content| content.lines().map(String::from).collect())
+            .unwrap_or_default();
+        history.drain(..history.len().saturating_sub(MAX_HISTORY));
+        LineEditor {
+            history,
+            history_path,
+        }
+    }
+
+    fn add_history(&mut self, line: &str) {
+        if line.trim().is_empty() || self.history.last().is_some_and(|last| last == line) {
+            return;
+        }
+        self.history.push(line.to_string());
+        if let Some(path) = &self.history_path {
+            let appended = OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(path)
+                .and_then(|mut file| writeln!(file, "{}", line));
+            if let Err(e) = appended {
+                warn!("Failed to save history in {}: {}", path.display(), e);
+                self.history_path = None;
+            }
+        }
+    }
+
+    /// The next line, None at the end of input. `complete` gets the text before the cursor
+    /// and returns the words the one being typed can complete to.
+    pub fn read_line(
+        &mut self,
+        prompt: &str,
+        complete: &dyn Fn(&str) -> Vec<String>,
+    ) -> Result<Option<String>> {
+        if !io::stdin().is_terminal() {
+            let mut line = String::new();
+            if io::stdin().lock().read_line(&mut line)? == 0 {
+                return Ok(None);
+            }
+            return Ok(Some(line.trim_end_matches(['\r', '\n']).to_string()));
+        }
+        let mut out = io::stdout();
+        let _raw_mode = RawMode::enable()?;
+        let mut state = EditState::default();
+        render(&mut out, prompt, &state)?;
+        loop {
+            let Event::Key(key) = event::read()? else {
+                continue;
+            };
+            if key.kind != KeyEventKind::Press {
+                continue;
+            }
+            match state.handle_key(key, &self.history) {
+                Action::Continue => {}
+                Action::Submit => {
+                    write!(out, "\r\n")?;
+                    let line = state.text();
+                    self.add_history(&line);
+                    return Ok(Some(line));
+                }
+                Action::Cancel => {
+                    write!(out, "^C\r\n")?;
+                    state = EditState::default();
+                }
+                Action::Eof => {
+                    write!(out, "\r\n")?;
+                    return Ok(None);
+                }
+                Action::Complete => {
+                    let before: String = state.line[..state.cursor].iter().collect();
+                    let options = state.complete(&complete(&before));
+                    if !options.is_empty() {
+                        write!(out, "\r\n{}\r\n", options.join("  "))?;
+                    }
+                }
+            }
+            render(&mut out, prompt, &state)?;
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+
+    use super::{common_prefix, Action, EditState};
+
+    fn type_keys(state: &mut EditState, keys: &[KeyCode], history: &[String]) -> Action {
+        let mut action = Action::Continue;
+        for key in keys {
+            action = state.handle_key(KeyEvent::new(*key, KeyModifiers::NONE), history);
+        }
+        action
+    }
+
+    fn type_text(state: &mut EditState, text: &str) {
+        let keys: Vec<KeyCode> = text.chars().map(KeyCode::Char).collect();
+        type_keys(state, &keys, &[]);
+    }
+
+    #[test]
+    fn test_edit_line() {
+        let mut state = EditState::default();
+        type_text(&mut state, "helo");
+        type_keys(&mut state, &[KeyCode::Left], &[]);
+        type_text(&mut state, "l");
+        assert_eq!(state.text(), "hello");
+        type_keys(
+            &mut state,
+            &[
+                KeyCode::Home,
+                KeyCode::Delete,
+                KeyCode::End,
+                KeyCode::Backspace,
+            ],
+            &[],
+        );
+        assert_eq!(state.text(), "ell");
+        let ctrl_d = KeyEvent::new(KeyCode::Char('d'), KeyModifiers::CONTROL);
+        assert_eq!(state.handle_key(ctrl_d, &[]), Action::Continue);
+        assert_eq!(
+            type_keys(&mut state, &[KeyCode::Enter], &[]),
+            Action::Submit
+        );
+        assert_eq!(EditState::default().handle_key(ctrl_d, &[]), Action::Eof);
+    }
+
+    #[test]
+    fn test_history() {
+        let history = vec!["open events".to_string(), "schema".to_string()];
+        let mut state = EditState::default();
+        type_text(&mut state, "he");
+        type_keys(&mut state, &[KeyCode::Up], &history);
+        assert_eq!(state.text(), "schema");
+        type_keys(&mut state, &[KeyCode::Up, KeyCode::Up], &history);
+        assert_eq!(state.text(), "open events");
+        type_keys(&mut state, &[KeyCode::Down], &history);
+        assert_eq!(state.text(), "schema");
+        type_keys(&mut state, &[KeyCode::Down], &history);
+        assert_eq!(state.text(), "he");
+    }
+
+    #[test]
+    fn test_complete() {
+        let columns = ["open", "opening_time", "close"].map(String::from);
+        let mut state = EditState::default();
+        type_text(&mut state, "select cl");
+        assert!(state.complete(&columns).is_empty());
+        assert_eq!(state.text(), "select close ");
+
+        type_text(&mut state, "o");
+        assert!(state.complete(&columns).is_empty());
+        assert_eq!(state.text(), "select close open");
+        assert_eq!(
+            state.complete(&columns),
+            vec!["open".to_string(), "opening_time".to_string()]
+        );
+        assert!(state.complete(&["other".to_string()]).is_empty());
+        assert_eq!(state.text(), "select close open");
+    }
+
+    #[test]
+    fn test_common_prefix() {
+        let words = ["opening".to_string(), "open".to_string()];
+        assert_eq!(common_prefix(&words.iter().collect::<Vec<_>>()), "open");
+        assert_eq!(common_prefix(&[]), "");
+    }
+}
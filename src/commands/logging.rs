@@ -0,0 +1,146 @@
+use std::{
+    fs::{File, OpenOptions},
+    io::Write,
+    path::Path,
+    sync::Mutex,
+};
+
+use anyhow::{Context, Result};
+use chrono::Local;
+use clap::ValueEnum;
+use log::{LevelFilter, Log, Metadata, Record};
+use serde_json::json;
+
+/// Crates whose log lines are kept, the ones of dependencies are left out.
+const TARGETS: [&str; 3] = ["wdapty", "file_processing", "credentials"];
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, ValueEnum)]
+pub enum LogFormat {
+    /// Timestamp, level, module and message on one line
+    #[default]
+    Text,
+    /// One JSON object per line
+    Json,
+}
+
+struct Logger {
+    level: LevelFilter,
+    format: LogFormat,
+    file: Option<Mutex<File>>,
+}
+
+fn format_line(format: LogFormat, record: &Record, timestamp: &str) -> String {
+    match format {
+        LogFormat::Text => format!(
+            "{} {:<5} {}: {}",
+            timestamp,
+            record.level(),
+            record.target(),
+            record.args()
+        ),
+        LogFormat::Json => json!({
+            "timestamp": timestamp,
+            "level": record.level().as_str(),
+            "target": record.target(),
+            "message": record.args().to_string(),
+        })
+        .to_string(),
+    }
+}
+
+impl Log for Logger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= self.level
+            && TARGETS.iter().any(|target| {
+                metadata
+                    .target()
+                    .strip_prefix(target)
+                    .is_some_and(|rest| rest.is_empty() || rest.starts_with("::"))
+            })
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+        let line = format_line(self.format, record, &Local::now().to_rfc3339());
+        match &self.file {
+            Some(file) => {
+                let _ = writeln!(file.lock().unwrap(), "{}", line);
+            }
+            None => eprintln!("{}", line),
+        }
+    }
+
+    fn flush(&self) {
+        if let Some(file) = &self.file {
+            let _ = file.lock().unwrap().flush();
+        }
+    }
+}
+
+/// Sends the log lines of wdapty and its crates up to `level` to stderr,
+/// or appends them to `log_file` when given.
+pub fn init_logging(level: LevelFilter, format: LogFormat, log_file: Option<&Path>) -> Result<()> {
+    let file = log_file
+        .map(|path| {
+            OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(path)
+                .with_context(|| format!("Failed to open log file {}", path.display()))
+        })
+        .transpose()?;
+    log::set_boxed_logger(Box::new(Logger {
+        level,
+        format,
+        file: file.map(Mutex::new),
+    }))
+    .with_context(|| "Failed to set up logging")?;
+    log::set_max_level(level);
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use log::{Level, LevelFilter, Log, Metadata, Record};
+
+    use super::{format_line, LogFormat, Logger};
+
+    #[test]
+    fn test_format_line() {
+        let args = format_args!("Collected {} rows", 3);
+        let record = Record::builder()
+            .level(Level::Info)
+            .target("file_processing::dataframe::parq")
+            .args(args)
+            .build();
+        assert_eq!(
+            format_line(LogFormat::Text, &record, "2024-02-01T10:00:00+00:00"),
+            "2024-02-01T10:00:00+00:00 INFO  file_processing::dataframe::parq: Collected 3 rows"
+        );
+        let line: serde_json::Value = serde_json::from_str(&format_line(
+            LogFormat::Json,
+            &record,
+            "2024-02-01T10:00:00+00:00",
+        ))
+        .unwrap();
+        assert_eq!(line["level"], "INFO");
+        assert_eq!(line["message"], "Collected 3 rows");
+    }
+
+    #[test]
+    fn test_enabled() {
+        let logger = Logger {
+            level: LevelFilter::Debug,
+            format: LogFormat::Text,
+            file: None,
+        };
+        let metadata = |level, target| Metadata::builder().level(level).target(target).build();
+        assert!(logger.enabled(&metadata(Level::Debug, "wdapty::commands::query")));
+        assert!(logger.enabled(&metadata(Level::Info, "credentials")));
+        assert!(!logger.enabled(&metadata(Level::Trace, "wdapty")));
+        assert!(!logger.enabled(&metadata(Level::Info, "aws_config::profile")));
+        assert!(!logger.enabled(&metadata(Level::Info, "credentials_extra")));
+    }
+}
@@ -1,59 +1,240 @@
+pub mod batch;
+pub mod catalog;
 pub mod config;
 pub mod configure;
+pub mod credentials;
+pub mod display;
+pub mod format;
 pub mod input;
+pub mod line_editor;
+pub mod logging;
+pub mod output;
 pub mod pattern;
-use anyhow::{Context, Result};
-use regex::Regex;
-use std::{collections::HashMap, io, path::PathBuf};
+pub mod placeholder;
+pub mod query;
+pub mod shell;
+pub mod stats;
+pub mod variables;
+pub mod viewer;
+use anyhow::{anyhow, Context, Result};
+use std::{
+    collections::HashMap,
+    io::{self, IsTerminal},
+    path::PathBuf,
+};
 
-use self::{config::read_config_file, pattern::handle_pattern};
+use self::{
+    format::{apply_formats, Format},
+    input::request_user_input,
+    pattern::handle_pattern,
+    placeholder::{placeholder_name, placeholder_tokens, split_formats, Placeholder},
+    variables::PatternVariables,
+};
 
 fn collect_user_input_from_string(value: &str) -> Vec<String> {
-    let re = Regex::new(r"\{(.*?)\}").unwrap();
-    let variables_to_ask = re
-        .captures_iter(value)
-        .map(|cap| cap[1].to_string())
-        .collect::<Vec<_>>();
-    variables_to_ask
+    placeholder_tokens(value)
+        .into_iter()
+        .map(|(_, token)| token.to_string())
+        .collect()
 }
 
-fn ask_user_variables_value(variables_to_ask: Vec<String>) -> HashMap<String, String> {
-    return variables_to_ask
+/// The placeholders of a pattern, the first occurrence of a name defines its type and default.
+fn collect_placeholders(value: &str) -> Result<Vec<Placeholder>> {
+    let mut placeholders: Vec<Placeholder> = Vec::new();
+    for token in collect_user_input_from_string(value) {
+        let placeholder = Placeholder::parse(&token)
+            .with_context(|| format!("Invalid placeholder {{{}}}", token))?;
+        if !placeholders
+            .iter()
+            .any(|known| known.name == placeholder.name)
+        {
+            placeholders.push(placeholder);
+        }
+    }
+    Ok(placeholders)
+}
+
+/// The placeholders of several values, a name is kept once as in `collect_placeholders`.
+fn collect_shared_placeholders(templates: &[&str]) -> Result<Vec<Placeholder>> {
+    let mut placeholders: Vec<Placeholder> = Vec::new();
+    for template in templates {
+        for placeholder in collect_placeholders(template)? {
+            if !placeholders
+                .iter()
+                .any(|known| known.name == placeholder.name)
+            {
+                placeholders.push(placeholder);
+            }
+        }
+    }
+    Ok(placeholders)
+}
+
+fn ask_user_variables_value<R, W>(
+    variables_to_ask: &[&Placeholder],
+    mut reader: R,
+    mut writer: W,
+) -> Result<HashMap<String, Vec<String>>>
+where
+    R: io::BufRead,
+    W: io::Write,
+{
+    let mut values = HashMap::new();
+    for placeholder in variables_to_ask {
+        let value = loop {
+            let input = request_user_input(&placeholder.prompt(), &mut reader, &mut writer)
+                .with_context(|| format!("Failed to read value for {}", placeholder.name))?;
+            if input.is_empty() {
+                return Err(anyhow!("No value given for {}", placeholder.name));
+            }
+            let input = match (input.trim(), &placeholder.default) {
+                ("", Some(default)) => default.as_str(),
+                (input, _) => input,
+            };
+            match placeholder.expand(input) {
+                Ok(value) => break value,
+                Err(e) => writeln!(writer, "{}", e)?,
+            }
+        };
+        values.insert(placeholder.name.to_string(), value);
+    }
+    Ok(values)
+}
+
+/// Every combination of the values of the placeholders, in the order they appear.
+fn combine_variables_values(
+    placeholders: &[Placeholder],
+    values: &HashMap<String, Vec<String>>,
+) -> Vec<HashMap<String, String>> {
+    placeholders
         .iter()
-        .map(|var_key| {
-            let mut input_string = String::new();
-            println!("Please type value for {}:", var_key);
-            io::stdin().read_line(&mut input_string).unwrap();
-            (var_key.to_string(), input_string)
+        .fold(vec![HashMap::new()], |combinations, placeholder| {
+            let options = values
+                .get(&placeholder.name)
+                .map(Vec::as_slice)
+                .unwrap_or_default();
+            combinations
+                .into_iter()
+                .flat_map(|combination| {
+                    options.iter().map(move |value| {
+                        let mut combination = combination.clone();
+                        combination.insert(placeholder.name.to_string(), value.to_string());
+                        combination
+                    })
+                })
+                .collect()
         })
-        .collect::<HashMap<String, String>>();
 }
 
+/// Fills the placeholders of a pattern with the given variables, prompting for the missing
+/// ones only when stdin is a terminal and prompts are allowed, falling back to their
+/// defaults otherwise.
+/// Values given as ranges expand the pattern into one path per value.
+fn fill_pattern_variables(pattern: &str, variables: &PatternVariables) -> Result<Vec<String>> {
+    Ok(fill_variables(&[pattern], variables)?
+        .into_iter()
+        .map(|mut filled| filled.remove(0))
+        .collect())
+}
+
+/// Fills several values sharing their placeholders, a name is asked at most once.
+/// Each combination of the range values gives one filled copy of all the values.
+fn fill_variables(templates: &[&str], variables: &PatternVariables) -> Result<Vec<Vec<String>>> {
+    let placeholders = collect_shared_placeholders(templates)?;
+    let names: Vec<String> = placeholders
+        .iter()
+        .map(|placeholder| placeholder.name.to_string())
+        .collect();
+    let (provided, missing) = variables.resolve(&names);
+    let mut values = HashMap::new();
+    for placeholder in &placeholders {
+        if let Some(value) = provided.get(&placeholder.name) {
+            values.insert(placeholder.name.to_string(), placeholder.expand(value)?);
+        }
+    }
+    let missing: Vec<&Placeholder> = placeholders
+        .iter()
+        .filter(|placeholder| missing.contains(&placeholder.name))
+        .collect();
+    if !missing.is_empty() {
+        if variables.can_prompt() && io::stdin().is_terminal() {
+            values.extend(ask_user_variables_value(
+                &missing,
+                io::stdin().lock(),
+                io::stdout(),
+            )?);
+        } else {
+            let mut unfilled = Vec::new();
+            for placeholder in missing {
+                match &placeholder.default {
+                    Some(default) => {
+                        values.insert(placeholder.name.to_string(), placeholder.expand(default)?);
+                    }
+                    None => unfilled.push(placeholder.name.as_str()),
+                }
+            }
+            if !unfilled.is_empty() {
+                return Err(anyhow!(
+                    "Missing values for pattern variables: {}. Set them with --var name=value, --vars-file or WDAPTY_VAR_<NAME>",
+                    unfilled.join(", ")
+                ));
+            }
+        }
+    }
+    combine_variables_values(&placeholders, &values)
+        .into_iter()
+        .map(|combination| {
+            templates
+                .iter()
+                .map(|template| replace_string_variables_with_value(template, combination.clone()))
+                .collect()
+        })
+        .collect()
+}
+
+/// Replaces each placeholder with its value, formatted by the formats of the placeholder.
 fn replace_string_variables_with_value(
     string_with_variables: &str,
     user_input: HashMap<String, String>,
-) -> String {
-    let mut result = string_with_variables.to_string();
-    for (key, value) in user_input {
-        result = result.replace(&format!("{{{}}}", key), value.trim());
+) -> Result<String> {
+    let mut result = String::new();
+    let mut last = 0;
+    for (range, token) in placeholder_tokens(string_with_variables) {
+        let (spec, formats) = split_formats(token);
+        if let Some(value) = user_input.get(placeholder_name(spec)) {
+            let formats = formats
+                .into_iter()
+                .map(Format::parse)
+                .collect::<Result<Vec<_>>>()?;
+            let value = apply_formats(value.trim(), &formats)
+                .with_context(|| format!("Failed to format placeholder {{{}}}", token))?;
+            result.push_str(&string_with_variables[last..range.start]);
+            result.push_str(&value);
+            last = range.end;
+        }
     }
-    result
+    result.push_str(&string_with_variables[last..]);
+    Ok(result)
 }
 
-pub fn acquire_file_name(
+pub fn acquire_file_names(
     pattern: Option<String>,
     file_name: Option<PathBuf>,
-) -> Result<PathBuf, anyhow::Error> {
-    let file_name = if let Some(pat) = pattern {
-        let file_name_from_pattern: PathBuf = handle_pattern(pat.as_str())?.into();
-        Some(file_name_from_pattern)
+    variables: &PatternVariables,
+) -> Result<Vec<PathBuf>, anyhow::Error> {
+    let file_names = if let Some(pat) = pattern {
+        let file_names_from_pattern: Vec<PathBuf> = handle_pattern(pat.as_str(), variables)?
+            .into_iter()
+            .map(PathBuf::from)
+            .collect();
+        Some(file_names_from_pattern)
     } else {
-        file_name
+        file_name.map(|file_name| vec![file_name])
     }
     .with_context(|| {
         "file name should be valued by option or by setting pattern and reading file"
     })?;
-    Ok(file_name)
+    Ok(file_names)
 }
 
 pub trait RunCommand {
@@ -61,10 +242,15 @@ pub trait RunCommand {
 }
 
 #[cfg(test)]
+#[allow(clippy::needless_borrow)]
 mod test {
     use std::collections::HashMap;
 
-    use crate::commands::{collect_user_input_from_string, replace_string_variables_with_value};
+    use crate::commands::{
+        ask_user_variables_value, collect_placeholders, collect_user_input_from_string,
+        fill_pattern_variables, fill_variables, placeholder::Placeholder,
+        replace_string_variables_with_value, variables::PatternVariables,
+    };
 
     #[test]
     fn test_collect_user_input_from_pattern() {
@@ -80,7 +266,171 @@ mod test {
         user_input.insert("test1".to_string(), "test1value".to_string());
         user_input.insert("test2".to_string(), "test2value".to_string());
         user_input.insert("test3".to_string(), "test3value".to_string());
-        let result = replace_string_variables_with_value(pattern, user_input);
+        let result = replace_string_variables_with_value(pattern, user_input).unwrap();
         assert_eq!(result, "test1value/test2value/test3value");
     }
+
+    fn fill(pattern: &str, values: &[(&str, &str)]) -> anyhow::Result<String> {
+        let user_input = values
+            .iter()
+            .map(|(key, value)| (key.to_string(), value.to_string()))
+            .collect();
+        replace_string_variables_with_value(pattern, user_input)
+    }
+
+    #[test]
+    fn test_fill_pattern_with_date_formats() {
+        let values = [("date", "2024-02-01")];
+        assert_eq!(
+            fill("events/{date|%Y/%m/%d}/part.parq", &values).unwrap(),
+            "events/2024/02/01/part.parq"
+        );
+        assert_eq!(
+            fill(
+                "year={date|year}/month={date|month}/day={date|day}",
+                &values
+            )
+            .unwrap(),
+            "year=2024/month=02/day=01"
+        );
+        assert_eq!(
+            fill("{date:date=today|%Y%m%d}/{date}", &values).unwrap(),
+            "20240201/2024-02-01"
+        );
+        assert!(fill("{date|year}", &[("date", "01/02/2024")]).is_err());
+    }
+
+    #[test]
+    fn test_fill_pattern_with_string_formats() {
+        assert_eq!(
+            fill("{account|lower}/{account|upper}", &[("account", "Main")]).unwrap(),
+            "main/MAIN"
+        );
+        assert_eq!(fill("{id|pad:8}", &[("id", "42")]).unwrap(), "00000042");
+        assert_eq!(fill("{id|pad:1}", &[("id", "42")]).unwrap(), "42");
+        assert_eq!(
+            fill("{id|hash_prefix:2}/{id}", &[("id", "42")]).unwrap(),
+            "a1/42"
+        );
+        assert_eq!(
+            fill("{id|hash_prefix:4|upper}", &[("id", "42")]).unwrap(),
+            "A1D0"
+        );
+        assert_eq!(fill("{id:regex(a|b)|upper}", &[("id", "a")]).unwrap(), "A");
+    }
+
+    #[test]
+    fn test_fill_pattern_with_invalid_format() {
+        assert!(fill("{id|pad}", &[("id", "42")]).is_err());
+        assert!(fill("{id|reverse}", &[("id", "42")]).is_err());
+        assert_eq!(
+            fill("{other|reverse}", &[("id", "42")]).unwrap(),
+            "{other|reverse}"
+        );
+    }
+
+    #[test]
+    fn test_ask_user_variables_value() {
+        let placeholders = collect_placeholders("{test1}/{env:enum(dev,prod)=dev}").unwrap();
+        let placeholders: Vec<&Placeholder> = placeholders.iter().collect();
+        let input = b"value1\ntest\n\n";
+        let mut output = Vec::new();
+        let result = ask_user_variables_value(&placeholders, &input[..], &mut output)
+            .expect("values should be read");
+        assert_eq!(result["test1"], vec!["value1"]);
+        assert_eq!(result["env"], vec!["dev"]);
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "Please type value for test1:\n\
+             Please type value for env (one of dev|prod, default dev):\n\
+             Value test for env is not one of dev, prod\n\
+             Please type value for env (one of dev|prod, default dev):\n"
+        );
+        assert!(ask_user_variables_value(&placeholders, &b""[..], &mut Vec::new()).is_err());
+    }
+
+    #[test]
+    fn test_fill_pattern_with_provided_variables() {
+        let variables = PatternVariables::new(
+            vec![
+                "test1=test1value".to_string(),
+                "test2=test2value".to_string(),
+            ],
+            None,
+        )
+        .unwrap();
+        let result = fill_pattern_variables("{test1}/{test2}/{test1}", &variables)
+            .expect("all variables are provided");
+        assert_eq!(result, vec!["test1value/test2value/test1value"]);
+    }
+
+    #[test]
+    fn test_fill_typed_pattern() {
+        let pattern = "{env:enum(dev,prod)=dev}/{hour:int(0..23)}/{date:date}/{env}";
+        let variables = PatternVariables::new(
+            vec!["hour=07".to_string(), "date=2024-02-01".to_string()],
+            None,
+        )
+        .unwrap();
+        let result = fill_pattern_variables(pattern, &variables).expect("defaults fill env");
+        assert_eq!(result, vec!["dev/07/2024-02-01/dev"]);
+
+        let variables = PatternVariables::new(
+            vec!["hour=24".to_string(), "date=2024-02-01".to_string()],
+            None,
+        )
+        .unwrap();
+        assert!(fill_pattern_variables(pattern, &variables).is_err());
+    }
+
+    #[test]
+    fn test_fill_pattern_with_ranges() {
+        let variables = PatternVariables::new(
+            vec![
+                "date=2024-02-01..2024-02-02".to_string(),
+                "hour=00..01".to_string(),
+            ],
+            None,
+        )
+        .unwrap();
+        let result = fill_pattern_variables("events/{date}/{hour:int(0..23)}.parq", &variables)
+            .expect("ranges should expand");
+        assert_eq!(
+            result,
+            vec![
+                "events/2024-02-01/00.parq",
+                "events/2024-02-01/01.parq",
+                "events/2024-02-02/00.parq",
+                "events/2024-02-02/01.parq",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_fill_variables_shared_between_values() {
+        let variables = PatternVariables::new(
+            vec![
+                "date=2024-02-01..2024-02-02".to_string(),
+                "id=7".to_string(),
+            ],
+            None,
+        )
+        .unwrap();
+        let result = fill_variables(
+            &[
+                "events/{date}.parq",
+                "{id|pad:3}",
+                "{date:date|%Y%m%d}_{id}.csv",
+            ],
+            &variables,
+        )
+        .expect("values should be filled");
+        assert_eq!(
+            result,
+            vec![
+                vec!["events/2024-02-01.parq", "007", "20240201_7.csv"],
+                vec!["events/2024-02-02.parq", "007", "20240202_7.csv"],
+            ]
+        );
+    }
 }
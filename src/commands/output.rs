@@ -0,0 +1,224 @@
+use std::{
+    io::{self, Write},
+    sync::OnceLock,
+};
+
+use anyhow::{anyhow, Result};
+use clap::ValueEnum;
+use file_processing::dataframe::file::write_csv;
+use polars::{frame::DataFrame, prelude::AnyValue};
+use serde::Serialize;
+use serde_json::{Map, Value};
+
+use super::{config::Display, display::print_table};
+
+static OUTPUT_MODE: OnceLock<OutputMode> = OnceLock::new();
+
+/// How data is printed on stdout, messages about what is going on always go to stderr.
+#[derive(Debug, Default, Clone, Copy, PartialEq, ValueEnum)]
+pub enum OutputMode {
+    /// Text and tables for people
+    #[default]
+    Table,
+    /// A JSON array, or an object for a single record
+    Json,
+    /// One JSON object per line
+    Ndjson,
+    /// Comma separated values with a header
+    Csv,
+}
+
+pub fn set_output_mode(mode: OutputMode) {
+    let _ = OUTPUT_MODE.set(mode);
+}
+
+pub fn output_mode() -> OutputMode {
+    OUTPUT_MODE.get().copied().unwrap_or_default()
+}
+
+fn to_objects<T: Serialize>(records: &[T]) -> Result<Vec<Map<String, Value>>> {
+    records
+        .iter()
+        .map(|record| match serde_json::to_value(record)? {
+            Value::Object(object) => Ok(object),
+            value => Err(anyhow!("Cannot print {} as a record", value)),
+        })
+        .collect()
+}
+
+fn csv_field(value: &Value) -> String {
+    let text = match value {
+        Value::Null => String::new(),
+        Value::String(text) => text.to_string(),
+        value => value.to_string(),
+    };
+    if text.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", text.replace('"', "\"\""))
+    } else {
+        text
+    }
+}
+
+/// Writes the records in a machine readable mode, a single record is a JSON object
+/// instead of an array. The csv header lists the fields of the first record.
+fn write_records(
+    out: &mut impl Write,
+    mode: OutputMode,
+    objects: Vec<Map<String, Value>>,
+    single: bool,
+) -> Result<()> {
+    match mode {
+        OutputMode::Table => return Err(anyhow!("Records are not printed as a table")),
+        OutputMode::Json if single && objects.len() == 1 => {
+            writeln!(out, "{}", serde_json::to_string_pretty(&objects[0])?)?
+        }
+        OutputMode::Json => writeln!(out, "{}", serde_json::to_string_pretty(&objects)?)?,
+        OutputMode::Ndjson => {
+            for object in &objects {
+                writeln!(out, "{}", serde_json::to_string(object)?)?;
+            }
+        }
+        OutputMode::Csv => {
+            let Some(first) = objects.first() else {
+                return Ok(());
+            };
+            let header: Vec<&String> = first.keys().collect();
+            let names: Vec<Value> = header
+                .iter()
+                .map(|name| Value::from(name.as_str()))
+                .collect();
+            writeln!(
+                out,
+                "{}",
+                names.iter().map(csv_field).collect::<Vec<_>>().join(",")
+            )?;
+            for object in &objects {
+                let fields: Vec<String> = header
+                    .iter()
+                    .map(|name| csv_field(object.get(*name).unwrap_or(&Value::Null)))
+                    .collect();
+                writeln!(out, "{}", fields.join(","))?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Prints the records for scripts, in table mode `table` prints them for people instead.
+pub fn print_records<T: Serialize>(records: &[T], table: impl FnOnce()) -> Result<()> {
+    match output_mode() {
+        OutputMode::Table => {
+            table();
+            Ok(())
+        }
+        mode => write_records(&mut io::stdout().lock(), mode, to_objects(records)?, false),
+    }
+}
+
+/// Like `print_records` for a single record.
+pub fn print_record<T: Serialize>(record: &T, table: impl FnOnce()) -> Result<()> {
+    match output_mode() {
+        OutputMode::Table => {
+            table();
+            Ok(())
+        }
+        mode => write_records(
+            &mut io::stdout().lock(),
+            mode,
+            to_objects(std::slice::from_ref(record))?,
+            true,
+        ),
+    }
+}
+
+/// Reports what a command did, the message in table mode and the record otherwise.
+pub fn print_status<T: Serialize>(message: &str, record: &T) -> Result<()> {
+    print_record(record, || println!("{}", message))
+}
+
+fn json_value(value: AnyValue) -> Value {
+    match value {
+        AnyValue::Null => Value::Null,
+        AnyValue::Boolean(value) => Value::Bool(value),
+        AnyValue::String(value) => Value::from(value),
+        AnyValue::Int8(value) => Value::from(value),
+        AnyValue::Int16(value) => Value::from(value),
+        AnyValue::Int32(value) => Value::from(value),
+        AnyValue::Int64(value) => Value::from(value),
+        AnyValue::UInt8(value) => Value::from(value),
+        AnyValue::UInt16(value) => Value::from(value),
+        AnyValue::UInt32(value) => Value::from(value),
+        AnyValue::UInt64(value) => Value::from(value),
+        AnyValue::Float32(value) => Value::from(value),
+        AnyValue::Float64(value) => Value::from(value),
+        value => Value::from(value.to_string()),
+    }
+}
+
+fn dataframe_objects(df: &DataFrame) -> Vec<Map<String, Value>> {
+    (0..df.height())
+        .map(|row| {
+            df.get_columns()
+                .iter()
+                .map(|series| {
+                    let value = series.get(row).map(json_value).unwrap_or(Value::Null);
+                    (series.name().to_string(), value)
+                })
+                .collect()
+        })
+        .collect()
+}
+
+/// Prints the results in the output mode, tables follow the display settings.
+pub fn print_dataframe(df: &mut DataFrame, display: &Display) -> Result<()> {
+    match output_mode() {
+        OutputMode::Table => print_table(df, display),
+        OutputMode::Csv => write_csv(df, io::stdout().lock()),
+        mode => write_records(&mut io::stdout().lock(), mode, dataframe_objects(df), false),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use polars::df;
+    use serde_json::json;
+
+    use super::{dataframe_objects, to_objects, write_records, OutputMode};
+
+    fn written(mode: OutputMode, records: &[serde_json::Value], single: bool) -> String {
+        let mut out = Vec::new();
+        write_records(&mut out, mode, to_objects(records).unwrap(), single).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn test_write_records() {
+        let records = [
+            json!({"name": "events", "value": "s3://bucket/{date}"}),
+            json!({"name": "logs", "value": "a,b \"c\""}),
+        ];
+        assert_eq!(
+            written(OutputMode::Ndjson, &records, false),
+            "{\"name\":\"events\",\"value\":\"s3://bucket/{date}\"}\n{\"name\":\"logs\",\"value\":\"a,b \\\"c\\\"\"}\n"
+        );
+        assert_eq!(
+            written(OutputMode::Csv, &records, false),
+            "name,value\nevents,s3://bucket/{date}\nlogs,\"a,b \"\"c\"\"\"\n"
+        );
+        let json: serde_json::Value =
+            serde_json::from_str(&written(OutputMode::Json, &records[..1], true)).unwrap();
+        assert_eq!(json, records[0]);
+        assert_eq!(written(OutputMode::Json, &[], false), "[]\n");
+        assert!(to_objects(&["not a record"]).is_err());
+    }
+
+    #[test]
+    fn test_dataframe_objects() {
+        let df = df!("id" => [1, 2], "name" => [Some("a"), None]).unwrap();
+        let objects = dataframe_objects(&df);
+        assert_eq!(
+            serde_json::Value::from(objects[1].clone()),
+            json!({"id": 2, "name": null})
+        );
+    }
+}
@@ -1,113 +1,259 @@
-use std::collections::HashMap;
+use std::{collections::BTreeMap, path::Path};
 
 use anyhow::{anyhow, Context, Result};
-use regex::Regex;
+use file_processing::dataframe::{file::ScanFile, parq::ParqProcessor};
+use log::debug;
+use serde_json::json;
 
 use super::{
-    ask_user_variables_value, collect_user_input_from_string,
-    config::{get_config_file, save_string_to_config, save_string_to_config_with_overwrite},
-    read_config_file, replace_string_variables_with_value,
+    catalog::load_with_catalogs,
+    collect_placeholders,
+    config::{Config, PatternConfig, PatternDefaults},
+    fill_pattern_variables,
+    input::confirm_removal,
+    output::{print_record, print_records, print_status},
+    variables::PatternVariables,
 };
 
-pub fn get_available_patterns() -> Result<HashMap<String, String>> {
-    let delimiter = '=';
-    let patterns = read_config_file()?
-        .lines()
-        .filter_map(|line| {
-            if does_pattern_match_pattern_format(line) {
-                let mut parts = line.splitn(2, delimiter);
-                Some((
-                    parts.next()?.trim().to_string(),
-                    parts.next()?.trim().to_string(),
-                ))
-            } else {
-                None
-            }
-        })
-        .collect::<HashMap<String, String>>();
-    Ok(patterns)
+pub fn get_available_patterns() -> Result<BTreeMap<String, String>> {
+    let config = load_with_catalogs(&[])?;
+    Ok(config
+        .patterns
+        .into_iter()
+        .map(|(name, pattern)| (name, pattern.value))
+        .collect())
 }
 
-pub fn add_pattern_to_config(name: String, value: String) -> Result<()> {
-    let available_patterns = get_available_patterns();
-    if let Ok(available) = available_patterns {
-        if available.get_key_value(&name).is_some() {
-            return Err(anyhow!(
-                "Pattern supplied with name {} is already in config",
-                &name
-            ));
-        }
+pub fn add_pattern_to_config(name: String, value: String, defaults: PatternDefaults) -> Result<()> {
+    let mut config = Config::load_user()?;
+    if config.patterns.contains_key(&name) {
+        return Err(anyhow!(
+            "Pattern supplied with name {} is already in config",
+            &name
+        ));
     }
-    let (_, mut file) = get_config_file(None)?;
-    save_string_to_config(format!("{}={}", name, value), &mut file)
+    config.patterns.insert(
+        name.clone(),
+        PatternConfig {
+            value: value.clone(),
+            defaults,
+        },
+    );
+    let path = config.save()?;
+    print_status(
+        &format!("Added pattern {} to {}", name, path.display()),
+        &json!({ "added": name, "value": value, "config": path }),
+    )
 }
 
-pub fn remove_pattern_from_config(name: String) -> Result<()> {
-    let content = read_config_file()?;
-    let new_file_content = content
-        .lines()
-        .filter_map(|line| {
-            if line.contains(format!("{}=", name.trim()).trim()) {
-                None
-            } else {
-                Some(line.to_string())
-            }
-        })
-        .collect::<Vec<String>>();
-    let (_, file) = get_config_file(None)?;
-    let _ = save_string_to_config_with_overwrite(new_file_content, file);
-    Ok(())
+/// Removes the pattern with exactly this name, after asking for confirmation unless `yes` is set.
+pub fn remove_pattern_from_config(name: String, yes: bool) -> Result<()> {
+    let mut config = Config::load_user()?;
+    let pattern = config
+        .patterns
+        .get(&name)
+        .ok_or_else(|| anyhow!("No pattern named {} in config", name))?;
+    eprintln!(
+        "Pattern {} with value {} will be removed",
+        name, pattern.value
+    );
+    if !confirm_removal(&format!("pattern {}", name), yes)? {
+        eprintln!("Nothing removed");
+        return Ok(());
+    }
+    config.patterns.remove(&name);
+    let path = config.save()?;
+    print_status(
+        &format!("Removed pattern {} from {}", name, path.display()),
+        &json!({ "removed": name, "config": path }),
+    )
 }
 
-pub fn handle_pattern(pattern_name: &str) -> Result<String> {
-    parse_config_file_for_pattern(pattern_name)
-        .map(|pat| {
-            let variables_to_ask = collect_user_input_from_string(&pat);
-            let user_filled_variables = ask_user_variables_value(variables_to_ask);
-            replace_string_variables_with_value(&pat, user_filled_variables)
-        })
-        .with_context(|| format!("Failed to handle pattern {}", pattern_name))
+pub fn show_pattern(name: &str) -> Result<()> {
+    let config = load_with_catalogs(&[name])?;
+    let pattern = config
+        .patterns
+        .get(name)
+        .ok_or_else(|| anyhow!("No pattern named {} in config", name))?;
+    let placeholders = collect_placeholders(&pattern.value)?;
+    let mut record = serde_json::to_value(pattern)?;
+    record["name"] = json!(name);
+    record["placeholders"] = placeholders
+        .iter()
+        .map(|placeholder| placeholder.describe())
+        .collect();
+    print_record(&record, || {
+        println!("Pattern {} has value {}", name, pattern.value);
+        if placeholders.is_empty() {
+            println!("No placeholders");
+        } else {
+            println!("Placeholders:");
+            for placeholder in &placeholders {
+                println!("  {}", placeholder.describe());
+            }
+        }
+        let defaults = &pattern.defaults;
+        if !defaults.is_empty() {
+            println!("Defaults:");
+            let listed = [
+                ("profile", defaults.profile.clone()),
+                ("execution type", defaults.execution_type.clone()),
+                ("cols", defaults.cols.as_ref().map(|cols| cols.join(" "))),
+                ("index name", defaults.index_name.clone()),
+                ("sort", defaults.sort.as_ref().map(|sort| sort.join(" "))),
+                ("output format", defaults.output_format.clone()),
+            ];
+            for (label, value) in listed {
+                if let Some(value) = value {
+                    println!("  {}: {}", label, value);
+                }
+            }
+        }
+    })
 }
 
-fn parse_config_file_for_pattern(pattern_name: &str) -> Result<String> {
-    let file = read_config_file()?;
-    let delimiter = '=';
-    let pattern = file
-        .lines()
-        .filter_map(|line| {
-            if line.contains(pattern_name) {
-                let mut line_parts = line.splitn(2, delimiter);
-                let property = line_parts.next()?.trim().to_string();
-                let value = line_parts.next()?.trim().to_string();
-                Some((property, value))
-            } else {
-                None
-            }
-        })
-        .collect::<HashMap<String, String>>();
+/// Replaces the value of a pattern and the defaults that are given, keeping the others.
+pub fn edit_pattern_in_config(
+    name: String,
+    value: Option<String>,
+    defaults: PatternDefaults,
+) -> Result<()> {
+    let mut config = Config::load_user()?;
+    let pattern = config
+        .patterns
+        .get_mut(&name)
+        .ok_or_else(|| anyhow!("No pattern named {} in config", name))?;
+    if value.is_none() && defaults.is_empty() {
+        return Err(anyhow!("Nothing to change in pattern {}", name));
+    }
+    if let Some(value) = value {
+        pattern.value = value;
+    }
+    pattern.defaults.update(defaults);
+    let value = pattern.value.clone();
+    let path = config.save()?;
+    print_status(
+        &format!("Updated pattern {} in {}", name, path.display()),
+        &json!({ "updated": name, "value": value, "config": path }),
+    )
+}
 
-    pattern
-        .get(pattern_name)
-        .map(|pat| pat.to_string())
-        .ok_or(anyhow!("No property named {} in wdapty.ini", pattern_name))
+pub fn rename_pattern_in_config(name: String, new_name: String) -> Result<()> {
+    let mut config = Config::load_user()?;
+    if config.patterns.contains_key(&new_name) {
+        return Err(anyhow!(
+            "Pattern supplied with name {} is already in config",
+            &new_name
+        ));
+    }
+    let pattern = config
+        .patterns
+        .remove(&name)
+        .ok_or_else(|| anyhow!("No pattern named {} in config", name))?;
+    config.patterns.insert(new_name.clone(), pattern);
+    let path = config.save()?;
+    print_status(
+        &format!(
+            "Renamed pattern {} to {} in {}",
+            name,
+            new_name,
+            path.display()
+        ),
+        &json!({ "renamed": name, "name": new_name, "config": path }),
+    )
 }
 
-pub fn does_pattern_match_pattern_format(pattern: &str) -> bool {
-    let pattern_format = Regex::new(r"^[A-Za-z0-9_\-.]+=([^\s\n]+)$").unwrap();
-    pattern_format.is_match(pattern)
+pub fn copy_pattern_in_config(name: String, new_name: String) -> Result<()> {
+    let mut config = Config::load_user()?;
+    if config.patterns.contains_key(&new_name) {
+        return Err(anyhow!(
+            "Pattern supplied with name {} is already in config",
+            &new_name
+        ));
+    }
+    let pattern = config
+        .patterns
+        .get(&name)
+        .cloned()
+        .ok_or_else(|| anyhow!("No pattern named {} in config", name))?;
+    config.patterns.insert(new_name.clone(), pattern);
+    let path = config.save()?;
+    print_status(
+        &format!(
+            "Copied pattern {} to {} in {}",
+            name,
+            new_name,
+            path.display()
+        ),
+        &json!({ "copied": name, "name": new_name, "config": path }),
+    )
 }
 
-#[test]
-fn test_does_pattern_match_pattern_format() {
-    let pattern = "amazingtestname=s3://somethingsomthineasdas//asas//assa/{asdasd}/{asdas}";
-    assert!(does_pattern_match_pattern_format(pattern));
+fn check_path_exists(path: &str, profile: Option<&str>) -> Result<()> {
+    if path.starts_with("s3://") {
+        ParqProcessor::new(None, None, None, vec![path.into()], profile, None)
+            .scan()
+            .map(|_| ())
+    } else if Path::new(shellexpand::tilde(path).as_ref()).exists() {
+        Ok(())
+    } else {
+        Err(anyhow!("File does not exist"))
+    }
+}
 
-    let pattern = "amazingtestname=s3://somethingsomthin easdas//asas//assa/{asdasd}/{asdas}";
-    assert!(does_pattern_match_pattern_format(pattern) == false);
+/// Prints the paths the pattern resolves to, failing when any of them does not exist.
+pub fn test_pattern(name: &str, variables: &PatternVariables, profile: Option<&str>) -> Result<()> {
+    let paths = handle_pattern(name, variables)?;
+    let checks: Vec<_> = paths
+        .iter()
+        .map(|path| (path, check_path_exists(path, profile)))
+        .collect();
+    let records: Vec<_> = checks
+        .iter()
+        .map(|(path, checked)| {
+            json!({
+                "path": path,
+                "exists": checked.is_ok(),
+                "error": checked.as_ref().err().map(|e| e.to_string()),
+            })
+        })
+        .collect();
+    print_records(&records, || {
+        for (path, checked) in &checks {
+            match checked {
+                Ok(()) => println!("{} exists", path),
+                Err(e) => println!("{} not found: {}", path, e),
+            }
+        }
+    })?;
+    let missing = checks
+        .iter()
+        .filter(|(_, checked)| checked.is_err())
+        .count();
+    if missing > 0 {
+        return Err(anyhow!(
+            "{} of {} paths for pattern {} do not exist",
+            missing,
+            paths.len(),
+            name
+        ));
+    }
+    Ok(())
+}
 
-    let pattern = "amazingtestname={PATH}/test.parq";
-    assert!(does_pattern_match_pattern_format(pattern));
+pub fn handle_pattern(pattern_name: &str, variables: &PatternVariables) -> Result<Vec<String>> {
+    let paths = parse_config_file_for_pattern(pattern_name)
+        .and_then(|pat| fill_pattern_variables(&pat, variables))
+        .with_context(|| format!("Failed to handle pattern {}", pattern_name))?;
+    debug!("Pattern {} resolves to {}", pattern_name, paths.join(", "));
+    Ok(paths)
+}
 
-    let pattern = "amazing-test-name={PATH}/test.parq";
-    assert!(does_pattern_match_pattern_format(pattern));
+fn parse_config_file_for_pattern(pattern_name: &str) -> Result<String> {
+    let config = load_with_catalogs(&[pattern_name])?;
+    config
+        .patterns
+        .get(pattern_name)
+        .map(|pattern| pattern.value.to_string())
+        .ok_or(anyhow!("No pattern named {} in config", pattern_name))
 }
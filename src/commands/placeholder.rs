@@ -0,0 +1,498 @@
+use std::ops::Range;
+
+use anyhow::{anyhow, Context, Result};
+use chrono::{Duration, Local, NaiveDate};
+use regex::Regex;
+
+use super::format::Format;
+
+const DATE_FORMAT: &str = "%Y-%m-%d";
+const MAX_RANGE_VALUES: usize = 10_000;
+
+/// Finds the `{...}` placeholders of a pattern, with their position and inner text.
+/// Braces nest, so `{id:regex([0-9a-f]{8})}` is a single placeholder.
+pub fn placeholder_tokens(value: &str) -> Vec<(Range<usize>, &str)> {
+    let mut tokens = Vec::new();
+    let mut start = None;
+    let mut depth = 0;
+    for (index, c) in value.char_indices() {
+        match c {
+            '{' => {
+                if depth == 0 {
+                    start = Some(index);
+                }
+                depth += 1;
+            }
+            '}' if depth > 0 => {
+                depth -= 1;
+                if depth == 0 {
+                    if let Some(start) = start.take() {
+                        tokens.push((start..index + 1, &value[start + 1..index]));
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+    tokens
+}
+
+#[derive(Debug)]
+pub enum PlaceholderKind {
+    Text,
+    Date,
+    Int { min: Option<i64>, max: Option<i64> },
+    Enum(Vec<String>),
+    Regex(Regex),
+}
+
+/// A pattern placeholder in the form `{name[:type][=default][|format...]}`,
+/// e.g. `{env:enum(dev,staging,prod)=dev}`, `{hour:int(0..23)}` or `{date:date|%Y/%m/%d}`.
+/// Formats only change how a value is written, so they are kept out of the placeholder.
+#[derive(Debug)]
+pub struct Placeholder {
+    pub name: String,
+    pub kind: PlaceholderKind,
+    pub default: Option<String>,
+}
+
+/// The name of a placeholder without its type, default and formats.
+pub fn placeholder_name(token: &str) -> &str {
+    token
+        .split([':', '=', '|'])
+        .next()
+        .unwrap_or_default()
+        .trim()
+}
+
+/// Splits the formats off a placeholder, `date:date|year|pad:4` gives `date:date`
+/// and `["year", "pad:4"]`. A `|` inside a type like `regex(a|b)` is not a separator.
+pub fn split_formats(token: &str) -> (&str, Vec<&str>) {
+    let mut depth = 0;
+    for (index, c) in token.char_indices() {
+        match c {
+            '(' => depth += 1,
+            ')' => depth -= 1,
+            '|' if depth == 0 => {
+                return (&token[..index], token[index + 1..].split('|').collect());
+            }
+            _ => {}
+        }
+    }
+    (token, Vec::new())
+}
+
+/// Splits `ident(args)rest` keeping nested parentheses inside the args.
+fn split_type(spec: &str) -> Result<(&str, Option<&str>, &str)> {
+    let ident_end = spec
+        .find(|c: char| !c.is_ascii_alphanumeric())
+        .unwrap_or(spec.len());
+    let (ident, rest) = spec.split_at(ident_end);
+    if !rest.starts_with('(') {
+        return Ok((ident, None, rest));
+    }
+    let mut depth = 0;
+    for (index, c) in rest.char_indices() {
+        match c {
+            '(' => depth += 1,
+            ')' => {
+                depth -= 1;
+                if depth == 0 {
+                    return Ok((ident, Some(&rest[1..index]), &rest[index + 1..]));
+                }
+            }
+            _ => {}
+        }
+    }
+    Err(anyhow!("Unbalanced parentheses in type {}", spec))
+}
+
+fn parse_date(value: &str) -> Option<NaiveDate> {
+    let today = Local::now().date_naive();
+    match value {
+        "today" => Some(today),
+        "yesterday" => Some(today - Duration::days(1)),
+        _ => NaiveDate::parse_from_str(value, DATE_FORMAT).ok(),
+    }
+}
+
+/// Expands `start..end` into every date or integer in between, ends included.
+/// Integers keep the zero padding of the start, `00..23` gives `00`, `01`, ... `23`.
+/// Returns None when the ends are neither dates nor integers.
+fn expand_range(start: &str, end: &str) -> Option<Result<Vec<String>>> {
+    let (start, end) = (start.trim(), end.trim());
+    let values = if let (Some(start), Some(end)) = (parse_date(start), parse_date(end)) {
+        let days = (end - start).num_days();
+        if days < 0 {
+            return Some(Err(anyhow!(
+                "Range {}..{} ends before it starts",
+                start,
+                end
+            )));
+        }
+        (0..=days)
+            .take(MAX_RANGE_VALUES + 1)
+            .map(|day| {
+                (start + Duration::days(day))
+                    .format(DATE_FORMAT)
+                    .to_string()
+            })
+            .collect::<Vec<_>>()
+    } else if let (Ok(first), Ok(last)) = (start.parse::<i64>(), end.parse::<i64>()) {
+        if last < first {
+            return Some(Err(anyhow!(
+                "Range {}..{} ends before it starts",
+                start,
+                end
+            )));
+        }
+        let width = if start.starts_with('0') {
+            start.len()
+        } else {
+            0
+        };
+        (first..=last)
+            .take(MAX_RANGE_VALUES + 1)
+            .map(|number| format!("{:0width$}", number, width = width))
+            .collect::<Vec<_>>()
+    } else {
+        return None;
+    };
+    if values.len() > MAX_RANGE_VALUES {
+        return Some(Err(anyhow!(
+            "Range {}..{} has more than {} values",
+            start,
+            end,
+            MAX_RANGE_VALUES
+        )));
+    }
+    Some(Ok(values))
+}
+
+fn parse_bound(bound: &str) -> Result<Option<i64>> {
+    let bound = bound.trim();
+    if bound.is_empty() {
+        return Ok(None);
+    }
+    bound
+        .parse()
+        .map(Some)
+        .map_err(|_| anyhow!("Invalid integer bound {}", bound))
+}
+
+impl PlaceholderKind {
+    fn parse(ident: &str, args: Option<&str>) -> Result<Self> {
+        let kind = match (ident, args) {
+            ("string", None) => PlaceholderKind::Text,
+            ("date", None) => PlaceholderKind::Date,
+            ("int", None) => PlaceholderKind::Int {
+                min: None,
+                max: None,
+            },
+            ("int", Some(range)) => {
+                let (min, max) = range
+                    .split_once("..")
+                    .ok_or_else(|| anyhow!("Integer range {} should be min..max", range))?;
+                PlaceholderKind::Int {
+                    min: parse_bound(min)?,
+                    max: parse_bound(max)?,
+                }
+            }
+            ("enum", Some(choices)) => PlaceholderKind::Enum(
+                choices
+                    .split(',')
+                    .map(|choice| choice.trim().to_string())
+                    .filter(|choice| !choice.is_empty())
+                    .collect(),
+            ),
+            ("regex", Some(regex)) => PlaceholderKind::Regex(
+                Regex::new(&format!("^(?:{})$", regex))
+                    .map_err(|e| anyhow!("Invalid regex {}: {}", regex, e))?,
+            ),
+            (ident, _) => return Err(anyhow!("Unknown placeholder type {}", ident)),
+        };
+        Ok(kind)
+    }
+}
+
+impl Placeholder {
+    pub fn parse(token: &str) -> Result<Self> {
+        let (token, formats) = split_formats(token);
+        for format in formats {
+            Format::parse(format)
+                .with_context(|| format!("Invalid format in placeholder {{{}}}", token))?;
+        }
+        let name = placeholder_name(token);
+        if name.is_empty() {
+            return Err(anyhow!("Placeholder {{{}}} has no name", token));
+        }
+        let rest = token.trim()[name.len()..].trim_start();
+        let (kind, rest) = match rest.strip_prefix(':') {
+            Some(spec) => {
+                let (ident, args, rest) = split_type(spec.trim_start())?;
+                (PlaceholderKind::parse(ident, args)?, rest)
+            }
+            None => (PlaceholderKind::Text, rest),
+        };
+        let default = match rest.strip_prefix('=') {
+            Some(default) => Some(default.to_string()),
+            None if rest.trim().is_empty() => None,
+            None => return Err(anyhow!("Unexpected {} in placeholder {{{}}}", rest, token)),
+        };
+        let placeholder = Placeholder {
+            name: name.to_string(),
+            kind,
+            default,
+        };
+        if let Some(default) = &placeholder.default {
+            placeholder
+                .validate(default)
+                .map_err(|e| anyhow!("Invalid default in placeholder {{{}}}: {}", token, e))?;
+        }
+        Ok(placeholder)
+    }
+
+    /// Checks the value against the placeholder type, returning the value to put in the path.
+    pub fn validate(&self, value: &str) -> Result<String> {
+        let value = value.trim();
+        match &self.kind {
+            PlaceholderKind::Text => Ok(value.to_string()),
+            PlaceholderKind::Date => {
+                let date = parse_date(value).ok_or_else(|| {
+                    anyhow!(
+                        "Value {} for {} is not a date in the format YYYY-MM-DD",
+                        value,
+                        self.name
+                    )
+                })?;
+                Ok(date.format(DATE_FORMAT).to_string())
+            }
+            PlaceholderKind::Int { min, max } => {
+                let number: i64 = value
+                    .parse()
+                    .map_err(|_| anyhow!("Value {} for {} is not an integer", value, self.name))?;
+                if min.is_some_and(|min| number < min) || max.is_some_and(|max| number > max) {
+                    return Err(anyhow!(
+                        "Value {} for {} is not in the range {}",
+                        value,
+                        self.name,
+                        self.range_hint()
+                    ));
+                }
+                Ok(value.to_string())
+            }
+            PlaceholderKind::Enum(choices) => {
+                if choices.iter().any(|choice| choice == value) {
+                    Ok(value.to_string())
+                } else {
+                    Err(anyhow!(
+                        "Value {} for {} is not one of {}",
+                        value,
+                        self.name,
+                        choices.join(", ")
+                    ))
+                }
+            }
+            PlaceholderKind::Regex(regex) => {
+                if regex.is_match(value) {
+                    Ok(value.to_string())
+                } else {
+                    Err(anyhow!(
+                        "Value {} for {} does not match {}",
+                        value,
+                        self.name,
+                        regex.as_str()
+                    ))
+                }
+            }
+        }
+    }
+
+    /// Validates the value, expanding date and integer ranges like `2024-02-01..2024-02-07`
+    /// into one value per day or number.
+    pub fn expand(&self, value: &str) -> Result<Vec<String>> {
+        let range = match self.kind {
+            PlaceholderKind::Text | PlaceholderKind::Date | PlaceholderKind::Int { .. } => {
+                value.split_once("..")
+            }
+            PlaceholderKind::Enum(_) | PlaceholderKind::Regex(_) => None,
+        };
+        match range.and_then(|(start, end)| expand_range(start, end)) {
+            Some(values) => values?.iter().map(|value| self.validate(value)).collect(),
+            None => Ok(vec![self.validate(value)?]),
+        }
+    }
+
+    fn range_hint(&self) -> String {
+        match &self.kind {
+            PlaceholderKind::Int { min, max } => format!(
+                "{}..{}",
+                min.map(|min| min.to_string()).unwrap_or_default(),
+                max.map(|max| max.to_string()).unwrap_or_default()
+            ),
+            _ => String::new(),
+        }
+    }
+
+    /// The name with the expected type, choices and default, as in `env (one of dev|prod, default dev)`.
+    pub fn describe(&self) -> String {
+        let mut hints = Vec::new();
+        match &self.kind {
+            PlaceholderKind::Text => {}
+            PlaceholderKind::Date => hints.push("date YYYY-MM-DD".to_string()),
+            PlaceholderKind::Int {
+                min: None,
+                max: None,
+            } => hints.push("integer".to_string()),
+            PlaceholderKind::Int { .. } => hints.push(format!("integer {}", self.range_hint())),
+            PlaceholderKind::Enum(choices) => hints.push(format!("one of {}", choices.join("|"))),
+            PlaceholderKind::Regex(regex) => hints.push(format!("matching {}", regex.as_str())),
+        }
+        if let Some(default) = &self.default {
+            hints.push(format!("default {}", default));
+        }
+        if hints.is_empty() {
+            self.name.to_string()
+        } else {
+            format!("{} ({})", self.name, hints.join(", "))
+        }
+    }
+
+    /// The interactive prompt, showing the expected type, choices and default.
+    pub fn prompt(&self) -> String {
+        format!("Please type value for {}:", self.describe())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use chrono::Local;
+
+    use super::{placeholder_tokens, split_formats, Placeholder, PlaceholderKind};
+
+    #[test]
+    fn test_placeholder_tokens() {
+        let tokens = placeholder_tokens("s3://bucket/{date:date=today}/{id:regex([0-9a-f]{8})}/x");
+        let inner: Vec<&str> = tokens.iter().map(|(_, token)| *token).collect();
+        assert_eq!(inner, vec!["date:date=today", "id:regex([0-9a-f]{8})"]);
+        assert_eq!(tokens[0].0, 12..29);
+    }
+
+    #[test]
+    fn test_parse_placeholder() {
+        let placeholder = Placeholder::parse("env:enum(dev,staging,prod)=dev").unwrap();
+        assert_eq!(placeholder.name, "env");
+        assert_eq!(placeholder.default.as_deref(), Some("dev"));
+        assert!(
+            matches!(placeholder.kind, PlaceholderKind::Enum(ref choices) if choices.len() == 3)
+        );
+
+        let placeholder = Placeholder::parse("account=main").unwrap();
+        assert!(matches!(placeholder.kind, PlaceholderKind::Text));
+        assert_eq!(placeholder.default.as_deref(), Some("main"));
+
+        let placeholder = Placeholder::parse("hour:int(0..23)").unwrap();
+        assert!(matches!(
+            placeholder.kind,
+            PlaceholderKind::Int {
+                min: Some(0),
+                max: Some(23)
+            }
+        ));
+
+        assert!(Placeholder::parse("x:float").is_err());
+        assert!(Placeholder::parse("env:enum(dev,prod)=test").is_err());
+        assert!(Placeholder::parse(":date").is_err());
+
+        let placeholder = Placeholder::parse("date:date=today|%Y/%m|pad:8").unwrap();
+        assert_eq!(placeholder.name, "date");
+        assert_eq!(placeholder.default.as_deref(), Some("today"));
+        assert!(Placeholder::parse("date|unknown").is_err());
+    }
+
+    #[test]
+    fn test_split_formats() {
+        assert_eq!(
+            split_formats("date:date|year|pad:4"),
+            ("date:date", vec!["year", "pad:4"])
+        );
+        assert_eq!(
+            split_formats("id:regex(a|b)|upper"),
+            ("id:regex(a|b)", vec!["upper"])
+        );
+        assert_eq!(split_formats("id"), ("id", vec![]));
+    }
+
+    #[test]
+    fn test_validate_placeholder() {
+        let date = Placeholder::parse("date:date=today").unwrap();
+        assert_eq!(date.validate("2024-02-01").unwrap(), "2024-02-01");
+        assert_eq!(
+            date.validate("today").unwrap(),
+            Local::now().date_naive().format("%Y-%m-%d").to_string()
+        );
+        assert!(date.validate("01/02/2024").is_err());
+
+        let hour = Placeholder::parse("hour:int(0..23)").unwrap();
+        assert_eq!(hour.validate("05").unwrap(), "05");
+        assert!(hour
+            .validate("24")
+            .unwrap_err()
+            .to_string()
+            .contains("not in the range 0..23"));
+
+        let id = Placeholder::parse("id:regex([0-9a-f]{8})").unwrap();
+        assert!(id.validate("deadbeef").is_ok());
+        assert!(id.validate("deadbeef0").is_err());
+
+        let env = Placeholder::parse("env:enum(dev,staging,prod)").unwrap();
+        assert!(env.validate("staging").is_ok());
+        assert!(env.validate("test").is_err());
+    }
+
+    #[test]
+    fn test_expand_placeholder() {
+        let date = Placeholder::parse("date").unwrap();
+        assert_eq!(
+            date.expand("2024-02-27..2024-03-02").unwrap(),
+            vec![
+                "2024-02-27",
+                "2024-02-28",
+                "2024-02-29",
+                "2024-03-01",
+                "2024-03-02"
+            ]
+        );
+        assert!(date.expand("2024-02-07..2024-02-01").is_err());
+        assert_eq!(date.expand("a..b").unwrap(), vec!["a..b"]);
+
+        let hour = Placeholder::parse("hour:int(0..23)").unwrap();
+        let hours = hour.expand("00..23").unwrap();
+        assert_eq!(hours.len(), 24);
+        assert_eq!(hours[0], "00");
+        assert_eq!(hours[23], "23");
+        assert!(hour.expand("20..25").is_err());
+        assert_eq!(hour.expand("7").unwrap(), vec!["7"]);
+
+        let env = Placeholder::parse("env:enum(a..b,c)").unwrap();
+        assert_eq!(env.expand("a..b").unwrap(), vec!["a..b"]);
+    }
+
+    #[test]
+    fn test_placeholder_prompt() {
+        assert_eq!(
+            Placeholder::parse("name").unwrap().prompt(),
+            "Please type value for name:"
+        );
+        assert_eq!(
+            Placeholder::parse("env:enum(dev,staging,prod)=dev")
+                .unwrap()
+                .prompt(),
+            "Please type value for env (one of dev|staging|prod, default dev):"
+        );
+        assert_eq!(
+            Placeholder::parse("hour:int(0..23)").unwrap().prompt(),
+            "Please type value for hour (integer 0..23):"
+        );
+    }
+}
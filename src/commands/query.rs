@@ -0,0 +1,547 @@
+use std::path::PathBuf;
+
+use anyhow::{anyhow, Context, Result};
+use credentials::chain::CredentialsChain;
+use file_processing::{
+    dataframe::{
+        expressions::get_index_expr_if_needed,
+        file::OutputFormat,
+        operations::{Aggregation, Filter},
+        parq::ParqProcessor,
+        plan::{scan_pushdowns, ScanPushdown},
+        processor::Runnable,
+    },
+    Processors,
+};
+use log::debug;
+use polars::frame::DataFrame;
+use serde_json::json;
+
+use super::{
+    catalog::{load_with_catalogs, merge_catalogs},
+    collect_shared_placeholders,
+    config::{Config, Display, PatternConfig, PatternDefaults, QueryConfig},
+    fill_variables,
+    input::confirm_removal,
+    output::{output_mode, print_dataframe, print_record, print_records, print_status},
+    stats::{print_stats, StatsFormat},
+    variables::PatternVariables,
+    viewer::view_dataframe,
+};
+
+/// What running a query does.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub enum QueryAction {
+    /// Reads the files and prints or writes the results.
+    #[default]
+    Run,
+    /// Prints the optimised plan, only the schemas of the files are read.
+    Explain,
+    /// Prints what would be read and where the results would go, nothing is read.
+    DryRun,
+}
+
+/// A search, or a download when there is no index value, over files already resolved.
+#[derive(Debug, Default)]
+pub struct Query {
+    pub file_names: Vec<PathBuf>,
+    pub index_name: Option<String>,
+    pub index_value: Option<String>,
+    /// Filters like `amount > 100`, every one of them has to match.
+    pub filters: Vec<String>,
+    /// Columns the aggregations are grouped by.
+    pub group_by: Vec<String>,
+    /// Aggregations like `sum(amount)`, they replace the columns.
+    pub aggregations: Vec<String>,
+    pub limit: Option<u32>,
+    pub output_file: Option<String>,
+    /// Profile, execution type, columns, sort and output format, `index_name` is not used.
+    pub defaults: PatternDefaults,
+    /// Opens the results in the table viewer instead of printing them.
+    pub view: bool,
+    /// Display settings layered over the ones of the config.
+    pub display: Display,
+    /// Prints neither the results nor where they were written, for jobs running side by side.
+    pub quiet: bool,
+    pub action: QueryAction,
+    /// Prints what was read and how long each step took once the query ran.
+    pub stats: Option<StatsFormat>,
+}
+
+impl Query {
+    /// Runs the query, the config fills in the profile and execution type left unset
+    /// and places relative output files.
+    pub fn run(self, config: &Config) -> Result<DataFrame> {
+        let PatternDefaults {
+            profile,
+            execution_type,
+            cols,
+            sort,
+            output_format,
+            ..
+        } = self.defaults;
+        let profile = config.resolve_profile(profile);
+        let execution_type = execution_type
+            .or_else(|| config.defaults.execution_type.clone())
+            .unwrap_or_else(|| "parq".to_string());
+        let output_format = output_format
+            .map(|output_format| OutputFormat::parse(&output_format))
+            .transpose()?
+            .unwrap_or_default();
+        let output_file = self
+            .output_file
+            .map(|output_file| config.output.resolve(output_file));
+        let mut display = config.output.display.clone();
+        display.update(self.display);
+
+        debug!(
+            "Running {} over {} with profile {}",
+            execution_type,
+            self.file_names
+                .iter()
+                .map(|file_name| file_name.display().to_string())
+                .collect::<Vec<_>>()
+                .join(", "),
+            profile.as_deref().unwrap_or("default")
+        );
+        if self.action == QueryAction::DryRun {
+            let output = match &output_file {
+                Some(output_file) => {
+                    format!(
+                        "{} as {}",
+                        output_file,
+                        format!("{:?}", output_format).to_lowercase()
+                    )
+                }
+                None if self.view => "the table viewer".to_string(),
+                None => format!(
+                    "stdout as {}",
+                    format!("{:?}", output_mode()).to_lowercase()
+                ),
+            };
+            print_dry_run(DryRun {
+                execution_type: &execution_type,
+                file_names: &self.file_names,
+                profile: profile.as_deref(),
+                index_name: self.index_name.as_deref(),
+                index_value: self.index_value.as_deref(),
+                filters: &self.filters,
+                group_by: &self.group_by,
+                aggregations: &self.aggregations,
+                cols: cols.as_deref(),
+                sort: sort.as_deref(),
+                limit: self.limit,
+                output,
+            })?;
+            return Ok(DataFrame::default());
+        }
+        let processor = match execution_type.as_str() {
+            "parq" => Processors::Parq(
+                ParqProcessor::new(
+                    self.index_name,
+                    self.index_value,
+                    cols,
+                    self.file_names,
+                    profile.as_deref(),
+                    output_file.clone(),
+                )
+                .with_sort(sort)
+                .with_filters(self.filters)
+                .with_aggregations(self.group_by, self.aggregations)
+                .with_limit(self.limit)
+                .with_output_format(output_format)
+                .with_print_results(false),
+            ),
+            _ => return Err(anyhow!("Invalid Execution type")),
+        };
+        if self.action == QueryAction::Explain {
+            print_explain(&processor.explain()?)?;
+            return Ok(DataFrame::default());
+        }
+        let (mut df, stats) = match self.stats {
+            Some(format) => {
+                let (df, stats) = processor.run_with_stats()?;
+                (df, Some((stats, format)))
+            }
+            None => (processor.run()?, None),
+        };
+        if self.quiet {
+            return Ok(df);
+        }
+        if let Some(output_file) = &output_file {
+            print_status(
+                &format!("Results are available in {}", output_file),
+                &json!({ "output_file": output_file, "rows": df.height() }),
+            )?;
+        }
+        if self.view {
+            view_dataframe(df.clone())?;
+        } else if output_file.is_none() {
+            print_dataframe(&mut df, &display)?;
+        }
+        if let Some((stats, format)) = stats {
+            print_stats(&stats, format);
+        }
+        Ok(df)
+    }
+}
+
+fn describe_scan(scan: &ScanPushdown) -> String {
+    let columns = match scan.projection.as_deref() {
+        Some(projection) if !projection.starts_with('*') => match projection.split_once('/') {
+            Some((read, total)) => format!("{} of {} columns", read, total),
+            None => format!("{} columns", projection),
+        },
+        _ => "all columns".to_string(),
+    };
+    match &scan.predicate {
+        Some(predicate) => format!("reads {} of the rows matching {}", columns, predicate),
+        None => format!("reads {} of every row", columns),
+    }
+}
+
+fn print_explain(plan: &str) -> Result<()> {
+    let scans = scan_pushdowns(plan);
+    let records: Vec<_> = scans
+        .iter()
+        .map(|scan| {
+            json!({
+                "source": scan.source,
+                "projection": scan.projection,
+                "predicate": scan.predicate,
+            })
+        })
+        .collect();
+    print_record(&json!({ "plan": plan, "scans": records }), || {
+        println!("{}", plan);
+        println!("Pushed down into the parquet scans:");
+        for scan in &scans {
+            println!("  {} {}", scan.source, describe_scan(scan));
+        }
+    })
+}
+
+/// Everything a query would use, resolved without reading any file.
+struct DryRun<'a> {
+    execution_type: &'a str,
+    file_names: &'a [PathBuf],
+    profile: Option<&'a str>,
+    index_name: Option<&'a str>,
+    index_value: Option<&'a str>,
+    filters: &'a [String],
+    group_by: &'a [String],
+    aggregations: &'a [String],
+    cols: Option<&'a [String]>,
+    sort: Option<&'a [String]>,
+    limit: Option<u32>,
+    output: String,
+}
+
+/// Which link of the chain would supply the credentials of the profile and what it would
+/// run or call, without running nor calling it.
+fn describe_credentials(profile: Option<&str>) -> String {
+    let chain = CredentialsChain::aws_default(profile, None);
+    match chain.describe() {
+        Ok(Some((source, description))) => format!("from {}, {}", source, description),
+        Ok(None) => format!("not found, tried {}", chain.link_names().join(", ")),
+        Err(e) => format!("not found, {:#}", e),
+    }
+}
+
+fn print_dry_run(dry_run: DryRun) -> Result<()> {
+    let filter = match (dry_run.index_name, dry_run.index_value) {
+        (Some(index_name), Some(index_value)) => {
+            // Fails on the index values the search would reject.
+            let _ = get_index_expr_if_needed(index_name, index_value)?;
+            Some(format!("{} = {}", index_name, index_value))
+        }
+        _ => None,
+    };
+    for filter in dry_run.filters {
+        Filter::parse(filter)?;
+    }
+    for aggregation in dry_run.aggregations {
+        Aggregation::parse(aggregation)?;
+    }
+    let filter = filter
+        .into_iter()
+        .chain(dry_run.filters.iter().cloned())
+        .reduce(|all, filter| format!("{} and {}", all, filter));
+    let files: Vec<String> = dry_run
+        .file_names
+        .iter()
+        .map(|file_name| file_name.display().to_string())
+        .collect();
+    let profile = dry_run.profile.unwrap_or("default");
+    let credentials = files
+        .iter()
+        .any(|file| file.starts_with("s3://"))
+        .then(|| describe_credentials(dry_run.profile));
+    let record = json!({
+        "execution_type": dry_run.execution_type,
+        "files": files,
+        "profile": profile,
+        "credentials": credentials,
+        "filter": filter,
+        "group_by": dry_run.group_by,
+        "aggregations": dry_run.aggregations,
+        "cols": dry_run.cols,
+        "sort": dry_run.sort,
+        "limit": dry_run.limit,
+        "output": dry_run.output,
+    });
+    print_record(&record, || {
+        println!("Dry run with {}, no rows read", dry_run.execution_type);
+        println!("Files:");
+        for file in &files {
+            println!("  {}", file);
+        }
+        println!("Profile: {}", profile);
+        if let Some(credentials) = &credentials {
+            println!("Credentials: {}", credentials);
+        }
+        println!("Filter: {}", filter.as_deref().unwrap_or("none, every row"));
+        if dry_run.aggregations.is_empty() {
+            println!(
+                "Columns: {}",
+                dry_run
+                    .cols
+                    .map_or("all".to_string(), |cols| cols.join(", "))
+            );
+        } else if dry_run.group_by.is_empty() {
+            println!("Aggregate: {}", dry_run.aggregations.join(", "));
+        } else {
+            println!(
+                "Aggregate: {} by {}",
+                dry_run.aggregations.join(", "),
+                dry_run.group_by.join(", ")
+            );
+        }
+        if let Some(sort) = dry_run.sort {
+            println!("Sort: {}", sort.join(", "));
+        }
+        if let Some(limit) = dry_run.limit {
+            println!("Limit: {}", limit);
+        }
+        println!("Output: {}", dry_run.output);
+    })
+}
+
+pub fn get_query<'a>(config: &'a Config, name: &str) -> Result<&'a QueryConfig> {
+    config
+        .queries
+        .get(name)
+        .ok_or_else(|| anyhow!("No query named {} in config", name))
+}
+
+/// Fills the placeholders the query shares with the pattern value,
+/// `overrides` win over the defaults of the query, which win over the ones of the pattern.
+pub fn resolve_query(
+    name: &str,
+    query: &QueryConfig,
+    pattern: &PatternConfig,
+    variables: &PatternVariables,
+    overrides: PatternDefaults,
+) -> Result<Query> {
+    let mut templates = vec![
+        pattern.value.as_str(),
+        query.index_value.as_deref().unwrap_or_default(),
+        query.output_file.as_deref().unwrap_or_default(),
+    ];
+    templates.extend(query.filters.iter().map(String::as_str));
+    let filled = fill_variables(&templates, variables)
+        .with_context(|| format!("Failed to fill query {}", name))?;
+    if filled.windows(2).any(|pair| pair[0][1..] != pair[1][1..]) {
+        return Err(anyhow!(
+            "Ranges in query {} can only be used by its pattern",
+            name
+        ));
+    }
+    let first = filled
+        .first()
+        .with_context(|| format!("Query {} has no file to read", name))?;
+
+    let mut defaults = pattern.defaults.clone();
+    defaults.update(query.defaults.clone());
+    defaults.update(overrides);
+    let index_value = query.index_value.as_ref().map(|_| first[1].to_string());
+    let index_name = match &index_value {
+        Some(_) => Some(defaults.index_name.clone().ok_or_else(|| {
+            anyhow!(
+                "Query {} needs an index_name, on the query or its pattern",
+                name
+            )
+        })?),
+        None => None,
+    };
+    Ok(Query {
+        file_names: filled
+            .iter()
+            .map(|paths| PathBuf::from(&paths[0]))
+            .collect(),
+        index_name,
+        index_value,
+        filters: first[3..].to_vec(),
+        limit: query.limit,
+        output_file: query.output_file.as_ref().map(|_| first[2].to_string()),
+        defaults,
+        ..Default::default()
+    })
+}
+
+pub fn get_query_pattern<'a>(
+    config: &'a Config,
+    name: &str,
+    query: &QueryConfig,
+) -> Result<&'a PatternConfig> {
+    config.patterns.get(&query.pattern).ok_or_else(|| {
+        anyhow!(
+            "Query {} reads pattern {} which is not in config",
+            name,
+            query.pattern
+        )
+    })
+}
+
+/// Runs a saved query, see `resolve_query`.
+pub fn run_query(
+    name: &str,
+    variables: &PatternVariables,
+    overrides: PatternDefaults,
+    view: bool,
+    display: Display,
+    stats: Option<StatsFormat>,
+) -> Result<DataFrame> {
+    let mut config = Config::load()?;
+    let pattern_name = get_query(&config, name)?.pattern.clone();
+    merge_catalogs(&mut config, &[&pattern_name]);
+    let query = get_query(&config, name)?;
+    let pattern = get_query_pattern(&config, name, query)?;
+    let mut query = resolve_query(name, query, pattern, variables, overrides)?;
+    query.view = view;
+    query.display = display;
+    query.stats = stats;
+    query.run(&config)
+}
+
+pub fn list_queries() -> Result<()> {
+    let config = Config::load()?;
+    let records: Vec<_> = config
+        .queries
+        .iter()
+        .map(|(name, query)| {
+            json!({ "name": name, "pattern": query.pattern, "index_value": query.index_value })
+        })
+        .collect();
+    print_records(&records, || {
+        if config.queries.is_empty() {
+            println!("No queries available in config");
+        }
+        for (name, query) in &config.queries {
+            match &query.index_value {
+                Some(index_value) => println!(
+                    "Query {} searches {} for {}",
+                    name, query.pattern, index_value
+                ),
+                None => println!("Query {} downloads {}", name, query.pattern),
+            }
+        }
+    })
+}
+
+pub fn show_query(name: &str) -> Result<()> {
+    let mut config = Config::load()?;
+    let pattern_name = get_query(&config, name)?.pattern.clone();
+    merge_catalogs(&mut config, &[&pattern_name]);
+    let query = get_query(&config, name)?;
+    let templates: Vec<&str> = [
+        config
+            .patterns
+            .get(&query.pattern)
+            .map(|pattern| pattern.value.as_str()),
+        query.index_value.as_deref(),
+        query.output_file.as_deref(),
+    ]
+    .into_iter()
+    .flatten()
+    .chain(query.filters.iter().map(String::as_str))
+    .collect();
+    let placeholders = collect_shared_placeholders(&templates)?;
+    let mut record = serde_json::to_value(query)?;
+    record["name"] = json!(name);
+    record["placeholders"] = placeholders
+        .iter()
+        .map(|placeholder| placeholder.describe())
+        .collect();
+    print_record(&record, || {
+        println!("Query {} reads pattern {}", name, query.pattern);
+        let defaults = &query.defaults;
+        let listed = [
+            ("index name", defaults.index_name.clone()),
+            ("index value", query.index_value.clone()),
+            (
+                "filters",
+                (!query.filters.is_empty()).then(|| query.filters.join(", ")),
+            ),
+            ("cols", defaults.cols.as_ref().map(|cols| cols.join(" "))),
+            ("sort", defaults.sort.as_ref().map(|sort| sort.join(" "))),
+            ("limit", query.limit.map(|limit| limit.to_string())),
+            ("output file", query.output_file.clone()),
+            ("output format", defaults.output_format.clone()),
+            ("profile", defaults.profile.clone()),
+            ("execution type", defaults.execution_type.clone()),
+        ];
+        for (label, value) in listed {
+            if let Some(value) = value {
+                println!("  {}: {}", label, value);
+            }
+        }
+        if !placeholders.is_empty() {
+            println!("Placeholders:");
+            for placeholder in &placeholders {
+                println!("  {}", placeholder.describe());
+            }
+        }
+    })
+}
+
+/// Saves a query in the user config, its pattern has to be known and its filters valid.
+pub fn add_query_to_config(name: String, query: QueryConfig) -> Result<()> {
+    let mut config = Config::load_user()?;
+    if config.queries.contains_key(&name) {
+        return Err(anyhow!(
+            "Query supplied with name {} is already in config",
+            name
+        ));
+    }
+    if !load_with_catalogs(&[&query.pattern])?
+        .patterns
+        .contains_key(&query.pattern)
+    {
+        return Err(anyhow!("No pattern named {} in config", query.pattern));
+    }
+    for filter in &query.filters {
+        Filter::parse(filter).with_context(|| format!("Invalid filter of query {}", name))?;
+    }
+    config.queries.insert(name.clone(), query);
+    let path = config.save()?;
+    print_status(
+        &format!("Added query {} to {}", name, path.display()),
+        &json!({ "added": name, "config": path }),
+    )
+}
+
+pub fn remove_query_from_config(name: String, yes: bool) -> Result<()> {
+    let mut config = Config::load_user()?;
+    get_query(&config, &name)?;
+    if !confirm_removal(&format!("query {}", name), yes)? {
+        eprintln!("Nothing removed");
+        return Ok(());
+    }
+    config.queries.remove(&name);
+    let path = config.save()?;
+    print_status(
+        &format!("Removed query {} from {}", name, path.display()),
+        &json!({ "removed": name, "config": path }),
+    )
+}
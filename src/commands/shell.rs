@@ -0,0 +1,356 @@
+use std::{
+    io::{self, Write},
+    path::PathBuf,
+};
+
+use anyhow::{anyhow, Context, Result};
+use file_processing::dataframe::{
+    file::{write_output, OutputFormat, ScanFile},
+    operations::{sort_columns, value_expr, CompareOp, Filter},
+    parq::ParqProcessor,
+};
+use polars::{
+    lazy::{
+        dsl::{col, Expr},
+        frame::LazyFrame,
+    },
+    sql::SQLContext,
+};
+
+use super::{
+    catalog::load_with_catalogs,
+    config::{user_config_path, Config},
+    line_editor::LineEditor,
+    pattern::handle_pattern,
+    variables::PatternVariables,
+    viewer::view_dataframe,
+};
+
+const HISTORY_FILE: &str = "shell_history";
+const DEFAULT_HEAD_ROWS: usize = 10;
+const SQL_TABLE: &str = "df";
+const COMMANDS: [&str; 12] = [
+    "open", "schema", "filter", "select", "sort", "head", "view", "sql", "save", "undo", "help",
+    "exit",
+];
+const HELP: &str = "\
+open <pattern|path>        read the files of a pattern or a path
+schema                     list the columns and their types
+filter <col> <op> <value>  keep the rows where the column compares to the value, op is one of = != > >= < <=
+select <col>...            keep these columns
+sort <col[:desc]>...       sort by these columns
+head [n]                   print the first rows, 10 by default
+view                       browse the current rows in a scrollable table, q to go back
+sql <query>                run a query on the current rows, available as table df
+save <file>                write the current rows to a csv or parquet file
+undo                       drop the last step
+exit                       leave the shell";
+
+#[derive(Debug, PartialEq)]
+pub enum ShellCommand {
+    Open(String),
+    Schema,
+    Filter {
+        column: String,
+        op: CompareOp,
+        value: String,
+    },
+    Select(Vec<String>),
+    Sort(Vec<String>),
+    Head(usize),
+    View,
+    Sql(String),
+    Save(String),
+    Undo,
+    Help,
+    Exit,
+}
+
+fn unquote(value: &str) -> &str {
+    let value = value.trim();
+    ['"', '\'']
+        .iter()
+        .find_map(|quote| {
+            value
+                .strip_prefix(*quote)
+                .and_then(|value| value.strip_suffix(*quote))
+        })
+        .unwrap_or(value)
+}
+
+fn required<'a>(command: &str, argument: &'a str) -> Result<&'a str> {
+    let argument = unquote(argument);
+    if argument.is_empty() {
+        return Err(anyhow!("{} needs an argument, see help", command));
+    }
+    Ok(argument)
+}
+
+fn words(command: &str, arguments: &str) -> Result<Vec<String>> {
+    let words: Vec<String> = arguments.split_whitespace().map(String::from).collect();
+    if words.is_empty() {
+        return Err(anyhow!("{} needs at least one column, see help", command));
+    }
+    Ok(words)
+}
+
+impl ShellCommand {
+    pub fn parse(line: &str) -> Result<Self> {
+        let line = line.trim();
+        let (name, arguments) = line
+            .split_once(char::is_whitespace)
+            .map(|(name, arguments)| (name, arguments.trim()))
+            .unwrap_or((line, ""));
+        let command = match name {
+            "open" => ShellCommand::Open(required(name, arguments)?.to_string()),
+            "schema" => ShellCommand::Schema,
+            "filter" => {
+                let Filter { column, op, value } = Filter::parse(arguments)?;
+                ShellCommand::Filter { column, op, value }
+            }
+            "select" => ShellCommand::Select(words(name, arguments)?),
+            "sort" => ShellCommand::Sort(words(name, arguments)?),
+            "head" if arguments.is_empty() => ShellCommand::Head(DEFAULT_HEAD_ROWS),
+            "head" => ShellCommand::Head(
+                arguments
+                    .parse()
+                    .map_err(|_| anyhow!("head needs a number of rows, got {}", arguments))?,
+            ),
+            "sql" => ShellCommand::Sql(required(name, arguments)?.to_string()),
+            "save" => ShellCommand::Save(required(name, arguments)?.to_string()),
+            "view" => ShellCommand::View,
+            "undo" => ShellCommand::Undo,
+            "help" => ShellCommand::Help,
+            "exit" | "quit" => ShellCommand::Exit,
+            _ => return Err(anyhow!("Unknown command {}, see help", name)),
+        };
+        Ok(command)
+    }
+}
+
+/// The source opened in the shell and the steps applied to it, each step is kept
+/// so it can be undone.
+pub struct Session {
+    config: Config,
+    profile: Option<String>,
+    variables: PatternVariables,
+    steps: Vec<LazyFrame>,
+    columns: Vec<String>,
+}
+
+impl Session {
+    pub fn new(config: Config, profile: Option<String>, variables: PatternVariables) -> Self {
+        Session {
+            config,
+            profile,
+            variables,
+            steps: Vec::new(),
+            columns: Vec::new(),
+        }
+    }
+
+    fn current(&self) -> Result<LazyFrame> {
+        self.steps
+            .last()
+            .cloned()
+            .ok_or_else(|| anyhow!("Nothing is open, use open <pattern|path>"))
+    }
+
+    fn refresh_columns(&mut self) -> Result<()> {
+        self.columns = match self.steps.last() {
+            Some(lf) => lf
+                .schema()?
+                .iter_fields()
+                .map(|field| field.name().to_string())
+                .collect(),
+            None => Vec::new(),
+        };
+        Ok(())
+    }
+
+    /// Adds a step once its schema resolves, so a wrong column fails here and not later.
+    fn push(&mut self, lf: LazyFrame) -> Result<()> {
+        lf.schema()?;
+        self.steps.push(lf);
+        self.refresh_columns()
+    }
+
+    fn open(&mut self, source: &str) -> Result<LazyFrame> {
+        let pattern = self.config.patterns.get(source);
+        let file_names = match pattern {
+            Some(_) => handle_pattern(source, &self.variables)?
+                .into_iter()
+                .map(PathBuf::from)
+                .collect(),
+            None => vec![PathBuf::from(source)],
+        };
+        let profile = self.config.resolve_profile(
+            self.profile
+                .clone()
+                .or_else(|| pattern.and_then(|pattern| pattern.defaults.profile.clone())),
+        );
+        ParqProcessor::new(None, None, None, file_names, profile.as_deref(), None).scan()
+    }
+
+    /// Words the last word of `before_cursor` can complete to: commands first,
+    /// then pattern names after `open` and columns elsewhere.
+    pub fn completions(&self, before_cursor: &str) -> Vec<String> {
+        match before_cursor.split_once(char::is_whitespace) {
+            None => COMMANDS.iter().map(|command| command.to_string()).collect(),
+            Some(("open", _)) => self.config.patterns.keys().cloned().collect(),
+            Some(_) => self.columns.clone(),
+        }
+    }
+
+    /// Runs a command, returns false when the shell should stop.
+    pub fn execute(&mut self, command: ShellCommand, out: &mut impl Write) -> Result<bool> {
+        match command {
+            ShellCommand::Open(source) => {
+                let lf = self.open(&source)?;
+                self.steps.clear();
+                self.push(lf)?;
+                writeln!(out, "Opened {} with {} columns", source, self.columns.len())?;
+            }
+            ShellCommand::Schema => {
+                for field in self.current()?.schema()?.iter_fields() {
+                    writeln!(out, "{}: {}", field.name(), field.data_type())?;
+                }
+            }
+            ShellCommand::Filter { column, op, value } => {
+                let filtered = self
+                    .current()?
+                    .filter(op.apply(col(&column), value_expr(&value)));
+                self.push(filtered)?;
+            }
+            ShellCommand::Select(columns) => {
+                let exprs: Vec<Expr> = columns.iter().map(|column| col(column)).collect();
+                let selected = self.current()?.select(exprs);
+                self.push(selected)?;
+            }
+            ShellCommand::Sort(columns) => {
+                let sorted = sort_columns(self.current()?, &columns)?;
+                self.push(sorted)?;
+            }
+            ShellCommand::Head(rows) => {
+                let rows = rows.try_into().unwrap_or(u32::MAX);
+                writeln!(out, "{}", self.current()?.limit(rows).collect()?)?;
+            }
+            ShellCommand::View => view_dataframe(self.current()?.collect()?)?,
+            ShellCommand::Sql(query) => {
+                let mut context = SQLContext::new();
+                context.register(SQL_TABLE, self.current()?);
+                let result = context
+                    .execute(&query)
+                    .with_context(|| "Failed to run the sql query")?;
+                self.push(result)?;
+            }
+            ShellCommand::Save(file) => {
+                let path = self.config.output.resolve(file);
+                let mut df = self.current()?.collect()?;
+                write_output(&mut df, &path, OutputFormat::of(&path))?;
+                writeln!(out, "Saved {} rows to {}", df.height(), path)?;
+            }
+            ShellCommand::Undo => {
+                if self.steps.len() < 2 {
+                    return Err(anyhow!("Nothing to undo"));
+                }
+                self.steps.pop();
+                self.refresh_columns()?;
+            }
+            ShellCommand::Help => writeln!(out, "{}", HELP)?,
+            ShellCommand::Exit => return Ok(false),
+        }
+        Ok(true)
+    }
+}
+
+fn history_path() -> Option<PathBuf> {
+    let (config_path, _) = user_config_path().ok()?;
+    Some(config_path.parent()?.join(HISTORY_FILE))
+}
+
+/// Reads commands until `exit` or the end of input, errors are printed and the session goes on.
+pub fn run_shell(profile: Option<String>, variables: PatternVariables) -> Result<()> {
+    let config = load_with_catalogs(&[])?;
+    let mut session = Session::new(config, profile, variables);
+    let mut editor = LineEditor::new(history_path());
+    let mut out = io::stdout();
+    while let Some(line) = editor.read_line("wdapty> ", &|before_cursor| {
+        session.completions(before_cursor)
+    })? {
+        if line.trim().is_empty() {
+            continue;
+        }
+        match ShellCommand::parse(&line).and_then(|command| session.execute(command, &mut out)) {
+            Ok(true) => {}
+            Ok(false) => break,
+            Err(e) => eprintln!("Error: {:#}", e),
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use polars::{df, lazy::frame::IntoLazy};
+
+    use crate::commands::{config::Config, variables::PatternVariables};
+
+    use super::{CompareOp, Session, ShellCommand};
+
+    #[test]
+    fn test_parse_shell_command() {
+        assert_eq!(
+            ShellCommand::parse("filter transaction_time >= '2024-02-01 17:01:00'").unwrap(),
+            ShellCommand::Filter {
+                column: "transaction_time".to_string(),
+                op: CompareOp::GtEq,
+                value: "2024-02-01 17:01:00".to_string(),
+            }
+        );
+        assert_eq!(
+            ShellCommand::parse("sort open:desc close").unwrap(),
+            ShellCommand::Sort(vec!["open:desc".to_string(), "close".to_string()])
+        );
+        assert_eq!(ShellCommand::parse("head").unwrap(), ShellCommand::Head(10));
+        assert_eq!(
+            ShellCommand::parse(" head 3 ").unwrap(),
+            ShellCommand::Head(3)
+        );
+        assert!(ShellCommand::parse("head all").is_err());
+        assert!(ShellCommand::parse("filter open ~ 3").is_err());
+        assert!(ShellCommand::parse("select").is_err());
+        assert!(ShellCommand::parse("drop open").is_err());
+    }
+
+    #[test]
+    fn test_session_steps() {
+        let mut session = Session::new(Config::default(), None, PatternVariables::default());
+        let mut out = Vec::new();
+        assert!(session.execute(ShellCommand::Schema, &mut out).is_err());
+
+        let df = df!("id" => [1, 2, 3], "name" => ["a", "b", "c"]).unwrap();
+        session.push(df.lazy()).unwrap();
+        let run = |session: &mut Session, line: &str| {
+            session.execute(ShellCommand::parse(line).unwrap(), &mut Vec::new())
+        };
+        run(&mut session, "filter id > 1").unwrap();
+        run(&mut session, "select name").unwrap();
+        assert_eq!(session.columns, vec!["name"]);
+        assert_eq!(session.completions("sort n"), vec!["name"]);
+        assert!(session.completions("sc").contains(&"schema".to_string()));
+        assert!(run(&mut session, "select id").is_err());
+
+        run(&mut session, "undo").unwrap();
+        assert_eq!(session.columns, vec!["id", "name"]);
+        run(&mut session, "sql select id from df where name != 'c'").unwrap();
+        let df = session.current().unwrap().collect().unwrap();
+        assert_eq!(df.column("id").unwrap().i32().unwrap().get(0), Some(2));
+        assert_eq!(df.height(), 1);
+
+        run(&mut session, "undo").unwrap();
+        run(&mut session, "undo").unwrap();
+        assert!(run(&mut session, "undo").is_err());
+        assert!(!run(&mut session, "exit").unwrap());
+    }
+}
@@ -0,0 +1,140 @@
+use std::time::Duration;
+
+use clap::ValueEnum;
+use file_processing::dataframe::stats::RunStats;
+use serde_json::{json, Value};
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, ValueEnum)]
+pub enum StatsFormat {
+    /// A summary for people
+    #[default]
+    Text,
+    /// One JSON object, sizes in bytes and times in milliseconds
+    Json,
+}
+
+fn size(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["KiB", "MiB", "GiB", "TiB"];
+    if bytes < 1024 {
+        return format!("{} B", bytes);
+    }
+    let mut value = bytes as f64 / 1024.0;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    format!("{:.1} {}", value, UNITS[unit])
+}
+
+fn milliseconds(duration: Duration) -> f64 {
+    duration.as_secs_f64() * 1000.0
+}
+
+fn stats_record(stats: &RunStats) -> Value {
+    json!({
+        "files": stats.files,
+        "rows_total": stats.rows_total,
+        "rows_scanned_estimate": stats.rows_scanned_estimate,
+        "rows_matched": stats.rows_matched,
+        "bytes_read_local_estimate": stats.bytes_read_local_estimate,
+        "bytes_read_s3_estimate": stats.bytes_read_s3_estimate,
+        "row_groups_read_estimate": stats.row_groups_read_estimate,
+        "row_groups_skipped_estimate": stats.row_groups_skipped_estimate,
+        "scan_ms": milliseconds(stats.scan),
+        "filter_ms": milliseconds(stats.filter),
+        "collect_ms": milliseconds(stats.collect),
+        "write_ms": milliseconds(stats.write),
+        "wall_ms": milliseconds(stats.wall),
+        "peak_memory": stats.peak_memory,
+        "output_size": stats.output_size,
+    })
+}
+
+fn stats_lines(stats: &RunStats) -> Vec<String> {
+    vec![
+        format!(
+            "Rows: {} matched of {} in {} files",
+            stats.rows_matched, stats.rows_total, stats.files
+        ),
+        format!(
+            "Estimated from the parquet metadata: {} rows scanned, {} row groups read, {} skipped",
+            stats.rows_scanned_estimate,
+            stats.row_groups_read_estimate,
+            stats.row_groups_skipped_estimate
+        ),
+        format!(
+            "Estimated read: {} from local files, {} from s3",
+            size(stats.bytes_read_local_estimate),
+            size(stats.bytes_read_s3_estimate)
+        ),
+        format!(
+            "Time: scan {:.2?}, filter {:.2?}, collect {:.2?}, write {:.2?}, wall {:.2?}",
+            stats.scan, stats.filter, stats.collect, stats.write, stats.wall
+        ),
+        format!(
+            "Peak memory: {}",
+            stats.peak_memory.map_or("unknown".to_string(), size)
+        ),
+        format!("Output: {}", size(stats.output_size)),
+    ]
+}
+
+/// Prints the stats of a run on stderr, leaving stdout to the results.
+pub fn print_stats(stats: &RunStats, format: StatsFormat) {
+    match format {
+        StatsFormat::Text => {
+            for line in stats_lines(stats) {
+                eprintln!("{}", line);
+            }
+        }
+        StatsFormat::Json => eprintln!("{}", stats_record(stats)),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::Duration;
+
+    use file_processing::dataframe::stats::RunStats;
+
+    use super::{size, stats_lines, stats_record};
+
+    #[test]
+    fn test_size() {
+        assert_eq!(size(512), "512 B");
+        assert_eq!(size(1536), "1.5 KiB");
+        assert_eq!(size(5 * 1024 * 1024 * 1024), "5.0 GiB");
+    }
+
+    #[test]
+    fn test_stats_output() {
+        let stats = RunStats {
+            files: 2,
+            rows_total: 1000,
+            rows_scanned_estimate: 400,
+            rows_matched: 3,
+            bytes_read_s3_estimate: 2048,
+            row_groups_read_estimate: 4,
+            row_groups_skipped_estimate: 6,
+            scan: Duration::from_millis(120),
+            wall: Duration::from_millis(150),
+            ..Default::default()
+        };
+        let lines = stats_lines(&stats);
+        assert_eq!(lines[0], "Rows: 3 matched of 1000 in 2 files");
+        assert_eq!(
+            lines[1],
+            "Estimated from the parquet metadata: 400 rows scanned, 4 row groups read, 6 skipped"
+        );
+        assert_eq!(
+            lines[2],
+            "Estimated read: 0 B from local files, 2.0 KiB from s3"
+        );
+        assert_eq!(lines[4], "Peak memory: unknown");
+        let record = stats_record(&stats);
+        assert_eq!(record["scan_ms"], 120.0);
+        assert_eq!(record["row_groups_skipped_estimate"], 6);
+        assert!(record["peak_memory"].is_null());
+    }
+}
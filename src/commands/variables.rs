@@ -0,0 +1,200 @@
+use std::{collections::HashMap, env, fs, path::Path};
+
+use anyhow::{anyhow, Context, Result};
+
+const VAR_ENV_PREFIX: &str = "WDAPTY_VAR_";
+
+/// Values for pattern placeholders given without prompting.
+/// `--var` wins over `--vars-file`, which wins over `WDAPTY_VAR_<NAME>`.
+#[derive(Debug, Default, Clone)]
+pub struct PatternVariables {
+    provided: HashMap<String, String>,
+    no_prompt: bool,
+}
+
+fn parse_var_definition(definition: &str) -> Result<(String, String)> {
+    definition
+        .split_once('=')
+        .map(|(name, value)| (name.trim().to_string(), value.to_string()))
+        .filter(|(name, _)| !name.is_empty())
+        .ok_or_else(|| anyhow!("Variable {} is not in the format name=value", definition))
+}
+
+fn read_vars_file(path: &Path) -> Result<HashMap<String, String>> {
+    let content = fs::read_to_string(path)
+        .with_context(|| format!("Failed to read vars file {}", path.display()))?;
+    let is_json = path
+        .extension()
+        .is_some_and(|extension| extension.eq_ignore_ascii_case("json"));
+    let values = if is_json {
+        serde_json::from_str::<HashMap<String, serde_json::Value>>(&content)
+            .with_context(|| format!("Invalid json in vars file {}", path.display()))?
+            .into_iter()
+            .map(|(name, value)| match value {
+                serde_json::Value::String(value) => (name, value),
+                value => (name, value.to_string()),
+            })
+            .collect()
+    } else {
+        toml::from_str::<HashMap<String, toml::Value>>(&content)
+            .with_context(|| format!("Invalid toml in vars file {}", path.display()))?
+            .into_iter()
+            .map(|(name, value)| match value {
+                toml::Value::String(value) => (name, value),
+                value => (name, value.to_string()),
+            })
+            .collect()
+    };
+    Ok(values)
+}
+
+/// Name of the env var holding the value of a placeholder, `{run-date}` is `WDAPTY_VAR_RUN_DATE`.
+pub fn variable_env_name(name: &str) -> String {
+    let name: String = name
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() {
+                c.to_ascii_uppercase()
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    format!("{}{}", VAR_ENV_PREFIX, name)
+}
+
+impl PatternVariables {
+    pub fn new(vars: Vec<String>, vars_file: Option<&Path>) -> Result<Self> {
+        let mut provided = match vars_file {
+            Some(path) => read_vars_file(path)?,
+            None => HashMap::new(),
+        };
+        for definition in vars {
+            let (name, value) = parse_var_definition(&definition)?;
+            provided.insert(name, value);
+        }
+        Ok(PatternVariables {
+            provided,
+            no_prompt: false,
+        })
+    }
+
+    /// Adds values for the names that have none yet.
+    pub fn with_fallbacks(mut self, values: impl IntoIterator<Item = (String, String)>) -> Self {
+        for (name, value) in values {
+            self.provided.entry(name).or_insert(value);
+        }
+        self
+    }
+
+    /// Never prompts for missing values, even when stdin is a terminal.
+    pub fn without_prompts(mut self) -> Self {
+        self.no_prompt = true;
+        self
+    }
+
+    pub fn can_prompt(&self) -> bool {
+        !self.no_prompt
+    }
+
+    pub fn get(&self, name: &str) -> Option<String> {
+        self.provided
+            .get(name)
+            .cloned()
+            .or_else(|| env::var(variable_env_name(name)).ok())
+    }
+
+    /// Splits the placeholders into the values already known and the names still missing.
+    pub fn resolve(&self, names: &[String]) -> (HashMap<String, String>, Vec<String>) {
+        let mut values = HashMap::new();
+        let mut missing = Vec::new();
+        for name in names {
+            match self.get(name) {
+                Some(value) => {
+                    values.insert(name.to_string(), value);
+                }
+                None if !missing.contains(name) => missing.push(name.to_string()),
+                None => {}
+            }
+        }
+        (values, missing)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::env;
+
+    use assert_fs::{prelude::*, TempDir};
+
+    use super::{parse_var_definition, variable_env_name, PatternVariables};
+
+    #[test]
+    fn test_parse_var_definition() {
+        let (name, value) = parse_var_definition("date=2024-02-01").expect("should be valid");
+        assert_eq!(name, "date");
+        assert_eq!(value, "2024-02-01");
+        let (_, value) = parse_var_definition("filter=a=b").expect("should be valid");
+        assert_eq!(value, "a=b");
+        assert!(parse_var_definition("date").is_err());
+        assert!(parse_var_definition("=2024").is_err());
+    }
+
+    #[test]
+    fn test_variable_env_name() {
+        assert_eq!(variable_env_name("run-date"), "WDAPTY_VAR_RUN_DATE");
+        assert_eq!(variable_env_name("account"), "WDAPTY_VAR_ACCOUNT");
+    }
+
+    #[test]
+    fn test_vars_file() {
+        let dir = TempDir::new().unwrap();
+        let json = dir.child("vars.json");
+        json.write_str(r#"{"date": "2024-02-01", "hour": 5}"#)
+            .unwrap();
+        let variables = PatternVariables::new(vec![], Some(json.path())).unwrap();
+        assert_eq!(variables.get("date").unwrap(), "2024-02-01");
+        assert_eq!(variables.get("hour").unwrap(), "5");
+
+        let toml = dir.child("vars.toml");
+        toml.write_str("date = \"2024-02-02\"\nhour = 6\n").unwrap();
+        let variables = PatternVariables::new(vec![], Some(toml.path())).unwrap();
+        assert_eq!(variables.get("date").unwrap(), "2024-02-02");
+        assert_eq!(variables.get("hour").unwrap(), "6");
+    }
+
+    #[test]
+    fn test_resolve_precedence() {
+        let dir = TempDir::new().unwrap();
+        let file = dir.child("vars.toml");
+        file.write_str("date = \"from-file\"\naccount = \"from-file\"\n")
+            .unwrap();
+        env::set_var("WDAPTY_VAR_ACCOUNT", "from-env");
+        env::set_var("WDAPTY_VAR_REGION", "from-env");
+        let variables =
+            PatternVariables::new(vec!["date=from-cli".to_string()], Some(file.path())).unwrap();
+        let names = ["date", "account", "region", "missing", "missing"].map(String::from);
+        let (values, missing) = variables.resolve(&names);
+        env::remove_var("WDAPTY_VAR_ACCOUNT");
+        env::remove_var("WDAPTY_VAR_REGION");
+
+        assert_eq!(values["date"], "from-cli");
+        assert_eq!(values["account"], "from-file");
+        assert_eq!(values["region"], "from-env");
+        assert_eq!(missing, vec!["missing".to_string()]);
+    }
+
+    #[test]
+    fn test_with_fallbacks() {
+        let variables = PatternVariables::new(vec!["date=from-cli".to_string()], None)
+            .unwrap()
+            .with_fallbacks([
+                ("date".to_string(), "from-job".to_string()),
+                ("account".to_string(), "from-job".to_string()),
+            ]);
+        assert_eq!(variables.get("date").unwrap(), "from-cli");
+        assert_eq!(variables.get("account").unwrap(), "from-job");
+        assert!(variables.can_prompt());
+        assert!(!variables.without_prompts().can_prompt());
+    }
+}
@@ -0,0 +1,493 @@
+use std::io::{self, IsTerminal, Write};
+
+use anyhow::Result;
+use crossterm::{
+    clipboard::CopyToClipboard,
+    cursor::{Hide, MoveTo, Show},
+    event::{self, Event, KeyCode, KeyEvent, KeyEventKind},
+    execute, queue,
+    style::{Attribute, Print, SetAttribute},
+    terminal::{self, Clear, ClearType, EnterAlternateScreen, LeaveAlternateScreen},
+};
+use file_processing::dataframe::operations::sort_columns;
+use log::warn;
+use polars::{frame::DataFrame, lazy::frame::IntoLazy, prelude::AnyValue};
+
+use super::line_editor::RawMode;
+
+const COMPACT_WIDTH: usize = 20;
+const WIDE_WIDTH: usize = 80;
+/// Rows the column widths are measured on, the cells of later rows are cut to fit.
+const WIDTH_SAMPLE_ROWS: usize = 200;
+const HELP: &str =
+    "q quit  / search  n/N next/previous  s sort  f freeze  w width  y copy cell  Y copy row";
+
+#[derive(Debug, PartialEq)]
+enum ViewAction {
+    Continue,
+    Quit,
+    Copy(String),
+}
+
+#[derive(Debug, PartialEq)]
+enum Mode {
+    Browse,
+    Search(String),
+}
+
+/// A scrollable grid over a data frame, the cursor is on the cell at `row` and `column`.
+/// Cells are formatted when shown, not up front.
+pub struct TableView {
+    df: DataFrame,
+    columns: Vec<String>,
+    widths: Vec<usize>,
+    row: usize,
+    column: usize,
+    top: usize,
+    left: usize,
+    /// Leading columns kept on screen while scrolling right.
+    frozen: usize,
+    wide: bool,
+    mode: Mode,
+    search: Option<String>,
+    /// Column sorted by and whether it is descending.
+    sort: Option<(usize, bool)>,
+    message: String,
+}
+
+fn format_value(value: AnyValue) -> String {
+    match value {
+        AnyValue::Null => String::new(),
+        AnyValue::String(value) => value.to_string(),
+        value => value.to_string(),
+    }
+}
+
+/// Leaves the alternate screen and shows the cursor when dropped, also on errors and panics.
+struct AlternateScreen;
+
+impl AlternateScreen {
+    fn enter(out: &mut impl Write) -> Result<Self> {
+        execute!(out, EnterAlternateScreen, Hide)?;
+        Ok(AlternateScreen)
+    }
+}
+
+impl Drop for AlternateScreen {
+    fn drop(&mut self) {
+        let _ = execute!(io::stdout(), Show, LeaveAlternateScreen);
+    }
+}
+
+/// Cuts or pads the text to exactly `width` chars.
+fn fit(text: &str, width: usize) -> String {
+    if text.chars().count() > width {
+        let mut cut: String = text.chars().take(width.saturating_sub(1)).collect();
+        cut.push('…');
+        cut
+    } else {
+        format!("{:<width$}", text, width = width)
+    }
+}
+
+impl TableView {
+    pub fn new(df: DataFrame) -> Self {
+        let mut view = TableView {
+            columns: df
+                .get_column_names()
+                .into_iter()
+                .map(|name| name.to_string())
+                .collect(),
+            df,
+            widths: Vec::new(),
+            row: 0,
+            column: 0,
+            top: 0,
+            left: 0,
+            frozen: 0,
+            wide: false,
+            mode: Mode::Browse,
+            search: None,
+            sort: None,
+            message: String::new(),
+        };
+        view.refresh_widths();
+        view
+    }
+
+    fn height(&self) -> usize {
+        self.df.height()
+    }
+
+    fn cell(&self, row: usize, column: usize) -> String {
+        self.df.get_columns()[column]
+            .get(row)
+            .map(format_value)
+            .unwrap_or_default()
+            .replace(['\n', '\r', '\t'], " ")
+    }
+
+    fn refresh_widths(&mut self) {
+        let max_width = if self.wide { WIDE_WIDTH } else { COMPACT_WIDTH };
+        let sample = self.height().min(WIDTH_SAMPLE_ROWS);
+        self.widths = self
+            .columns
+            .iter()
+            .enumerate()
+            .map(|(index, name)| {
+                (0..sample)
+                    .map(|row| self.cell(row, index).chars().count())
+                    .chain([name.chars().count()])
+                    .max()
+                    .unwrap_or_default()
+                    .clamp(1, max_width)
+            })
+            .collect();
+    }
+
+    /// The frozen columns followed by the ones from `left` that fit in `width`, with the
+    /// width they are shown with. The last one is cut to the remaining width when it does not fit.
+    fn visible_columns(&self, width: usize) -> Vec<(usize, usize)> {
+        let frozen = self.frozen.min(self.columns.len());
+        let mut visible = Vec::new();
+        let mut used = 0;
+        for index in (0..frozen).chain(self.left.max(frozen)..self.columns.len()) {
+            let remaining = width.saturating_sub(used + 1);
+            if self.widths[index] > remaining {
+                if remaining > 0 {
+                    visible.push((index, remaining));
+                }
+                break;
+            }
+            visible.push((index, self.widths[index]));
+            used += self.widths[index] + 1;
+        }
+        visible
+    }
+
+    fn scroll_to_cursor(&mut self, width: usize, page: usize) {
+        if self.row < self.top {
+            self.top = self.row;
+        } else if self.row >= self.top + page {
+            self.top = self.row + 1 - page;
+        }
+        self.left = self.left.max(self.frozen);
+        if self.column < self.frozen {
+            return;
+        }
+        if self.column < self.left {
+            self.left = self.column;
+        }
+        while self.left < self.column
+            && !self
+                .visible_columns(width)
+                .iter()
+                .any(|(index, _)| *index == self.column)
+        {
+            self.left += 1;
+        }
+    }
+
+    /// Moves to the next cell containing the search text, wrapping around.
+    fn find(&mut self, forward: bool) {
+        let Some(search) = self.search.as_ref().map(|search| search.to_lowercase()) else {
+            self.message = "Nothing to search, use /".to_string();
+            return;
+        };
+        let width = self.columns.len();
+        let cells = self.height() * width;
+        let current = self.row * width + self.column;
+        for step in 1..=cells {
+            let position = if forward {
+                (current + step) % cells
+            } else {
+                (current + cells - step) % cells
+            };
+            let (row, column) = (position / width, position % width);
+            if self.cell(row, column).to_lowercase().contains(&search) {
+                self.row = row;
+                self.column = column;
+                self.message = format!("Found {} in {}", search, self.columns[column]);
+                return;
+            }
+        }
+        self.message = format!("No match for {}", search);
+    }
+
+    /// Sorts ascending by the column, or descending when it is already sorted ascending by it.
+    fn sort_by(&mut self, column: usize) {
+        let descending = self.sort == Some((column, false));
+        let name = self.columns[column].clone();
+        let direction = if descending { "desc" } else { "asc" };
+        let sorted = sort_columns(self.df.clone().lazy(), &[format!("{}:{}", name, direction)])
+            .and_then(|lf| Ok(lf.collect()?));
+        match sorted {
+            Ok(df) => {
+                self.df = df;
+                self.refresh_widths();
+                self.sort = Some((column, descending));
+                self.message = format!("Sorted by {} {}", name, direction);
+            }
+            Err(e) => self.message = format!("Failed to sort by {}: {}", name, e),
+        }
+    }
+
+    fn handle_key(&mut self, key: KeyEvent, page: usize) -> ViewAction {
+        if let Mode::Search(search) = &mut self.mode {
+            match key.code {
+                KeyCode::Char(c) => search.push(c),
+                KeyCode::Backspace => {
+                    search.pop();
+                }
+                KeyCode::Esc => self.mode = Mode::Browse,
+                KeyCode::Enter => {
+                    self.search = Some(std::mem::take(search)).filter(|search| !search.is_empty());
+                    self.mode = Mode::Browse;
+                    self.find(true);
+                }
+                _ => {}
+            }
+            return ViewAction::Continue;
+        }
+        if self.height() == 0 && !matches!(key.code, KeyCode::Char('q') | KeyCode::Esc) {
+            return ViewAction::Continue;
+        }
+        let last_row = self.height().saturating_sub(1);
+        let last_column = self.columns.len().saturating_sub(1);
+        self.message.clear();
+        match key.code {
+            KeyCode::Char('q') | KeyCode::Esc => return ViewAction::Quit,
+            KeyCode::Up | KeyCode::Char('k') => self.row = self.row.saturating_sub(1),
+            KeyCode::Down | KeyCode::Char('j') => self.row = (self.row + 1).min(last_row),
+            KeyCode::Left | KeyCode::Char('h') => self.column = self.column.saturating_sub(1),
+            KeyCode::Right | KeyCode::Char('l') => self.column = (self.column + 1).min(last_column),
+            KeyCode::PageUp => self.row = self.row.saturating_sub(page),
+            KeyCode::PageDown => self.row = (self.row + page).min(last_row),
+            KeyCode::Home | KeyCode::Char('g') => self.row = 0,
+            KeyCode::End | KeyCode::Char('G') => self.row = last_row,
+            KeyCode::Char('0') => self.column = 0,
+            KeyCode::Char('$') => self.column = last_column,
+            KeyCode::Char('f') => {
+                self.frozen = if self.frozen == self.column + 1 {
+                    0
+                } else {
+                    self.column + 1
+                };
+                self.message = format!("{} columns frozen", self.frozen);
+            }
+            KeyCode::Char('w') => {
+                self.wide = !self.wide;
+                self.refresh_widths();
+            }
+            KeyCode::Char('/') => self.mode = Mode::Search(String::new()),
+            KeyCode::Char('n') => self.find(true),
+            KeyCode::Char('N') => self.find(false),
+            KeyCode::Char('s') => self.sort_by(self.column),
+            KeyCode::Char('y') => return ViewAction::Copy(self.cell(self.row, self.column)),
+            KeyCode::Char('Y') => {
+                let row: Vec<String> = (0..self.columns.len())
+                    .map(|column| self.cell(self.row, column))
+                    .collect();
+                return ViewAction::Copy(row.join("\t"));
+            }
+            _ => {}
+        }
+        ViewAction::Continue
+    }
+
+    fn render(&self, out: &mut impl Write, width: usize, height: usize) -> Result<()> {
+        queue!(out, Clear(ClearType::All))?;
+        let visible = self.visible_columns(width);
+        let separator = |index: usize| {
+            if index + 1 == self.frozen {
+                "│"
+            } else {
+                " "
+            }
+        };
+        queue!(out, MoveTo(0, 0), SetAttribute(Attribute::Bold))?;
+        for &(index, shown) in &visible {
+            let name = match self.sort {
+                Some((sorted, descending)) if sorted == index => {
+                    format!(
+                        "{}{}",
+                        self.columns[index],
+                        if descending { "↓" } else { "↑" }
+                    )
+                }
+                _ => self.columns[index].to_string(),
+            };
+            queue!(out, Print(fit(&name, shown)), Print(separator(index)))?;
+        }
+        queue!(out, SetAttribute(Attribute::Reset))?;
+        let page = height.saturating_sub(2);
+        for (line, row) in (self.top..self.height()).take(page).enumerate() {
+            queue!(out, MoveTo(0, (line + 1).try_into().unwrap_or(u16::MAX)))?;
+            for &(index, shown) in &visible {
+                let cell = fit(&self.cell(row, index), shown);
+                if row == self.row && index == self.column {
+                    queue!(
+                        out,
+                        SetAttribute(Attribute::Reverse),
+                        Print(cell),
+                        SetAttribute(Attribute::Reset)
+                    )?;
+                } else {
+                    queue!(out, Print(cell))?;
+                }
+                queue!(out, Print(separator(index)))?;
+            }
+        }
+        let status = match &self.mode {
+            Mode::Search(search) => format!("/{}", search),
+            Mode::Browse if self.height() == 0 => format!("No rows  {}", HELP),
+            Mode::Browse => format!(
+                "row {}/{} column {}/{} {}  {}",
+                self.row + 1,
+                self.height(),
+                self.column + 1,
+                self.columns.len(),
+                self.columns[self.column],
+                if self.message.is_empty() {
+                    HELP
+                } else {
+                    &self.message
+                }
+            ),
+        };
+        queue!(
+            out,
+            MoveTo(0, height.saturating_sub(1).try_into().unwrap_or(u16::MAX)),
+            SetAttribute(Attribute::Reverse),
+            Print(fit(&status, width)),
+            SetAttribute(Attribute::Reset)
+        )?;
+        out.flush()?;
+        Ok(())
+    }
+
+    fn run(&mut self, out: &mut impl Write) -> Result<()> {
+        loop {
+            let (width, height) = terminal::size()?;
+            let (width, height) = (width as usize, height as usize);
+            let page = height.saturating_sub(2).max(1);
+            self.scroll_to_cursor(width, page);
+            self.render(out, width, height)?;
+            let Event::Key(key) = event::read()? else {
+                continue;
+            };
+            if key.kind != KeyEventKind::Press {
+                continue;
+            }
+            match self.handle_key(key, page) {
+                ViewAction::Continue => {}
+                ViewAction::Quit => return Ok(()),
+                ViewAction::Copy(text) => {
+                    queue!(out, CopyToClipboard::to_clipboard_from(text))?;
+                    self.message = "Copied".to_string();
+                }
+            }
+        }
+    }
+}
+
+/// Opens the results in a full screen grid, they are printed instead when not in a terminal.
+pub fn view_dataframe(df: DataFrame) -> Result<()> {
+    if !io::stdin().is_terminal() || !io::stdout().is_terminal() {
+        warn!("Viewing results needs a terminal, printing them instead");
+        println!("{}", df);
+        return Ok(());
+    }
+    let mut view = TableView::new(df);
+    let mut out = io::stdout();
+    let _raw_mode = RawMode::enable()?;
+    let _screen = AlternateScreen::enter(&mut out)?;
+    view.run(&mut out)
+}
+
+#[cfg(test)]
+mod test {
+    use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+    use polars::df;
+
+    use super::{fit, TableView, ViewAction};
+
+    fn press(view: &mut TableView, keys: &[KeyCode]) -> ViewAction {
+        let mut action = ViewAction::Continue;
+        for key in keys {
+            action = view.handle_key(KeyEvent::new(*key, KeyModifiers::NONE), 2);
+        }
+        action
+    }
+
+    fn view() -> TableView {
+        TableView::new(
+            df!(
+                "id" => [3, 1, 2],
+                "name" => ["charlie", "alice", "bob"],
+                "description" => ["a rather long description", "short", "medium size"]
+            )
+            .unwrap(),
+        )
+    }
+
+    #[test]
+    fn test_navigate_and_copy() {
+        let mut view = view();
+        press(&mut view, &[KeyCode::Down, KeyCode::Down, KeyCode::Down]);
+        assert_eq!(view.row, 2);
+        press(&mut view, &[KeyCode::Char('l'), KeyCode::Char('$')]);
+        assert_eq!(view.column, 2);
+        assert_eq!(
+            press(&mut view, &[KeyCode::Char('0'), KeyCode::Char('y')]),
+            ViewAction::Copy("2".to_string())
+        );
+        assert_eq!(
+            press(&mut view, &[KeyCode::Char('Y')]),
+            ViewAction::Copy("2\tbob\tmedium size".to_string())
+        );
+        press(&mut view, &[KeyCode::PageUp]);
+        assert_eq!(view.row, 0);
+        assert_eq!(press(&mut view, &[KeyCode::Char('q')]), ViewAction::Quit);
+    }
+
+    #[test]
+    fn test_freeze_and_scroll_columns() {
+        let mut view = view();
+        assert_eq!(view.widths, vec![2, 7, 20]);
+        // Each column takes its width and a separator, description is cut to what is left.
+        assert_eq!(view.visible_columns(16), vec![(0, 2), (1, 7), (2, 4)]);
+        assert_eq!(view.visible_columns(12), vec![(0, 2), (1, 7)]);
+        press(&mut view, &[KeyCode::Char('f'), KeyCode::Char('$')]);
+        view.scroll_to_cursor(12, 2);
+        assert_eq!(view.left, 2);
+        assert_eq!(view.visible_columns(12), vec![(0, 2), (2, 8)]);
+        press(&mut view, &[KeyCode::Char('w')]);
+        assert_eq!(view.widths[2], 25);
+        press(&mut view, &[KeyCode::Char('0'), KeyCode::Char('f')]);
+        assert_eq!(view.frozen, 0);
+    }
+
+    #[test]
+    fn test_search_and_sort() {
+        let mut view = view();
+        let mut keys = vec![KeyCode::Char('/')];
+        keys.extend("BO".chars().map(KeyCode::Char));
+        keys.push(KeyCode::Enter);
+        press(&mut view, &keys);
+        assert_eq!((view.row, view.column), (2, 1));
+        press(&mut view, &[KeyCode::Char('n')]);
+        assert_eq!((view.row, view.column), (2, 1));
+
+        press(&mut view, &[KeyCode::Char('0'), KeyCode::Char('s')]);
+        assert_eq!(view.cell(0, 1), "alice");
+        press(&mut view, &[KeyCode::Char('s')]);
+        assert_eq!(view.cell(0, 1), "charlie");
+    }
+
+    #[test]
+    fn test_fit() {
+        assert_eq!(fit("abc", 5), "abc  ");
+        assert_eq!(fit("abcdef", 4), "abc…");
+    }
+}
@@ -1,15 +1,37 @@
 use anyhow::Result;
 use clap::{Args, Parser, Subcommand};
+use clap_verbosity_flag::{Verbosity, WarnLevel};
 use commands::{configure::initialize, pattern::get_available_patterns, RunCommand};
-use file_processing::{
-    dataframe::{parq::ParqProcessor, processor::Runnable},
-    Processors,
-};
+use log::info;
+use serde_json::json;
 use std::path::PathBuf;
 
 use crate::commands::{
-    acquire_file_name,
-    pattern::{add_pattern_to_config, remove_pattern_from_config},
+    acquire_file_names,
+    batch::{print_batch_report, run_batch, Manifest, DEFAULT_PARALLELISM},
+    catalog::{
+        export_patterns, import_patterns, load_with_catalogs, merge_catalogs, refresh_catalogs,
+        PatternSetFormat,
+    },
+    config::{
+        list_backups, restore_backup, set_config_override, user_config_path, Config, Display,
+        PatternDefaults, QueryConfig,
+    },
+    credentials::{check_profile, print_explanation, print_profiles},
+    display::apply_display,
+    logging::{init_logging, LogFormat},
+    output::{print_records, print_status, set_output_mode, OutputMode},
+    pattern::{
+        add_pattern_to_config, copy_pattern_in_config, edit_pattern_in_config,
+        remove_pattern_from_config, rename_pattern_in_config, show_pattern, test_pattern,
+    },
+    query::{
+        add_query_to_config, list_queries, remove_query_from_config, run_query, show_query, Query,
+        QueryAction,
+    },
+    shell::run_shell,
+    stats::StatsFormat,
+    variables::PatternVariables,
 };
 mod commands;
 
@@ -17,18 +39,141 @@ mod commands;
 struct CliArgs {
     #[command(subcommand)]
     command: Commands,
+    /// Config file to use instead of the user one, also settable through WDAPTY_CONFIG
+    #[arg(long, global = true)]
+    config: Option<PathBuf>,
+    /// How results and command reports are printed, messages go to stderr
+    #[arg(long, global = true, value_enum, default_value_t)]
+    output: OutputMode,
+    #[command(flatten)]
+    verbosity: Verbosity<WarnLevel>,
+    #[arg(long, global = true, value_enum, default_value_t)]
+    log_format: LogFormat,
+    /// Append log lines to this file instead of writing them to stderr
+    #[arg(long, global = true)]
+    log_file: Option<PathBuf>,
 }
 
+/// Profile, execution type, cols, sort and output format fall back to the defaults
+/// of the pattern when not given.
 #[derive(Debug, Args)]
 struct DefaultProcessingOpts {
     #[arg(long, short)]
     profile: Option<String>,
     #[arg(long)]
     pattern: Option<String>,
-    #[arg(long, short, default_value = "parq")]
-    execution_type: String,
+    #[arg(long, short)]
+    execution_type: Option<String>,
     #[arg(long)]
     file_name: Option<PathBuf>,
+    #[arg(long, num_args = 1..)]
+    cols: Option<Vec<String>>,
+    /// Columns to sort by, as name or name:desc
+    #[arg(long, num_args = 1..)]
+    sort: Option<Vec<String>>,
+    #[arg(long, value_parser = ["csv", "parquet"])]
+    output_format: Option<String>,
+    /// Keep only the first rows
+    #[arg(long)]
+    limit: Option<u32>,
+    /// Browse the results in a scrollable table instead of printing them
+    #[arg(long)]
+    view: bool,
+    /// Print the optimised plan and what is pushed down into the parquet scans, without running it
+    #[arg(long, conflicts_with_all = ["dry_run", "view"])]
+    explain: bool,
+    /// Print the files, credentials, filter and output that would be used, without reading anything
+    #[arg(long, conflicts_with = "view")]
+    dry_run: bool,
+    /// Print rows, row groups and bytes read, timings and memory to stderr once done
+    #[arg(long, value_enum, num_args = 0..=1, default_missing_value = "text", conflicts_with_all = ["explain", "dry_run"])]
+    stats: Option<StatsFormat>,
+    #[command(flatten)]
+    display: DisplayOpts,
+    #[command(flatten)]
+    variables: VariablesOpts,
+}
+
+/// How printed results look, falling back to the [output] section of the config.
+#[derive(Debug, Clone, Args)]
+struct DisplayOpts {
+    /// Rows printed, 0 prints all of them
+    #[arg(long)]
+    max_rows: Option<usize>,
+    /// Columns printed, 0 prints all of them
+    #[arg(long)]
+    max_cols: Option<usize>,
+    /// Longest text printed in a cell
+    #[arg(long)]
+    max_col_width: Option<usize>,
+    #[arg(long, value_parser = ["ascii", "utf8", "markdown", "compact"])]
+    table_style: Option<String>,
+    #[arg(long)]
+    no_header: bool,
+    /// Print results taller than the terminal instead of paging them through $PAGER
+    #[arg(long)]
+    no_pager: bool,
+}
+
+impl From<DisplayOpts> for Display {
+    fn from(opts: DisplayOpts) -> Self {
+        Display {
+            max_rows: opts.max_rows,
+            max_cols: opts.max_cols,
+            max_col_width: opts.max_col_width,
+            table_style: opts.table_style,
+            header: opts.no_header.then_some(false),
+            pager: opts.no_pager.then_some(false),
+        }
+    }
+}
+
+/// Defaults stored with a pattern, used when processing it without the matching flags.
+#[derive(Debug, Args)]
+struct PatternDefaultsOpts {
+    #[arg(long)]
+    profile: Option<String>,
+    #[arg(long)]
+    execution_type: Option<String>,
+    #[arg(long, num_args = 1..)]
+    cols: Option<Vec<String>>,
+    #[arg(long)]
+    index_name: Option<String>,
+    /// Columns to sort by, as name or name:desc
+    #[arg(long, num_args = 1..)]
+    sort: Option<Vec<String>>,
+    #[arg(long, value_parser = ["csv", "parquet"])]
+    output_format: Option<String>,
+}
+
+impl From<PatternDefaultsOpts> for PatternDefaults {
+    fn from(opts: PatternDefaultsOpts) -> Self {
+        PatternDefaults {
+            profile: opts.profile,
+            execution_type: opts.execution_type,
+            cols: opts.cols,
+            index_name: opts.index_name,
+            sort: opts.sort,
+            output_format: opts.output_format,
+        }
+    }
+}
+
+#[derive(Debug, Args)]
+struct VariablesOpts {
+    /// Value of a pattern variable, repeatable. Dates and integers take ranges like
+    /// 2024-02-01..2024-02-07, scanning one file per value
+    #[arg(long = "var", value_name = "NAME=VALUE")]
+    vars: Vec<String>,
+    /// JSON or TOML file with the values of pattern variables
+    #[arg(long)]
+    vars_file: Option<PathBuf>,
+}
+
+impl VariablesOpts {
+    fn load(self) -> Result<PatternVariables> {
+        PatternVariables::new(self.vars, self.vars_file.as_deref())
+    }
 }
 
 #[derive(Debug, Subcommand)]
@@ -39,9 +184,90 @@ enum Commands {
     },
     #[command(subcommand)]
     Patterns(PatternsCommands),
+    #[command(subcommand)]
+    Credentials(CredentialsCommands),
+    #[command(subcommand)]
+    Config(ConfigCommands),
     #[command(arg_required_else_help = true)]
     #[command(subcommand)]
     Processing(ProcessingCommands),
+    /// Run a query saved in the config
+    #[command(arg_required_else_help = true)]
+    Run {
+        name: String,
+        #[arg(long, short)]
+        profile: Option<String>,
+        /// Browse the results in a scrollable table instead of printing them
+        #[arg(long)]
+        view: bool,
+        /// Print rows, row groups and bytes read, timings and memory to stderr once done
+        #[arg(long, value_enum, num_args = 0..=1, default_missing_value = "text")]
+        stats: Option<StatsFormat>,
+        #[command(flatten)]
+        display: DisplayOpts,
+        #[command(flatten)]
+        variables: VariablesOpts,
+    },
+    #[command(subcommand)]
+    Queries(QueriesCommands),
+    /// Explore a pattern or a file with commands like filter, select, sort, head and sql
+    Shell {
+        #[arg(long, short)]
+        profile: Option<String>,
+        #[command(flatten)]
+        variables: VariablesOpts,
+    },
+    /// Run the jobs listed in a toml manifest and print a report of each of them
+    #[command(arg_required_else_help = true)]
+    Batch {
+        manifest: PathBuf,
+        /// How many jobs run at the same time, overrides the manifest
+        #[arg(long, short = 'j')]
+        parallelism: Option<usize>,
+        /// Skip the jobs not started yet once one fails
+        #[arg(long)]
+        fail_fast: bool,
+        #[command(flatten)]
+        variables: VariablesOpts,
+    },
+}
+
+// Parsed once per run, boxing the add arguments would only make them harder to read.
+#[allow(clippy::large_enum_variant)]
+#[derive(Debug, Subcommand)]
+enum QueriesCommands {
+    List,
+    /// Save a search, or a download without --index-value, under a name
+    #[command(arg_required_else_help = true)]
+    Add {
+        name: String,
+        #[arg(long)]
+        pattern: String,
+        #[arg(long)]
+        index_value: Option<String>,
+        /// Keep the rows where a column compares to a value, as "col op value" with op
+        /// one of = != > >= < <=, repeatable
+        #[arg(long = "filter", value_name = "FILTER")]
+        filters: Vec<String>,
+        #[arg(long)]
+        limit: Option<u32>,
+        #[arg(long)]
+        output_file: Option<String>,
+        #[command(flatten)]
+        defaults: PatternDefaultsOpts,
+    },
+    /// Show a query with its placeholders
+    #[command(arg_required_else_help = true)]
+    Show {
+        name: String,
+    },
+    #[command(arg_required_else_help = true)]
+    Remove {
+        name: String,
+        /// Remove without asking for confirmation
+        #[arg(long, short)]
+        yes: bool,
+    },
 }
 
 #[derive(Debug, Subcommand)]
@@ -53,11 +279,95 @@ enum PatternsCommands {
         name: String,
         #[arg(long)]
         value: String,
+        #[command(flatten)]
+        defaults: PatternDefaultsOpts,
     },
     #[command(arg_required_else_help = true)]
     Remove {
         #[arg(long)]
         name: String,
+        /// Remove without asking for confirmation
+        #[arg(long, short)]
+        yes: bool,
+    },
+    /// Show a pattern with its placeholders
+    #[command(arg_required_else_help = true)]
+    Show {
+        name: String,
+    },
+    /// Replace the value or the defaults of a pattern
+    #[command(arg_required_else_help = true)]
+    Edit {
+        #[arg(long)]
+        name: String,
+        #[arg(long)]
+        value: Option<String>,
+        #[command(flatten)]
+        defaults: PatternDefaultsOpts,
+    },
+    #[command(arg_required_else_help = true)]
+    Rename {
+        #[arg(long)]
+        name: String,
+        #[arg(long)]
+        new_name: String,
+    },
+    #[command(arg_required_else_help = true)]
+    Copy {
+        #[arg(long)]
+        name: String,
+        #[arg(long)]
+        new_name: String,
+    },
+    /// Print the patterns of the user config as toml or json
+    Export {
+        #[arg(long, value_enum, default_value = "toml")]
+        format: PatternSetFormat,
+    },
+    /// Add the patterns of a toml or json file, keeping the existing ones on conflicts
+    /// unless --replace is passed
+    #[command(arg_required_else_help = true)]
+    Import {
+        path: PathBuf,
+        #[arg(long)]
+        replace: bool,
+    },
+    /// Download the s3 pattern catalogs again, commands otherwise read them from the cache
+    /// and only download the stale ones for a pattern missing from the config
+    Refresh,
+    /// Print the paths a pattern resolves to and check they exist
+    #[command(arg_required_else_help = true)]
+    Test {
+        name: String,
+        #[arg(long, short)]
+        profile: Option<String>,
+        #[command(flatten)]
+        variables: VariablesOpts,
+    },
+}
+
+#[derive(Debug, Subcommand)]
+enum ConfigCommands {
+    /// List the backups taken before each config change
+    Backups,
+    /// Restore the config from a backup, the latest one by default
+    Restore { backup: Option<String> },
+}
+
+#[derive(Debug, Subcommand)]
+enum CredentialsCommands {
+    List,
+    Check {
+        #[arg(long, short)]
+        profile: Option<String>,
+        #[arg(long)]
+        call: bool,
+        #[arg(long)]
+        endpoint: Option<String>,
+    },
+    Explain {
+        #[arg(long, short)]
+        profile: Option<String>,
     },
 }
 
@@ -72,14 +382,13 @@ enum ProcessingCommands {
     },
     #[command(arg_required_else_help = true)]
     Search {
+        /// Falls back to the index name of the pattern
         #[arg(long)]
-        index_name: String,
+        index_name: Option<String>,
         #[arg(long)]
         index_value: String,
         #[arg(long)]
         output_file: Option<String>,
-        #[arg(long, num_args = 1..)]
-        cols: Option<Vec<String>>,
         #[command(flatten)]
         defaults: DefaultProcessingOpts,
     },
@@ -87,122 +396,415 @@ enum ProcessingCommands {
 
 impl RunCommand for ProcessingCommands {
     fn run(self) -> Result<()> {
-        let (index_name, index_value, cols, output_file, file_name, execution_type, profile) =
-            match self {
-                ProcessingCommands::Download {
-                    output_file,
-                    defaults,
-                } => {
-                    let DefaultProcessingOpts {
-                        profile,
-                        file_name,
-                        execution_type,
-                        pattern,
-                        ..
-                    } = defaults;
-                    let file_name = acquire_file_name(pattern, file_name)?;
-                    println!("Preparing for Download Command");
-                    (
-                        None,
-                        None,
-                        None,
-                        Some(output_file),
-                        file_name,
-                        execution_type,
-                        profile,
-                    )
-                }
-                ProcessingCommands::Search {
-                    index_name,
-                    index_value,
-                    output_file,
-                    cols,
+        let pattern = match &self {
+            ProcessingCommands::Download { defaults, .. }
+            | ProcessingCommands::Search { defaults, .. } => defaults.pattern.clone(),
+        };
+        let config = load_with_catalogs(&pattern.as_deref().into_iter().collect::<Vec<_>>())?;
+        let (command, index_name, index_value, output_file, defaults, pattern_defaults) = match self
+        {
+            ProcessingCommands::Download {
+                output_file,
+                defaults,
+            } => {
+                let pattern_defaults = config.pattern_defaults(defaults.pattern.as_deref());
+                (
+                    "Download",
+                    None,
+                    None,
+                    Some(output_file),
                     defaults,
-                } => {
-                    let DefaultProcessingOpts {
-                        profile,
-                        file_name,
-                        execution_type,
-                        pattern,
-                        ..
-                    } = defaults;
-                    let file_name = acquire_file_name(pattern, file_name)?;
-                    println!("Preparing for Search Command");
-                    (
-                        Some(index_name),
-                        Some(index_value),
-                        cols,
-                        output_file,
-                        file_name,
-                        execution_type,
-                        profile,
-                    )
-                }
-            };
-
-        let processor = match execution_type.as_str() {
-            "parq" => Processors::Parq(ParqProcessor::new(
+                    pattern_defaults,
+                )
+            }
+            ProcessingCommands::Search {
                 index_name,
                 index_value,
-                cols,
-                file_name,
-                profile.as_deref(),
                 output_file,
-            )),
-            _ => return Err(anyhow::anyhow!("Invalid Execution type")),
+                defaults,
+            } => {
+                let pattern_defaults = config.pattern_defaults(defaults.pattern.as_deref());
+                let index_name = index_name
+                    .or_else(|| pattern_defaults.index_name.clone())
+                    .ok_or_else(|| {
+                        anyhow::anyhow!(
+                            "Search needs --index-name or an index_name default on the pattern"
+                        )
+                    })?;
+                (
+                    "Search",
+                    Some(index_name),
+                    Some(index_value),
+                    output_file,
+                    defaults,
+                    pattern_defaults,
+                )
+            }
         };
+        let DefaultProcessingOpts {
+            profile,
+            pattern,
+            execution_type,
+            file_name,
+            cols,
+            sort,
+            output_format,
+            limit,
+            view,
+            explain,
+            dry_run,
+            stats,
+            display,
+            variables,
+        } = defaults;
+        let variables = variables.load()?;
+        let file_names = acquire_file_names(pattern, file_name, &variables)?;
+        info!("Preparing for {} Command", command);
 
-        processor.run()?;
+        let mut defaults = pattern_defaults;
+        defaults.update(PatternDefaults {
+            profile,
+            execution_type,
+            cols,
+            index_name: None,
+            sort,
+            output_format,
+        });
+        Query {
+            file_names,
+            index_name,
+            index_value,
+            limit,
+            output_file,
+            defaults,
+            view,
+            display: display.into(),
+            action: if explain {
+                QueryAction::Explain
+            } else if dry_run {
+                QueryAction::DryRun
+            } else {
+                QueryAction::Run
+            },
+            stats,
+            ..Default::default()
+        }
+        .run(&config)?;
 
         Ok(())
     }
 }
 
+impl RunCommand for QueriesCommands {
+    fn run(self) -> Result<()> {
+        match self {
+            QueriesCommands::List => list_queries(),
+            QueriesCommands::Add {
+                name,
+                pattern,
+                index_value,
+                filters,
+                limit,
+                output_file,
+                defaults,
+            } => add_query_to_config(
+                name,
+                QueryConfig {
+                    pattern,
+                    index_value,
+                    filters,
+                    limit,
+                    output_file,
+                    defaults: defaults.into(),
+                },
+            ),
+            QueriesCommands::Show { name } => show_query(&name),
+            QueriesCommands::Remove { name, yes } => remove_query_from_config(name, yes),
+        }
+    }
+}
+
 impl RunCommand for PatternsCommands {
     fn run(self) -> Result<()> {
         match self {
             PatternsCommands::List => {
-                let patterns_available = get_available_patterns();
-                patterns_available.map(|available| {
+                let available = get_available_patterns()?;
+                let records: Vec<_> = available
+                    .iter()
+                    .map(|(name, value)| json!({ "name": name, "value": value }))
+                    .collect();
+                print_records(&records, || {
                     if available.keys().len() > 0 {
-                        for (key, value) in available {
+                        for (key, value) in &available {
                             println!("Pattern {} has value {}", key, value);
                         }
                     } else {
-                        println!("No patterns available in config.ini");
+                        println!("No patterns available in config");
                     };
                 })
             }
-            PatternsCommands::Add { name, value } => {
-                println!(
+            PatternsCommands::Add {
+                name,
+                value,
+                defaults,
+            } => {
+                info!(
                     "Performing Add Pattern with name {} and value {}",
                     name, value
                 );
-                add_pattern_to_config(name, value)
+                add_pattern_to_config(name, value, defaults.into())
+            }
+            PatternsCommands::Remove { name, yes } => {
+                info!("Performing Remove Pattern with name {}", name);
+                remove_pattern_from_config(name, yes)
+            }
+            PatternsCommands::Show { name } => show_pattern(&name),
+            PatternsCommands::Edit {
+                name,
+                value,
+                defaults,
+            } => {
+                info!("Performing Edit Pattern with name {}", name);
+                edit_pattern_in_config(name, value, defaults.into())
+            }
+            PatternsCommands::Rename { name, new_name } => {
+                info!("Performing Rename Pattern {} to {}", name, new_name);
+                rename_pattern_in_config(name, new_name)
+            }
+            PatternsCommands::Copy { name, new_name } => {
+                info!("Performing Copy Pattern {} to {}", name, new_name);
+                copy_pattern_in_config(name, new_name)
+            }
+            PatternsCommands::Export { format } => {
+                print!("{}", export_patterns(format)?);
+                Ok(())
+            }
+            PatternsCommands::Import { path, replace } => {
+                let report = import_patterns(&path, replace)?;
+                let conflict_status = if replace { "replaced" } else { "conflict" };
+                let records: Vec<_> = report
+                    .added
+                    .iter()
+                    .map(|name| json!({ "name": name, "status": "added" }))
+                    .chain(report.conflicts.iter().map(|(name, existing, imported)| {
+                        json!({
+                            "name": name,
+                            "status": conflict_status,
+                            "existing": existing,
+                            "imported": imported,
+                        })
+                    }))
+                    .collect();
+                print_records(&records, || {
+                    for name in &report.added {
+                        println!("Added pattern {}", name);
+                    }
+                    for (name, existing, imported) in &report.conflicts {
+                        if replace {
+                            println!(
+                                "Replaced pattern {} value {} with {}",
+                                name, existing, imported
+                            );
+                        } else {
+                            println!(
+                                "Conflict on pattern {}: kept {}, {} has {}",
+                                name,
+                                existing,
+                                path.display(),
+                                imported
+                            );
+                        }
+                    }
+                    println!(
+                        "Imported {} patterns, {} unchanged, {} conflicts",
+                        report.added.len(),
+                        report.unchanged.len(),
+                        report.conflicts.len()
+                    );
+                })
+            }
+            PatternsCommands::Refresh => {
+                let refreshed = refresh_catalogs()?;
+                let records: Vec<_> = refreshed
+                    .iter()
+                    .map(|(name, patterns)| match patterns {
+                        Ok(patterns) => json!({ "name": name, "patterns": patterns }),
+                        Err(e) => json!({ "name": name, "error": format!("{:#}", e) }),
+                    })
+                    .collect();
+                print_records(&records, || {
+                    if refreshed.is_empty() {
+                        println!("No catalogs in config");
+                    }
+                    for (name, patterns) in &refreshed {
+                        match patterns {
+                            Ok(patterns) => {
+                                println!("Refreshed catalog {} with {} patterns", name, patterns)
+                            }
+                            Err(e) => println!("Failed to refresh catalog {}: {:#}", name, e),
+                        }
+                    }
+                })
             }
-            PatternsCommands::Remove { name } => {
-                println!("Performing Remove Pattern with name {}", name);
-                remove_pattern_from_config(name)
+            PatternsCommands::Test {
+                name,
+                profile,
+                variables,
+            } => {
+                let profile = Config::load()?.resolve_profile(profile);
+                test_pattern(&name, &variables.load()?, profile.as_deref())
             }
         }
     }
 }
 
+impl RunCommand for ConfigCommands {
+    fn run(self) -> Result<()> {
+        let (config_path, _) = user_config_path()?;
+        match self {
+            ConfigCommands::Backups => {
+                let backups = list_backups(&config_path)?;
+                let records: Vec<_> = backups
+                    .iter()
+                    .map(|backup| json!({ "backup": backup }))
+                    .collect();
+                print_records(&records, || {
+                    if backups.is_empty() {
+                        println!("No backups for {}", config_path.display());
+                    }
+                    for backup in &backups {
+                        println!("{}", backup.display());
+                    }
+                })
+            }
+            ConfigCommands::Restore { backup } => {
+                let restored = restore_backup(&config_path, backup.as_deref())?;
+                print_status(
+                    &format!(
+                        "Restored {} into {}",
+                        restored.display(),
+                        config_path.display()
+                    ),
+                    &json!({ "restored": restored, "config": config_path }),
+                )
+            }
+        }
+    }
+}
+
+impl RunCommand for CredentialsCommands {
+    fn run(self) -> Result<()> {
+        match self {
+            CredentialsCommands::List => print_profiles(),
+            CredentialsCommands::Check {
+                profile,
+                call,
+                endpoint,
+            } => check_profile(profile.as_deref(), endpoint.as_deref(), call),
+            CredentialsCommands::Explain { profile } => print_explanation(profile.as_deref()),
+        }
+    }
+}
+
 impl RunCommand for Commands {
     fn run(self) -> Result<()> {
         match self {
             Commands::Configure { patterns } => {
                 let config_path = initialize(patterns)?;
-                println!("Saved config.ini in {}", config_path);
-                Ok(())
+                print_status(
+                    &format!("Saved config in {}", config_path),
+                    &json!({ "saved": config_path }),
+                )
             }
             Commands::Patterns(pattern_command) => pattern_command.run(),
+            Commands::Credentials(credentials_command) => credentials_command.run(),
+            Commands::Config(config_command) => config_command.run(),
             Commands::Processing(processing_command) => processing_command.run(),
+            Commands::Run {
+                name,
+                profile,
+                view,
+                stats,
+                display,
+                variables,
+            } => {
+                let overrides = PatternDefaults {
+                    profile,
+                    ..Default::default()
+                };
+                run_query(
+                    &name,
+                    &variables.load()?,
+                    overrides,
+                    view,
+                    display.into(),
+                    stats,
+                )?;
+                Ok(())
+            }
+            Commands::Queries(queries_command) => queries_command.run(),
+            Commands::Shell { profile, variables } => run_shell(profile, variables.load()?),
+            Commands::Batch {
+                manifest,
+                parallelism,
+                fail_fast,
+                variables,
+            } => {
+                let manifest = Manifest::load(&manifest)?;
+                let mut config = Config::load()?;
+                let patterns = manifest.patterns(&config);
+                merge_catalogs(
+                    &mut config,
+                    &patterns.iter().map(String::as_str).collect::<Vec<_>>(),
+                );
+                let parallelism = parallelism
+                    .or(manifest.parallelism)
+                    .unwrap_or(DEFAULT_PARALLELISM);
+                let reports = run_batch(
+                    &manifest,
+                    &config,
+                    &variables.load()?,
+                    parallelism,
+                    fail_fast,
+                );
+                print_batch_report(&reports)
+            }
+        }
+    }
+}
+
+impl Commands {
+    /// The display options given on the command line, if the command takes them.
+    fn display(&self) -> Option<Display> {
+        match self {
+            Commands::Processing(
+                ProcessingCommands::Download { defaults, .. }
+                | ProcessingCommands::Search { defaults, .. },
+            ) => Some(defaults.display.clone().into()),
+            Commands::Run { display, .. } => Some(display.clone().into()),
+            _ => None,
         }
     }
 }
 
 pub fn run() -> Result<()> {
     let args = CliArgs::parse();
+    init_logging(
+        args.verbosity.log_level_filter(),
+        args.log_format,
+        args.log_file.as_deref(),
+    )?;
+    if let Some(config) = args.config {
+        set_config_override(config);
+    }
+    set_output_mode(args.output);
+    // A config that cannot be read is reported by the command using it.
+    let mut display = Config::load()
+        .map(|config| config.output.display)
+        .unwrap_or_default();
+    if let Some(command_display) = args.command.display() {
+        display.update(command_display);
+    }
+    apply_display(&display)?;
     args.command.run()
 }
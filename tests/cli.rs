@@ -1,8 +1,9 @@
 #[cfg(test)]
 mod test {
     use assert_cmd::prelude::*; // Add methods on commands
+    use assert_fs::{prelude::*, TempDir};
     use predicates::prelude::*; // Used for writing assertions
-    use std::{env, process::Command}; // Run programs
+    use std::{env, process::Command, sync::OnceLock}; // Run programs
 
     #[macro_export]
     macro_rules! integration_test_files_path {
@@ -28,9 +29,40 @@ mod test {
         };
     }
 
+    /// An empty home shared by the tests, so that nothing is read from the user's
+    /// `~/.aws` or `~/.wdapty`, nor from a `.wdapty.toml` above the repo.
+    fn test_home() -> &'static TempDir {
+        static HOME: OnceLock<TempDir> = OnceLock::new();
+        HOME.get_or_init(|| TempDir::new().expect("should create test home"))
+    }
+
+    /// The binary with its config isolated from the user home.
+    fn wdapty_cmd() -> Result<Command, Box<dyn std::error::Error>> {
+        let mut cmd = Command::cargo_bin("wdapty")?;
+        let home = test_home();
+        cmd.env(
+            "WDAPTY_CONFIG",
+            integration_test_results_path!("cli_config.toml"),
+        )
+        .env("HOME", home.path())
+        .env("XDG_CONFIG_HOME", home.child(".config").path())
+        .current_dir(home.path());
+        Ok(cmd)
+    }
+
+    #[test]
+    fn credentials_read_from_test_home() -> Result<(), Box<dyn std::error::Error>> {
+        let mut cmd = wdapty_cmd()?;
+        cmd.args(["credentials", "list"]);
+        cmd.assert().success().stdout(predicate::str::contains(
+            "No profiles found in ~/.aws/credentials or ~/.aws/config",
+        ));
+        Ok(())
+    }
+
     #[test]
     fn file_doesnt_exist() -> Result<(), Box<dyn std::error::Error>> {
-        let mut cmd = Command::cargo_bin("wdapty")?;
+        let mut cmd = wdapty_cmd()?;
         cmd.arg("processing")
             .arg("search")
             .arg("--file-name")
@@ -50,7 +82,7 @@ mod test {
     fn file_exists() -> Result<(), Box<dyn std::error::Error>> {
         let test_file_path = integration_test_files_path!("test_file1.parq");
 
-        let mut cmd = Command::cargo_bin("wdapty")?;
+        let mut cmd = wdapty_cmd()?;
         cmd.arg("processing")
             .arg("search")
             .arg("--file-name")
@@ -69,7 +101,7 @@ mod test {
         let test_file_path = integration_test_files_path!("test_file1.parq");
         let output_file = integration_test_results_path!("test_with_output_file.csv");
 
-        let mut cmd = Command::cargo_bin("wdapty")?;
+        let mut cmd = wdapty_cmd()?;
         cmd.arg("processing")
             .arg("download")
             .arg("--file-name")
@@ -86,7 +118,7 @@ mod test {
         let test_file_path = integration_test_files_path!("test_file1.parq");
         let output_file = "random/path/that/doesnot/exist";
 
-        let mut cmd = Command::cargo_bin("wdapty")?;
+        let mut cmd = wdapty_cmd()?;
         cmd.arg("processing")
             .arg("download")
             .arg("--file-name")
@@ -105,7 +137,7 @@ mod test {
         let test_file_path = integration_test_files_path!("test_file1.parq");
         let cols = vec!["open".to_string(), "close".to_string()];
 
-        let mut cmd = Command::cargo_bin("wdapty")?;
+        let mut cmd = wdapty_cmd()?;
         cmd.arg("processing")
             .arg("search")
             .arg("--file-name")
@@ -126,7 +158,7 @@ mod test {
         let test_file_path = integration_test_files_path!("test_file1.parq");
         let cols = vec!["open".to_string(), "donotexist".to_string()];
 
-        let mut cmd = Command::cargo_bin("wdapty")?;
+        let mut cmd = wdapty_cmd()?;
         cmd.arg("processing")
             .arg("search")
             .arg("--file-name")
@@ -150,7 +182,7 @@ mod test {
         let index_name = "random-column";
         let index_value = "2024-02-01 18:13:00";
 
-        let mut cmd = Command::cargo_bin("wdapty")?;
+        let mut cmd = wdapty_cmd()?;
         cmd.arg("processing")
             .arg("search")
             .arg("--file-name")
@@ -172,7 +204,7 @@ mod test {
         let index_name = "random-column";
         let index_value = "testinvalidindexvalue";
 
-        let mut cmd = Command::cargo_bin("wdapty")?;
+        let mut cmd = wdapty_cmd()?;
         cmd.arg("processing")
             .arg("search")
             .arg("--file-name")
@@ -187,4 +219,795 @@ mod test {
 
         Ok(())
     }
+
+    #[test]
+    fn patterns_use_config_option() -> Result<(), Box<dyn std::error::Error>> {
+        let config_path = integration_test_results_path!("cli_config_option.toml");
+        let _ = std::fs::remove_file(&config_path);
+
+        let mut cmd = wdapty_cmd()?;
+        cmd.arg("patterns")
+            .arg("add")
+            .arg("--name")
+            .arg("events")
+            .arg("--value")
+            .arg("/data/my events/{date}.parq")
+            .arg("--config")
+            .arg(&config_path);
+        cmd.assert()
+            .success()
+            .stdout(predicate::str::contains(format!(
+                "Added pattern events to {}",
+                config_path
+            )));
+
+        let mut cmd = wdapty_cmd()?;
+        cmd.arg("--config")
+            .arg(&config_path)
+            .arg("patterns")
+            .arg("list");
+        cmd.assert().success().stdout(predicate::str::contains(
+            "Pattern events has value /data/my events/{date}.parq",
+        ));
+
+        let mut cmd = wdapty_cmd()?;
+        cmd.env("WDAPTY_CONFIG", &config_path)
+            .arg("patterns")
+            .arg("list");
+        cmd.assert()
+            .success()
+            .stdout(predicate::str::contains("Pattern events"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn patterns_layer_project_config() -> Result<(), Box<dyn std::error::Error>> {
+        let project = TempDir::new()?;
+        project
+            .child(".wdapty.toml")
+            .write_str("[patterns.project_events]\nvalue = \"data/{date}.parq\"\n")?;
+        let nested = project.child("nested/dir");
+        nested.create_dir_all()?;
+
+        let mut cmd = wdapty_cmd()?;
+        cmd.current_dir(nested.path()).arg("patterns").arg("list");
+        cmd.assert().success().stdout(predicate::str::contains(
+            "Pattern project_events has value data/{date}.parq",
+        ));
+
+        Ok(())
+    }
+
+    #[test]
+    fn pattern_variables_from_options() -> Result<(), Box<dyn std::error::Error>> {
+        let project = TempDir::new()?;
+        project
+            .child(".wdapty.toml")
+            .write_str("[patterns.fixture]\nvalue = \"{dir}/{file}.parq\"\n")?;
+        let resources_dir = integration_test_files_path!("");
+        let vars_file = project.child("vars.json");
+        vars_file.write_str(r#"{"file": "doesnotexist"}"#)?;
+
+        let mut cmd = wdapty_cmd()?;
+        cmd.current_dir(project.path())
+            .env("WDAPTY_VAR_FILE", "doesnotexist")
+            .arg("processing")
+            .arg("search")
+            .arg("--pattern")
+            .arg("fixture")
+            .arg("--var")
+            .arg(format!("dir={}", resources_dir.trim_end_matches('/')))
+            .arg("--var")
+            .arg("file=test_file1")
+            .arg("--vars-file")
+            .arg(vars_file.path())
+            .arg("--index-value")
+            .arg("2024-02-01 17:01:00")
+            .arg("--index-name")
+            .arg("transaction_time");
+        cmd.assert().success();
+
+        let mut cmd = wdapty_cmd()?;
+        cmd.current_dir(project.path())
+            .env("WDAPTY_VAR_FILE", "test_file1")
+            .arg("processing")
+            .arg("search")
+            .arg("--pattern")
+            .arg("fixture")
+            .arg("--var")
+            .arg(format!("dir={}", resources_dir.trim_end_matches('/')))
+            .arg("--index-value")
+            .arg("2024-02-01 17:01:00")
+            .arg("--index-name")
+            .arg("transaction_time");
+        cmd.assert().success();
+
+        Ok(())
+    }
+
+    #[test]
+    fn pattern_variables_missing() -> Result<(), Box<dyn std::error::Error>> {
+        let project = TempDir::new()?;
+        project
+            .child(".wdapty.toml")
+            .write_str("[patterns.fixture]\nvalue = \"{dir}/{file}/{date}.parq\"\n")?;
+
+        let mut cmd = wdapty_cmd()?;
+        cmd.current_dir(project.path())
+            .arg("processing")
+            .arg("download")
+            .arg("--pattern")
+            .arg("fixture")
+            .arg("--var")
+            .arg("dir=/data")
+            .arg("--output-file")
+            .arg("doesnotmatter.csv");
+        cmd.assert().failure().stderr(predicate::str::contains(
+            "Missing values for pattern variables: file, date",
+        ));
+
+        Ok(())
+    }
+
+    #[test]
+    fn pattern_variables_range() -> Result<(), Box<dyn std::error::Error>> {
+        let project = TempDir::new()?;
+        project
+            .child(".wdapty.toml")
+            .write_str("[patterns.fixture]\nvalue = \"{dir}/test_file{number}.parq\"\n")?;
+        let resources_dir = integration_test_files_path!("");
+
+        let mut cmd = wdapty_cmd()?;
+        cmd.current_dir(project.path())
+            .arg("processing")
+            .arg("search")
+            .arg("--pattern")
+            .arg("fixture")
+            .arg("--var")
+            .arg(format!("dir={}", resources_dir.trim_end_matches('/')))
+            .arg("--var")
+            .arg("number=1..2")
+            .arg("--index-value")
+            .arg("2024-02-01 17:01:00")
+            .arg("--index-name")
+            .arg("transaction_time");
+        cmd.assert()
+            .success()
+            .stderr(predicate::str::contains("WARN"))
+            .stderr(predicate::str::contains("Skipping"))
+            .stderr(predicate::str::contains("test_file2.parq"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn patterns_edit_rename_copy_show() -> Result<(), Box<dyn std::error::Error>> {
+        let config = TempDir::new()?;
+        let config_path = config.child("config.toml");
+        config_path.write_str("[patterns.events]\nvalue = \"/data/{date}.parq\"\n")?;
+        let patterns =
+            |args: &[&str]| -> Result<assert_cmd::assert::Assert, Box<dyn std::error::Error>> {
+                let mut cmd = wdapty_cmd()?;
+                cmd.env("WDAPTY_CONFIG", config_path.path())
+                    .arg("patterns")
+                    .args(args);
+                Ok(cmd.assert())
+            };
+
+        patterns(&[
+            "edit",
+            "--name",
+            "events",
+            "--value",
+            "/data/{env:enum(dev,prod)=dev}/{date:date}.parq",
+        ])?
+        .success()
+        .stdout(predicate::str::contains("Updated pattern events in"));
+        patterns(&["copy", "--name", "events", "--new-name", "events_copy"])?
+            .success()
+            .stdout(predicate::str::contains(
+                "Copied pattern events to events_copy in",
+            ));
+        patterns(&["rename", "--name", "events", "--new-name", "renamed"])?
+            .success()
+            .stdout(predicate::str::contains(
+                "Renamed pattern events to renamed in",
+            ));
+        patterns(&["rename", "--name", "events", "--new-name", "other"])?
+            .failure()
+            .stderr(predicate::str::contains("No pattern named events"));
+        patterns(&["copy", "--name", "renamed", "--new-name", "events_copy"])?
+            .failure()
+            .stderr(predicate::str::contains("already in config"));
+
+        patterns(&["show", "events_copy"])?
+            .success()
+            .stdout(predicate::str::contains(
+                "Pattern events_copy has value /data/{env:enum(dev,prod)=dev}/{date:date}.parq",
+            ))
+            .stdout(predicate::str::contains(
+                "env (one of dev|prod, default dev)",
+            ))
+            .stdout(predicate::str::contains("date (date YYYY-MM-DD)"));
+        patterns(&["list"])?
+            .success()
+            .stdout(predicate::str::contains("Pattern renamed"))
+            .stdout(predicate::str::contains("Pattern events ").not());
+
+        Ok(())
+    }
+
+    #[test]
+    fn patterns_test() -> Result<(), Box<dyn std::error::Error>> {
+        let project = TempDir::new()?;
+        project
+            .child(".wdapty.toml")
+            .write_str("[patterns.fixture]\nvalue = \"{dir}/test_file{number}.parq\"\n")?;
+        let resources_dir = integration_test_files_path!("");
+        let dir = format!("dir={}", resources_dir.trim_end_matches('/'));
+
+        let mut cmd = wdapty_cmd()?;
+        cmd.current_dir(project.path()).args([
+            "patterns", "test", "fixture", "--var", &dir, "--var", "number=1",
+        ]);
+        cmd.assert()
+            .success()
+            .stdout(predicate::str::contains("test_file1.parq exists"));
+
+        let mut cmd = wdapty_cmd()?;
+        cmd.current_dir(project.path()).args([
+            "patterns",
+            "test",
+            "fixture",
+            "--var",
+            &dir,
+            "--var",
+            "number=1..2",
+        ]);
+        cmd.assert()
+            .failure()
+            .stdout(predicate::str::contains("test_file2.parq not found"))
+            .stderr(predicate::str::contains(
+                "1 of 2 paths for pattern fixture do not exist",
+            ));
+
+        Ok(())
+    }
+
+    #[test]
+    fn patterns_remove_and_restore() -> Result<(), Box<dyn std::error::Error>> {
+        let config = TempDir::new()?;
+        let config_path = config.child("config.toml");
+        config_path.write_str(
+            "[patterns.log]\nvalue = \"/data/log.parq\"\n\n[patterns.access_log]\nvalue = \"/data/log=1.parq\"\n",
+        )?;
+        let wdapty =
+            |args: &[&str]| -> Result<assert_cmd::assert::Assert, Box<dyn std::error::Error>> {
+                let mut cmd = wdapty_cmd()?;
+                cmd.env("WDAPTY_CONFIG", config_path.path()).args(args);
+                Ok(cmd.assert())
+            };
+
+        wdapty(&["patterns", "remove", "--name", "log"])?
+            .failure()
+            .stderr(predicate::str::contains(
+                "Pattern log with value /data/log.parq will be removed",
+            ))
+            .stderr(predicate::str::contains("pass --yes"));
+        wdapty(&["patterns", "remove", "--name", "log", "--yes"])?.success();
+        wdapty(&["patterns", "list"])?
+            .success()
+            .stdout(predicate::str::contains("Pattern access_log"))
+            .stdout(predicate::str::contains("Pattern log ").not());
+
+        wdapty(&["config", "backups"])?
+            .success()
+            .stdout(predicate::str::contains("config-"));
+        wdapty(&["config", "restore"])?
+            .success()
+            .stdout(predicate::str::contains("Restored"));
+        wdapty(&["patterns", "list"])?
+            .success()
+            .stdout(predicate::str::contains(
+                "Pattern log has value /data/log.parq",
+            ));
+
+        Ok(())
+    }
+
+    #[test]
+    fn patterns_export_import_and_catalogs() -> Result<(), Box<dyn std::error::Error>> {
+        let dir = TempDir::new()?;
+        let source = dir.child("source.toml");
+        source.write_str(
+            "[patterns.events]\nvalue = \"/data/events/{date}.parq\"\n\n[patterns.logs]\nvalue = \"/data/logs.parq\"\n",
+        )?;
+        let target = dir.child("target.toml");
+        target.write_str("[patterns.events]\nvalue = \"/old/events.parq\"\n")?;
+
+        let mut cmd = wdapty_cmd()?;
+        cmd.env("WDAPTY_CONFIG", source.path())
+            .args(["patterns", "export", "--format", "json"]);
+        let exported = cmd.assert().success().get_output().stdout.clone();
+        let exported_file = dir.child("exported.json");
+        exported_file.write_binary(&exported)?;
+
+        let mut cmd = wdapty_cmd()?;
+        cmd.env("WDAPTY_CONFIG", target.path())
+            .arg("patterns")
+            .arg("import")
+            .arg(exported_file.path());
+        cmd.assert()
+            .success()
+            .stdout(predicate::str::contains("Added pattern logs"))
+            .stdout(predicate::str::contains(
+                "Conflict on pattern events: kept /old/events.parq",
+            ));
+
+        let mut cmd = wdapty_cmd()?;
+        cmd.env("WDAPTY_CONFIG", target.path())
+            .arg("patterns")
+            .arg("import")
+            .arg(exported_file.path())
+            .arg("--replace");
+        cmd.assert().success().stdout(predicate::str::contains(
+            "Replaced pattern events value /old/events.parq with /data/events/{date}.parq",
+        ));
+
+        let catalog_user = dir.child("catalog_user.toml");
+        catalog_user.write_str(&format!(
+            "[patterns.events]\nvalue = \"/mine.parq\"\n\n[catalogs.team]\nsource = {:?}\n\n[catalogs.broken]\nsource = \"/does/not/exist.toml\"\n",
+            source.path().display().to_string()
+        ))?;
+        let mut cmd = wdapty_cmd()?;
+        cmd.env("WDAPTY_CONFIG", catalog_user.path())
+            .args(["patterns", "list"]);
+        cmd.assert()
+            .success()
+            .stdout(predicate::str::contains(
+                "Pattern events has value /mine.parq",
+            ))
+            .stdout(predicate::str::contains(
+                "Pattern logs has value /data/logs.parq",
+            ))
+            .stderr(predicate::str::contains("Skipping catalog broken"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn pattern_defaults() -> Result<(), Box<dyn std::error::Error>> {
+        let project = TempDir::new()?;
+        project.child(".wdapty.toml").write_str(&format!(
+            "[patterns.fixture]\nvalue = {:?}\ncols = [\"open\", \"close\"]\nindex_name = \"transaction_time\"\nsort = [\"open:desc\"]\n\n[patterns.bare]\nvalue = {:?}\n",
+            integration_test_files_path!("test_file1.parq"),
+            integration_test_files_path!("test_file1.parq"),
+        ))?;
+        let search = |pattern: &str, extra: &[&str]| -> Result<_, Box<dyn std::error::Error>> {
+            let mut cmd = wdapty_cmd()?;
+            cmd.current_dir(project.path())
+                .args(["processing", "search", "--pattern", pattern])
+                .args(["--index-value", "2024-02-01 17:01:00"])
+                .args(extra);
+            Ok(cmd.assert())
+        };
+
+        search("fixture", &[])?
+            .success()
+            .stdout(predicate::str::contains("open"))
+            .stdout(predicate::str::contains("close"))
+            .stdout(predicate::str::contains("transaction_time").not());
+        search("fixture", &["--cols", "close"])?
+            .success()
+            .stdout(predicate::str::contains("close"))
+            .stdout(predicate::str::contains("open").not());
+        search("bare", &[])?
+            .failure()
+            .stderr(predicate::str::contains(
+                "Search needs --index-name or an index_name default on the pattern",
+            ));
+
+        let mut cmd = wdapty_cmd()?;
+        cmd.current_dir(project.path())
+            .args(["patterns", "show", "fixture"]);
+        cmd.assert()
+            .success()
+            .stdout(predicate::str::contains("cols: open close"))
+            .stdout(predicate::str::contains("sort: open:desc"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn saved_queries() -> Result<(), Box<dyn std::error::Error>> {
+        let dir = TempDir::new()?;
+        let config = dir.child("config.toml");
+        config.write_str(&format!(
+            "[patterns.fixture]\nvalue = \"{{dir}}/test_file{{number}}.parq\"\nindex_name = \"transaction_time\"\n\n[queries.latest]\npattern = \"fixture\"\nindex_value = \"{{time}}\"\ncols = [\"open\", \"close\"]\nlimit = 1\noutput_file = {:?}\n",
+            format!("{}/result_{{number}}.csv", dir.path().display()),
+        ))?;
+        let resources_dir = integration_test_files_path!("");
+        let wdapty = |args: &[&str]| -> Result<_, Box<dyn std::error::Error>> {
+            let mut cmd = wdapty_cmd()?;
+            cmd.env("WDAPTY_CONFIG", config.path()).args(args);
+            Ok(cmd.assert())
+        };
+
+        wdapty(&["queries", "list"])?
+            .success()
+            .stdout(predicate::str::contains(
+                "Query latest searches fixture for {time}",
+            ));
+        wdapty(&["queries", "show", "latest"])?
+            .success()
+            .stdout(predicate::str::contains("limit: 1"))
+            .stdout(predicate::str::contains(
+                "Placeholders:\n  dir\n  number\n  time\n",
+            ));
+
+        let mut cmd = wdapty_cmd()?;
+        cmd.env("WDAPTY_CONFIG", config.path())
+            .args(["run", "latest", "--var", "number=1"])
+            .arg("--var")
+            .arg(format!("dir={}", resources_dir.trim_end_matches('/')))
+            .args(["--var", "time=2024-02-01 17:01:00"]);
+        cmd.assert()
+            .success()
+            .stdout(predicate::str::contains("Results are available in"));
+        let result = std::fs::read_to_string(dir.child("result_1.csv").path())?;
+        assert_eq!(result.lines().next(), Some("open,close"));
+        assert!(result.lines().count() <= 2);
+
+        wdapty(&["run", "latest", "--var", "number=1"])?
+            .failure()
+            .stderr(predicate::str::contains(
+                "Missing values for pattern variables: dir, time",
+            ));
+        let opens = format!("{}/opens.csv", dir.path().display());
+        wdapty(&["queries", "add", "opens", "--pattern", "nope"])?
+            .failure()
+            .stderr(predicate::str::contains("No pattern named nope"));
+        wdapty(&[
+            "queries",
+            "add",
+            "opens",
+            "--pattern",
+            "fixture",
+            "--filter",
+            "open ~ {min}",
+        ])?
+        .failure()
+        .stderr(predicate::str::contains("Invalid filter of query opens"));
+        wdapty(&[
+            "queries",
+            "add",
+            "opens",
+            "--pattern",
+            "fixture",
+            "--filter",
+            "open > {min}",
+            "--cols",
+            "open",
+            "--output-file",
+            &opens,
+        ])?
+        .success()
+        .stdout(predicate::str::contains("Added query opens"));
+        wdapty(&["queries", "show", "opens"])?
+            .success()
+            .stdout(predicate::str::contains("filters: open > {min}"));
+        let mut cmd = wdapty_cmd()?;
+        cmd.env("WDAPTY_CONFIG", config.path())
+            .args(["run", "opens", "--var", "number=1", "--var", "min=1e12"])
+            .arg("--var")
+            .arg(format!("dir={}", resources_dir.trim_end_matches('/')));
+        cmd.assert().success();
+        let result = std::fs::read_to_string(&opens)?;
+        assert_eq!(result.lines().collect::<Vec<_>>(), vec!["open"]);
+
+        wdapty(&["queries", "remove", "latest", "--yes"])?
+            .success()
+            .stdout(predicate::str::contains("Removed query latest"));
+        wdapty(&["run", "latest"])?
+            .failure()
+            .stderr(predicate::str::contains("No query named latest in config"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn batch_manifest() -> Result<(), Box<dyn std::error::Error>> {
+        let dir = TempDir::new()?;
+        let manifest = dir.child("manifest.toml");
+        manifest.write_str(&format!(
+            "[vars]\nnumber = \"1\"\n\n[[jobs]]\nname = \"missing\"\nfile_name = \"/does/not/exist.parq\"\noutput_file = \"missing.csv\"\n\n[[jobs]]\nname = \"opens\"\nfile_name = {:?}\ncols = [\"open\"]\nlimit = 3\noutput_file = {:?}\n\n[[jobs]]\nname = \"totals\"\nfile_name = {:?}\nfilters = [\"open > 0\"]\naggregations = [\"count()\", \"max(open)\"]\noutput_file = {:?}\n",
+            format!("{}test_file{{number}}.parq", integration_test_files_path!("")),
+            format!("{}/opens_{{number}}.csv", dir.path().display()),
+            format!("{}test_file{{number}}.parq", integration_test_files_path!("")),
+            format!("{}/totals_{{number}}.csv", dir.path().display()),
+        ))?;
+
+        let mut cmd = wdapty_cmd()?;
+        cmd.arg("batch").arg(manifest.path()).args(["-j", "2"]);
+        cmd.assert()
+            .failure()
+            .stdout(predicate::str::is_match(r"opens\s+succeeded\s+3\s")?)
+            .stdout(predicate::str::is_match(r"missing\s+failed\s+-\s")?)
+            .stdout(predicate::str::is_match(r"totals\s+succeeded\s+1\s")?)
+            .stdout(predicate::str::contains(
+                "3 jobs: 2 succeeded, 1 failed, 0 skipped",
+            ))
+            .stderr(predicate::str::contains("1 of 3 jobs failed"));
+        assert!(dir.child("opens_1.csv").path().exists());
+        let totals = std::fs::read_to_string(dir.child("totals_1.csv").path())?;
+        assert_eq!(totals.lines().next(), Some("count,open_max"));
+
+        let mut cmd = wdapty_cmd()?;
+        cmd.arg("batch")
+            .arg(manifest.path())
+            .args(["-j", "1", "--fail-fast", "--var", "number=2"]);
+        cmd.assert()
+            .failure()
+            .stdout(predicate::str::is_match(r"opens\s+skipped")?);
+        assert!(!dir.child("opens_2.csv").path().exists());
+
+        Ok(())
+    }
+
+    #[test]
+    fn shell_session() -> Result<(), Box<dyn std::error::Error>> {
+        let dir = TempDir::new()?;
+        let output_file = dir.child("top.csv");
+        let commands = format!(
+            "open {}\nschema\nselect open close\nsort open:desc\nfilter open > 0\nselect missing\nundo\nhead 2\nsave {}\nexit\nhead\n",
+            integration_test_files_path!("test_file1.parq"),
+            output_file.path().display()
+        );
+
+        let mut cmd = assert_cmd::Command::from_std(wdapty_cmd()?);
+        cmd.arg("shell").write_stdin(commands);
+        cmd.assert()
+            .success()
+            .stdout(predicate::str::contains("Opened"))
+            .stdout(predicate::str::contains("transaction_time: datetime"))
+            .stdout(predicate::str::contains("shape: (2, 2)"))
+            .stdout(predicate::str::contains("Saved"))
+            .stderr(predicate::str::contains("Error:"))
+            .stderr(predicate::str::contains("missing"));
+        let saved = std::fs::read_to_string(output_file.path())?;
+        assert_eq!(saved.lines().next(), Some("open,close"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn view_without_terminal() -> Result<(), Box<dyn std::error::Error>> {
+        let mut cmd = wdapty_cmd()?;
+        cmd.args(["processing", "search", "--file-name"])
+            .arg(integration_test_files_path!("test_file1.parq"))
+            .args(["--index-name", "transaction_time"])
+            .args(["--index-value", "2024-02-01 17:01:00"])
+            .args(["--cols", "open", "close", "--limit", "1", "--view"]);
+        cmd.assert()
+            .success()
+            .stdout(predicate::str::contains("shape: (1, 2)"))
+            .stderr(predicate::str::contains("needs a terminal"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn display_options() -> Result<(), Box<dyn std::error::Error>> {
+        let search = |options: &[&str]| -> Result<Command, Box<dyn std::error::Error>> {
+            let mut cmd = wdapty_cmd()?;
+            cmd.args(["processing", "search", "--file-name"])
+                .arg(integration_test_files_path!("test_file1.parq"))
+                .args(["--index-name", "transaction_time"])
+                .args(["--index-value", "2024-02-01 17:01:00"])
+                .args(["--cols", "open", "close", "--limit", "1"])
+                .args(options);
+            Ok(cmd)
+        };
+
+        search(&["--table-style", "markdown"])?
+            .assert()
+            .success()
+            .stdout(predicate::str::contains("| open"))
+            .stdout(predicate::str::contains("shape:").not());
+        search(&["--no-header", "--table-style", "ascii"])?
+            .assert()
+            .success()
+            .stdout(predicate::str::contains("open").not())
+            .stdout(predicate::str::contains("shape: (1, 2)"));
+        search(&["--table-style", "fancy"])?.assert().failure();
+
+        Ok(())
+    }
+
+    #[test]
+    fn machine_readable_output() -> Result<(), Box<dyn std::error::Error>> {
+        let dir = TempDir::new()?;
+        let config_path = dir.child("config.toml");
+        config_path.write_str("[patterns.events]\nvalue = \"/data/events.parq\"\n")?;
+
+        let mut cmd = wdapty_cmd()?;
+        cmd.env("WDAPTY_CONFIG", config_path.path())
+            .args(["--output", "json", "patterns", "list"]);
+        let output = cmd.assert().success().get_output().stdout.clone();
+        let patterns: serde_json::Value = serde_json::from_slice(&output)?;
+        assert_eq!(
+            patterns,
+            serde_json::json!([{ "name": "events", "value": "/data/events.parq" }])
+        );
+
+        let mut cmd = wdapty_cmd()?;
+        cmd.args([
+            "-v",
+            "processing",
+            "search",
+            "--output",
+            "ndjson",
+            "--file-name",
+        ])
+        .arg(integration_test_files_path!("test_file1.parq"))
+        .args(["--index-name", "transaction_time"])
+        .args(["--index-value", "2024-02-01 17:01:00"])
+        .args(["--cols", "open", "close"]);
+        let assert = cmd
+            .assert()
+            .success()
+            .stderr(predicate::str::contains("Preparing for Search Command"));
+        let stdout = String::from_utf8(assert.get_output().stdout.clone())?;
+        assert_eq!(stdout.lines().count(), 1);
+        for line in stdout.lines() {
+            let row: serde_json::Value = serde_json::from_str(line)?;
+            assert!(row.get("open").is_some() && row.get("close").is_some());
+        }
+
+        let output_file = dir.child("out.csv");
+        let mut cmd = wdapty_cmd()?;
+        cmd.args(["--output", "csv", "processing", "search", "--file-name"])
+            .arg(integration_test_files_path!("test_file1.parq"))
+            .args(["--index-name", "transaction_time"])
+            .args(["--index-value", "2024-02-01 17:01:00"])
+            .args(["--cols", "open", "--output-file"])
+            .arg(output_file.path());
+        cmd.assert()
+            .success()
+            .stdout(predicate::str::starts_with("output_file,rows\n"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn explain_and_dry_run() -> Result<(), Box<dyn std::error::Error>> {
+        let dir = TempDir::new()?;
+        let output_file = dir.child("out.csv");
+        let search = |option: &str| -> Result<Command, Box<dyn std::error::Error>> {
+            let mut cmd = wdapty_cmd()?;
+            cmd.args(["processing", "search", option, "--file-name"])
+                .arg(integration_test_files_path!("test_file1.parq"))
+                .args(["--index-name", "transaction_time"])
+                .args(["--index-value", "2024-02-01 17:01:00"])
+                .args(["--cols", "open", "close", "--output-file"])
+                .arg(output_file.path());
+            Ok(cmd)
+        };
+
+        search("--explain")?
+            .assert()
+            .success()
+            .stdout(predicate::str::contains("SCAN"))
+            .stdout(predicate::str::contains(
+                "Pushed down into the parquet scans:",
+            ))
+            .stdout(predicate::str::contains("transaction_time"));
+        search("--dry-run")?
+            .assert()
+            .success()
+            .stdout(predicate::str::contains("no rows read"))
+            .stdout(predicate::str::contains("test_file1.parq"))
+            .stdout(predicate::str::contains(
+                "Filter: transaction_time = 2024-02-01 17:01:00",
+            ))
+            .stdout(predicate::str::contains("Columns: open, close"));
+        output_file.assert(predicate::path::missing());
+
+        let mut cmd = wdapty_cmd()?;
+        cmd.args(["--output", "json", "processing", "download", "--dry-run"])
+            .arg("--file-name")
+            .arg(integration_test_files_path!("test_file1.parq"))
+            .arg("--output-file")
+            .arg(output_file.path());
+        let output = cmd.assert().success().get_output().stdout.clone();
+        let dry_run: serde_json::Value = serde_json::from_slice(&output)?;
+        assert!(dry_run["filter"].is_null());
+        assert!(dry_run["credentials"].is_null());
+        assert!(dry_run["output"]
+            .as_str()
+            .unwrap()
+            .ends_with("out.csv as csv"));
+
+        search("--explain")?.arg("--dry-run").assert().failure();
+
+        Ok(())
+    }
+
+    #[test]
+    fn run_stats() -> Result<(), Box<dyn std::error::Error>> {
+        let mut cmd = wdapty_cmd()?;
+        cmd.args(["processing", "search", "--stats", "--file-name"])
+            .arg(integration_test_files_path!("test_file1.parq"))
+            .args(["--index-name", "transaction_time"])
+            .args(["--index-value", "2024-02-01 17:01:00"])
+            .args(["--cols", "open"]);
+        cmd.assert()
+            .success()
+            .stderr(predicate::str::contains("Rows: 1 matched"))
+            .stderr(predicate::str::contains("1 row groups read"))
+            .stderr(predicate::str::contains("Time: scan "));
+
+        let dir = TempDir::new()?;
+        let output_file = dir.child("out.csv");
+        let mut cmd = wdapty_cmd()?;
+        cmd.args(["processing", "download", "--stats", "json", "--limit", "2"])
+            .arg("--file-name")
+            .arg(integration_test_files_path!("test_file1.parq"))
+            .arg("--output-file")
+            .arg(output_file.path());
+        let assert = cmd.assert().success();
+        let stderr = String::from_utf8(assert.get_output().stderr.clone())?;
+        let stats: serde_json::Value = serde_json::from_str(stderr.lines().last().unwrap())?;
+        assert_eq!(stats["rows_matched"], 2);
+        assert_eq!(stats["row_groups_skipped_estimate"], 0);
+        assert!(stats["bytes_read_local_estimate"].as_u64().unwrap() > 0);
+        assert_eq!(
+            stats["output_size"].as_u64(),
+            Some(std::fs::metadata(output_file.path())?.len())
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn logging_levels() -> Result<(), Box<dyn std::error::Error>> {
+        let search = |options: &[&str]| -> Result<Command, Box<dyn std::error::Error>> {
+            let mut cmd = wdapty_cmd()?;
+            cmd.args(options)
+                .args(["processing", "search", "--file-name"])
+                .arg(integration_test_files_path!("test_file1.parq"))
+                .args(["--index-name", "transaction_time"])
+                .args(["--index-value", "2024-02-01 17:01:00"]);
+            Ok(cmd)
+        };
+
+        search(&[])?
+            .assert()
+            .success()
+            .stderr(predicate::str::contains("INFO").not());
+        search(&["-v"])?
+            .assert()
+            .success()
+            .stderr(predicate::str::contains("INFO  file_processing"))
+            .stderr(predicate::str::contains("Collected 1 rows"))
+            .stderr(predicate::str::contains("Query plan").not());
+
+        let dir = TempDir::new()?;
+        let log_file = dir.child("wdapty.log");
+        let log_path = log_file.path().display().to_string();
+        search(&["-vv", "--log-format", "json", "--log-file", &log_path])?
+            .assert()
+            .success()
+            .stderr(predicate::str::contains("DEBUG").not());
+        let content = std::fs::read_to_string(log_file.path())?;
+        let lines = content
+            .lines()
+            .map(serde_json::from_str)
+            .collect::<Result<Vec<serde_json::Value>, _>>()?;
+        assert!(lines.iter().any(|line| line["level"] == "DEBUG"
+            && line["message"].as_str().unwrap().starts_with("Query plan")));
+
+        Ok(())
+    }
 }